@@ -0,0 +1,1422 @@
+#![deny(
+    missing_debug_implementations,
+    rust_2018_idioms,
+    clippy::complexity,
+    clippy::correctness
+)]
+#![warn(clippy::perf, clippy::pedantic)]
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Mutex;
+
+use balatro_mod_index::{forge, mods::ModIndex};
+use clap::{Parser, Subcommand};
+use serde_json::json;
+
+use bmm_lib::{
+    cache, conflicts,
+    database::Database,
+    dependencies,
+    download::{DownloadJob, DownloadProgress},
+    errors::AppError,
+    finder::is_valid_game_dir,
+    game_source, game_version, installer,
+    integrity::{self, IntegrityStatus},
+    launcher, local_mod_detection,
+    lockfile::{self, Lockfile},
+    lovely::{self, LovelyStatus},
+    lovely_patches::{self, ConflictKind},
+    mod_collections,
+    operations::{self, AffectedMod, OperationKind, Outcome},
+    patch_preview,
+    paths::{Paths, PathsOrigin},
+    pins, settings,
+    updates::{self, LatestReleases},
+    version_history,
+};
+
+/// Manage Balatro mods without the GUI. `bmm` shares bromomethane's database
+/// and Mods directory, so changes made here show up in the app and vice versa.
+#[derive(Debug, Parser)]
+#[command(name = "bmm", version, about)]
+struct Cli {
+    /// Print JSON instead of plain text for commands that produce listings
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Keep the database, caches and logs under this directory instead of
+    /// the usual ones, as in portable mode. Defaults to `$BMM_HOME`
+    #[arg(long, global = true, value_name = "DIR")]
+    home: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List installed mods
+    List {
+        /// List the mods available in the index instead
+        #[arg(long)]
+        available: bool,
+    },
+    /// Install a mod by its index id or title along with its missing
+    /// dependencies, or from a direct download URL
+    Install {
+        /// Index id, title or URL of the mod
+        target: String,
+        /// Name of the directory the mod is extracted to, implies --no-deps
+        #[arg(long)]
+        folder_name: Option<String>,
+        /// Don't install missing dependencies
+        #[arg(long)]
+        no_deps: bool,
+        /// Install even if the mod conflicts with an enabled mod
+        #[arg(long)]
+        force: bool,
+    },
+    /// Uninstall a tracked mod
+    Uninstall {
+        name: String,
+        /// Also uninstall every mod that depends on it
+        #[arg(long)]
+        cascade: bool,
+    },
+    /// Enable a tracked mod
+    Enable {
+        name: String,
+        /// Enable even if the mod conflicts with another enabled mod or
+        /// doesn't support the installed game version
+        #[arg(long)]
+        force: bool,
+    },
+    /// Disable a tracked mod without uninstalling it
+    Disable { name: String },
+    /// Forget tracked mods whose directory no longer exists
+    Reindex,
+    /// Show what was installed, removed, enabled or disabled, newest first
+    Log {
+        /// Page to show, starting at 1
+        #[arg(long, default_value_t = 1)]
+        page: u32,
+        /// Only show operations that touched this mod
+        #[arg(long = "mod")]
+        mod_name: Option<String>,
+    },
+    /// List mods in the Mods directory that weren't installed by bromomethane
+    DetectLocal,
+    /// Show which game files each mod's lovely patches touch and where the
+    /// enabled mods' patches overlap
+    Patches,
+    /// Show what the enabled mods' lovely patches do to the game's Lua
+    /// sources as unified diffs, without launching the game
+    PatchPreview {
+        /// Only preview these files, such as `game.lua`
+        targets: Vec<String>,
+        /// Directory with the game's Lua sources, defaults to the sources
+        /// extracted from the game
+        #[arg(long)]
+        source: Option<PathBuf>,
+    },
+    /// Extract the game's Lua sources from the game, or list the releases
+    /// already extracted
+    #[command(subcommand)]
+    Source(SourceCommand),
+    /// Set the path of the Balatro installation to manage
+    SetPath { path: PathBuf },
+    /// Manage the copies of the game bromomethane manages, each with its own
+    /// Mods directory and installed mods
+    #[command(subcommand)]
+    Installation(InstallationCommand),
+    /// Show, export or import bromomethane's settings
+    #[command(subcommand)]
+    Settings(SettingsCommand),
+    /// Manage profiles, named sets of enabled mods
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+    /// Write the tracked mods, their versions and archive hashes to a lockfile
+    Export { file: PathBuf },
+    /// Install exactly the mods and versions recorded in a lockfile
+    Import { file: PathBuf },
+    /// Check tracked mods against the files recorded when they were installed
+    Verify {
+        /// Only check these mods
+        names: Vec<String>,
+        /// Reinstall damaged mods from the archive they were installed from
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Update tracked mods, Steamodded and Talisman to their latest versions
+    Update {
+        /// Only update these mods
+        names: Vec<String>,
+        /// Show what would be updated without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Hold a mod, Steamodded or Talisman at a version or range, or list the
+    /// pins when no name is given
+    Pin {
+        name: Option<String>,
+        /// Version or range such as `>=1.0, <<2.0`, defaults to the installed
+        /// version
+        constraint: Option<String>,
+        /// Why the mod is pinned, shown when an update is held back
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Let a pinned mod update again
+    Unpin { name: String },
+    /// List the remembered versions of a tracked mod, newest first
+    History { name: String },
+    /// Reinstall an earlier version of a tracked mod
+    Rollback {
+        name: String,
+        /// Version or archive hash prefix to go back to, defaults to the
+        /// version installed before the current one
+        version: Option<String>,
+    },
+    /// Launch Balatro with lovely
+    Launch {
+        /// Launch without loading any mods
+        #[arg(long)]
+        vanilla: bool,
+        /// Launch even if enabled mods don't support the installed game
+        /// version
+        #[arg(long)]
+        force: bool,
+    },
+    /// Show the installed game version, whether it changed since the last
+    /// launch, and the enabled mods that don't support it
+    GameVersion,
+    /// List, check or install releases of the lovely injector
+    #[command(subcommand)]
+    Lovely(LovelyCommand),
+}
+
+#[derive(Debug, Subcommand)]
+enum InstallationCommand {
+    /// List installations, marking the active one
+    List,
+    /// Add a copy of the game; it doesn't become active until switched to
+    Add {
+        name: String,
+        game_dir: PathBuf,
+        /// Point lovely at this Mods directory instead of the usual one
+        #[arg(long)]
+        mods_dir: Option<PathBuf>,
+    },
+    /// Make an installation the one every other command acts on
+    Switch {
+        name: String,
+    },
+    Rename {
+        name: String,
+        new_name: String,
+    },
+    /// Give an installation its own Mods directory, or put it back on the
+    /// usual one when no directory is given
+    ModsDir {
+        name: String,
+        mods_dir: Option<PathBuf>,
+    },
+    /// Forget an installation and the mods tracked in it, leaving its files
+    Remove {
+        name: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Subcommand)]
+enum SourceCommand {
+    /// Extract the Lua sources of the game and print where they are
+    Extract,
+    /// List the releases whose sources have been extracted
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+enum LovelyCommand {
+    /// List the lovely releases with a build for this platform
+    List,
+    /// Show which lovely is installed and whether it's unchanged
+    Status,
+    /// Install a lovely release, the latest one when no tag is given
+    Install { tag: Option<String> },
+}
+
+#[derive(Debug, Subcommand)]
+enum SettingsCommand {
+    /// Print the current settings
+    Show,
+    /// Write the settings to a JSON file
+    Export { file: PathBuf },
+    /// Apply the settings from a JSON file written by `bmm settings export`
+    Import { file: PathBuf },
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfileCommand {
+    /// List profiles
+    List,
+    /// Create a profile from the mods that are enabled right now
+    Create {
+        name: String,
+    },
+    /// Overwrite a profile with the mods that are enabled right now
+    Save {
+        name: String,
+    },
+    /// Enable exactly the mods of a profile
+    Switch {
+        name: String,
+        /// Switch even if mods in the profile conflict with each other or
+        /// don't support the installed game version
+        #[arg(long)]
+        force: bool,
+    },
+    Rename {
+        name: String,
+        new_name: String,
+    },
+    Clone {
+        name: String,
+        new_name: String,
+    },
+    Delete {
+        name: String,
+    },
+}
+
+/// Fetches the index as `(id, catalog entry)` pairs. Only the fields the
+/// resolver and the listings need are filled in.
+async fn fetch_catalog() -> Result<Vec<(String, cache::Mod)>, AppError> {
+    let client = reqwest::Client::new();
+    let index = ModIndex::from_reqwest(&client, <&forge::Tree<'_>>::default())
+        .await
+        .map_err(|e| AppError::Network(format!("Failed to fetch the mod index: {e}")))?;
+
+    Ok(index
+        .mods
+        .into_iter()
+        .map(|(id, m)| {
+            let catalog_mod = cache::Mod {
+                title: m.meta.title,
+                description: String::new(),
+                image: None,
+                categories: HashSet::new(),
+                colors: cache::ColorPair {
+                    color1: String::new(),
+                    color2: String::new(),
+                },
+                installed: false,
+                requires_steamodded: m.meta.requires_steamodded,
+                requires_talisman: m.meta.requires_talisman,
+                publisher: String::new(),
+                repo: String::new(),
+                download_url: m.meta.download_url,
+                folder_name: m.meta.folder_name,
+                version: Some(m.meta.version),
+                last_updated: 0,
+            };
+            (id.as_str().to_string(), catalog_mod)
+        })
+        .collect())
+}
+
+/// A download job that draws a progress line on stderr, and that Ctrl-C
+/// cancels so the partial download is cleaned up.
+fn download_job() -> DownloadJob {
+    let job = DownloadJob::new("bmm").on_progress(print_progress);
+    let cancel = job.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            cancel.cancel();
+        }
+    });
+    job
+}
+
+fn print_progress(progress: &DownloadProgress) {
+    let kib = |bytes: u64| bytes / 1024;
+    let total = progress
+        .total
+        .map_or_else(|| "?".to_string(), |total| kib(total).to_string());
+    eprint!(
+        "\r{} / {total} KiB ({} KiB/s)  ",
+        kib(progress.downloaded),
+        kib(progress.bytes_per_second)
+    );
+    if progress.total == Some(progress.downloaded) {
+        eprintln!();
+    }
+}
+
+fn print_installed(db: &Database, as_json: bool) -> Result<(), String> {
+    let installed_mods = db.get_installed_mods()?;
+
+    if as_json {
+        let mods = installed_mods
+            .iter()
+            .map(|m| {
+                json!({
+                    "name": m.name,
+                    "version": m.current_version,
+                    "enabled": installer::is_mod_enabled(PathBuf::from(&m.path).as_path()),
+                    "path": m.path,
+                    "dependencies": m.dependencies,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::Value::Array(mods));
+        return Ok(());
+    }
+
+    for m in &installed_mods {
+        let state = if installer::is_mod_enabled(PathBuf::from(&m.path).as_path()) {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        println!(
+            "{:<32} {:<20} {:<8} {}",
+            m.name,
+            m.current_version.as_deref().unwrap_or("-"),
+            state,
+            m.path
+        );
+    }
+    Ok(())
+}
+
+async fn print_available(as_json: bool) -> Result<(), String> {
+    let catalog = fetch_catalog().await?;
+
+    if as_json {
+        let mods = catalog
+            .iter()
+            .map(|(id, m)| {
+                json!({
+                    "id": id,
+                    "title": m.title,
+                    "version": m.version,
+                    "download_url": m.download_url,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::Value::Array(mods));
+        return Ok(());
+    }
+
+    for (id, m) in &catalog {
+        println!(
+            "{:<40} {:<32} {}",
+            id,
+            m.title,
+            m.version.as_deref().unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+async fn install_from_url(
+    db: &Database,
+    url: &str,
+    folder_name: Option<String>,
+    force: bool,
+) -> Result<(), String> {
+    let mods_dir = db.get_mods_dir()?;
+    let fallback_name = folder_name.clone().unwrap_or_else(|| url.to_string());
+    let check = conflicts::staged_check(&mods_dir);
+    let result = async {
+        let (installed_path, source) = installer::install_mod_with_source(
+            &mods_dir,
+            url.to_string(),
+            folder_name,
+            None,
+            (!force).then_some(&check),
+            &download_job(),
+        )
+        .await?;
+        let name = installed_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .ok_or_else(|| {
+                AppError::InvalidState(format!(
+                    "Invalid install path: {}",
+                    installed_path.display()
+                ))
+            })?;
+
+        db.record_install(&name, &installed_path.to_string_lossy(), &[], None, &source)?;
+        Ok((name, installed_path))
+    }
+    .await;
+    let affected = match &result {
+        Ok((name, _)) => AffectedMod::new(name, None),
+        Err(_) => AffectedMod::new(fallback_name, None),
+    };
+    let (name, installed_path) = operations::record(
+        db,
+        OperationKind::Install,
+        &[affected],
+        Some(format!("From {url}").as_str()),
+        result,
+    )?;
+    println!("installed {name} to {}", installed_path.display());
+    Ok(())
+}
+
+async fn install(
+    db: Database,
+    target: &str,
+    folder_name: Option<String>,
+    no_deps: bool,
+    force: bool,
+) -> Result<(), String> {
+    if target.starts_with("http://") || target.starts_with("https://") {
+        return install_from_url(&db, target, folder_name, force).await;
+    }
+    let mods_dir = db.get_mods_dir()?;
+
+    let (ids, catalog): (Vec<_>, Vec<_>) = fetch_catalog().await?.into_iter().unzip();
+    // match the index id first, then fall back to the title or folder name
+    let entry = ids
+        .iter()
+        .position(|id| id == target)
+        .map(|i| &catalog[i])
+        .or_else(|| dependencies::find_in_catalog(&catalog, target))
+        .ok_or_else(|| format!("No mod matching `{target}` in the index"))?;
+
+    if no_deps || folder_name.is_some() {
+        let folder_name = folder_name
+            .or_else(|| entry.folder_name.clone())
+            .unwrap_or_else(|| entry.title.replace(' ', ""));
+        let expected_hash =
+            version_history::recorded_archive(&db, &entry.download_url, entry.version.as_deref())?;
+        let check = conflicts::staged_check(&mods_dir);
+        let result = async {
+            let (installed_path, source) = installer::install_mod_with_source(
+                &mods_dir,
+                entry.download_url.clone(),
+                Some(folder_name),
+                expected_hash.as_deref(),
+                (!force).then_some(&check),
+                &download_job(),
+            )
+            .await?;
+
+            db.record_install(
+                &entry.title,
+                &installed_path.to_string_lossy(),
+                &dependencies::catalog_dependencies(entry),
+                entry.version.clone(),
+                &source,
+            )?;
+            Ok(installed_path)
+        }
+        .await;
+        let installed_path = operations::record(
+            &db,
+            OperationKind::Install,
+            &[AffectedMod::new(&entry.title, entry.version.clone())],
+            None,
+            result,
+        )?;
+        println!(
+            "installed {} {} to {}",
+            entry.title,
+            entry.version.as_deref().unwrap_or("-"),
+            installed_path.display()
+        );
+        return Ok(());
+    }
+
+    let db = Mutex::new(db);
+    let job = download_job();
+    for title in
+        dependencies::install_with_dependencies(&db, &catalog, &entry.title, force, &job).await?
+    {
+        println!("installed {title}");
+    }
+    Ok(())
+}
+
+fn uninstall(db: &Database, name: &str, cascade: bool) -> Result<(), String> {
+    if cascade {
+        for removed in installer::cascade_uninstall(db, name)? {
+            println!("uninstalled {removed}");
+        }
+        return Ok(());
+    }
+
+    let dependents = db
+        .get_dependents(name)?
+        .into_iter()
+        .filter(|dep| dep != name)
+        .collect::<Vec<_>>();
+    if !dependents.is_empty() {
+        return Err(format!(
+            "{name} is required by {}, pass --cascade to uninstall them too",
+            dependents.join(", ")
+        ));
+    }
+
+    let mod_details = db.get_mod_details(name)?;
+    let result = installer::uninstall_mod(&db.get_mods_dir()?, PathBuf::from(&mod_details.path))
+        .and_then(|()| db.remove_installed_mod(name));
+    operations::record(
+        db,
+        OperationKind::Uninstall,
+        &[AffectedMod::from(&mod_details)],
+        None,
+        result,
+    )?;
+    println!("uninstalled {name}");
+    Ok(())
+}
+
+fn set_enabled(db: &Database, name: &str, enabled: bool, force: bool) -> Result<(), String> {
+    let mod_details = db.get_mod_details(name)?;
+    let mod_dir = PathBuf::from(&mod_details.path);
+    if enabled && !force {
+        conflicts::check_conflicts(&db.get_mods_dir()?, &mod_dir)?;
+        game_version::check_mod(db.get_installation_path()?.as_ref(), &mod_dir)?;
+    }
+    operations::record(
+        db,
+        if enabled {
+            OperationKind::Enable
+        } else {
+            OperationKind::Disable
+        },
+        &[AffectedMod::from(&mod_details)],
+        None,
+        installer::set_mod_enabled(&mod_dir, enabled),
+    )?;
+    println!("{} {name}", if enabled { "enabled" } else { "disabled" });
+    Ok(())
+}
+
+fn game_version(db: &Database, as_json: bool) -> Result<(), String> {
+    let installation_path = db.get_installation_path()?;
+    let check = game_version::compare(db, &game_dir(db)?)?;
+    let incompatible =
+        game_version::incompatible_mods(installation_path.as_ref(), &db.get_mods_dir()?)?;
+
+    if as_json {
+        println!(
+            "{}",
+            json!({ "version": check, "incompatible_mods": incompatible })
+        );
+        return Ok(());
+    }
+
+    println!("Balatro {}", check.current);
+    if let Some(previous) = check.previous.as_ref().filter(|_| check.changed) {
+        println!("changed since the last launch, which was {previous}");
+    }
+    if !incompatible.is_empty() {
+        println!("enabled mods that don't support it:");
+    }
+    for incompatibility in &incompatible {
+        println!("  {incompatibility}");
+    }
+    Ok(())
+}
+
+fn detect_local(db: &Database, as_json: bool) -> Result<(), String> {
+    let detected_mods = local_mod_detection::get_all_detected_mods(db)?;
+
+    if as_json {
+        println!(
+            "{}",
+            serde_json::to_string(&detected_mods).map_err(|e| e.to_string())?
+        );
+        return Ok(());
+    }
+
+    for m in &detected_mods {
+        println!(
+            "{:<32} {:<20} {}",
+            m.name,
+            m.version.as_deref().unwrap_or("-"),
+            m.path
+        );
+    }
+    Ok(())
+}
+
+fn patches(db: &Database, as_json: bool) -> Result<(), String> {
+    let mods_dir = db.get_mods_dir()?;
+    let report = lovely_patches::analyse(&mods_dir)?;
+
+    if as_json {
+        println!(
+            "{}",
+            serde_json::to_string(&report).map_err(|e| e.to_string())?
+        );
+        return Ok(());
+    }
+
+    for m in &report.mods {
+        println!(
+            "{:<32} {}{}",
+            m.name,
+            m.targets.join(", "),
+            if m.enabled { "" } else { " (disabled)" }
+        );
+        for error in &m.errors {
+            println!("  error: {error}");
+        }
+    }
+    if report.conflicts.is_empty() {
+        return Ok(());
+    }
+    println!();
+    for conflict in &report.conflicts {
+        match (conflict.kind, &conflict.pattern) {
+            (ConflictKind::SamePattern, Some(pattern)) => println!(
+                "{} patch the same line of {}: {pattern}",
+                conflict.mods.join(", "),
+                conflict.target
+            ),
+            _ => println!("{} all patch {}", conflict.mods.join(", "), conflict.target),
+        }
+    }
+    Ok(())
+}
+
+fn game_dir(db: &Database) -> Result<PathBuf, String> {
+    db.get_installation_path()?
+        .map(PathBuf::from)
+        .ok_or_else(|| "No installation path set, use `bmm set-path` first".to_string())
+}
+
+fn patch_preview(
+    db: &Database,
+    source_dir: Option<PathBuf>,
+    targets: &[String],
+    as_json: bool,
+) -> Result<(), String> {
+    let source_dir = match source_dir {
+        Some(dir) => dir,
+        None => game_source::extract(&game_dir(db)?)?.dir,
+    };
+    let mods_dir = db.get_mods_dir()?;
+    let report = lovely_patches::analyse(&mods_dir)?;
+    let preview = patch_preview::preview(&source_dir, &report.mods, targets)?;
+
+    if as_json {
+        println!(
+            "{}",
+            serde_json::to_string(&preview).map_err(|e| e.to_string())?
+        );
+        return Ok(());
+    }
+
+    for file in &preview.files {
+        if file.diff.is_empty() {
+            println!("{} is unchanged", file.target);
+        } else {
+            print!("{}", file.diff);
+        }
+    }
+    for warning in &preview.warnings {
+        eprintln!(
+            "warning: {} ({}): {}",
+            warning.mod_name, warning.target, warning.message
+        );
+    }
+    Ok(())
+}
+
+fn source(db: &Database, command: SourceCommand, as_json: bool) -> Result<(), String> {
+    let sources = match command {
+        SourceCommand::Extract => vec![game_source::extract(&game_dir(db)?)?],
+        SourceCommand::List => game_source::list()?,
+    };
+    if as_json {
+        println!(
+            "{}",
+            serde_json::to_string(&sources).map_err(|e| e.to_string())?
+        );
+        return Ok(());
+    }
+    for source in &sources {
+        println!(
+            "{:<16} {} ({} Lua files)",
+            source.version.as_deref().unwrap_or("unknown"),
+            source.dir.display(),
+            source.files.len()
+        );
+    }
+    Ok(())
+}
+
+fn set_path(db: &Database, path: PathBuf) -> Result<(), String> {
+    // If the path points to an executable, use its parent directory
+    let game_dir = if path.is_file() {
+        path.parent()
+            .map(std::path::Path::to_path_buf)
+            .unwrap_or(path)
+    } else {
+        path
+    };
+
+    if !is_valid_game_dir(&game_dir) {
+        return Err(AppError::invalid_path(game_dir, "Not a Balatro installation").into());
+    }
+
+    let game_path = game_dir.to_string_lossy().into_owned();
+    db.update_settings(|settings| settings.installation_path = Some(game_path))?;
+    println!("game path set to {}", game_dir.display());
+    match db.get_mods_dir() {
+        Ok(mods_dir) => println!("mods directory is {}", mods_dir.display()),
+        Err(e) => println!("mods directory can't be found yet: {e}"),
+    }
+    Ok(())
+}
+
+async fn export_lockfile(db: &Database, file: &Path) -> Result<(), String> {
+    let catalog = fetch_catalog().await.unwrap_or_else(|e| {
+        log::warn!("Exporting without catalog ids: {e}");
+        Vec::new()
+    });
+    let lockfile = lockfile::export(db, &catalog)?;
+    lockfile.write(file)?;
+    println!(
+        "wrote {} mods to {}",
+        lockfile.mods.len()
+            + usize::from(lockfile.steamodded.is_some())
+            + usize::from(lockfile.talisman.is_some()),
+        file.display()
+    );
+    Ok(())
+}
+
+async fn import_lockfile(db: Database, file: &Path, as_json: bool) -> Result<(), String> {
+    let lockfile = Lockfile::read(file)?;
+    // only entries without a recorded download need the catalog
+    let catalog = if lockfile.mods.iter().any(|m| m.download_url.is_none()) {
+        fetch_catalog().await.unwrap_or_else(|e| {
+            log::warn!("Importing without the catalog: {e}");
+            Vec::new()
+        })
+    } else {
+        Vec::new()
+    };
+    let report = lockfile::import(&Mutex::new(db), &lockfile, &catalog, &download_job()).await?;
+
+    if as_json {
+        println!(
+            "{}",
+            serde_json::to_string(&report).map_err(|e| e.to_string())?
+        );
+    } else {
+        for name in &report.installed {
+            println!("installed {name}");
+        }
+        for failure in &report.failed {
+            eprintln!("could not reproduce {}: {}", failure.name, failure.reason);
+        }
+    }
+
+    if report.failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} of {} entries could not be reproduced",
+            report.failed.len(),
+            report.failed.len() + report.installed.len()
+        ))
+    }
+}
+
+async fn verify(db: Database, names: &[String], repair: bool, as_json: bool) -> Result<(), String> {
+    let reports = integrity::verify(&db)?
+        .into_iter()
+        .filter(|m| names.is_empty() || names.contains(&m.name))
+        .collect::<Vec<_>>();
+
+    if as_json && !repair {
+        println!(
+            "{}",
+            serde_json::to_string(&reports).map_err(|e| e.to_string())?
+        );
+    } else if !as_json {
+        for report in &reports {
+            let status = match report.status {
+                IntegrityStatus::Intact => "intact",
+                IntegrityStatus::Damaged => "damaged",
+                IntegrityStatus::Missing => "missing",
+                IntegrityStatus::Unrecorded => "not recorded",
+            };
+            println!("{}: {status}", report.name);
+            for (label, files) in [
+                ("modified", &report.modified),
+                ("missing", &report.missing),
+                ("extra", &report.extra),
+            ] {
+                for file in files {
+                    println!("  {label} {file}");
+                }
+            }
+        }
+    }
+
+    let damaged = reports
+        .iter()
+        .filter(|m| {
+            matches!(
+                m.status,
+                IntegrityStatus::Damaged | IntegrityStatus::Missing
+            )
+        })
+        .count();
+    if !repair {
+        return if damaged == 0 {
+            Ok(())
+        } else {
+            Err(format!(
+                "{damaged} mods are damaged, run with --repair to fix them"
+            ))
+        };
+    }
+
+    let names = (!names.is_empty()).then_some(names);
+    let report = integrity::repair(&Mutex::new(db), names, &download_job()).await?;
+    if as_json {
+        println!(
+            "{}",
+            serde_json::to_string(&report).map_err(|e| e.to_string())?
+        );
+    } else {
+        for name in &report.repaired {
+            println!("repaired {name}");
+        }
+        for failure in &report.failed {
+            eprintln!("could not repair {}: {}", failure.name, failure.reason);
+        }
+    }
+
+    if report.failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} mods could not be repaired",
+            report.failed.len()
+        ))
+    }
+}
+
+async fn update(
+    db: Database,
+    names: &[String],
+    dry_run: bool,
+    as_json: bool,
+) -> Result<(), String> {
+    let catalog = fetch_catalog()
+        .await?
+        .into_iter()
+        .map(|(_, m)| m)
+        .collect::<Vec<_>>();
+    let releases = LatestReleases::fetch(&db.get_installed_mods()?).await;
+    let names = (!names.is_empty()).then_some(names);
+    let plan = updates::plan(&db, &catalog, &releases, names)?;
+
+    if as_json && dry_run {
+        println!(
+            "{}",
+            serde_json::to_string(&plan).map_err(|e| e.to_string())?
+        );
+        return Ok(());
+    } else if !as_json {
+        for update in &plan.updates {
+            println!(
+                "{}: {} -> {}",
+                update.name, update.from_version, update.to_version
+            );
+        }
+        for title in &plan.install {
+            println!("  new dependency {title}");
+        }
+        for unmet in &plan.unmet {
+            eprintln!("warning: {} needs {}", unmet.dependent, unmet.spec);
+        }
+        for held in &plan.held {
+            println!("held back: {}", held.explanation);
+        }
+        if plan.updates.is_empty() {
+            println!("everything is up to date");
+        }
+    }
+    if dry_run || plan.updates.is_empty() {
+        return Ok(());
+    }
+
+    let report = updates::apply(&Mutex::new(db), &catalog, &plan, &download_job()).await?;
+    if as_json {
+        println!(
+            "{}",
+            serde_json::to_string(&report).map_err(|e| e.to_string())?
+        );
+    } else {
+        for name in &report.installed {
+            println!("installed {name}");
+        }
+        for name in &report.updated {
+            println!("updated {name}");
+        }
+        for failure in &report.failed {
+            eprintln!("could not update {}: {}", failure.name, failure.reason);
+        }
+    }
+
+    if report.failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} updates failed", report.failed.len()))
+    }
+}
+
+fn pin(
+    db: &Database,
+    name: Option<String>,
+    constraint: Option<&str>,
+    reason: Option<String>,
+    as_json: bool,
+) -> Result<(), String> {
+    let Some(name) = name else {
+        let pins = db.get_mod_pins()?;
+        if as_json {
+            println!(
+                "{}",
+                serde_json::to_string(&pins).map_err(|e| e.to_string())?
+            );
+            return Ok(());
+        }
+        for p in &pins {
+            println!(
+                "{:<32} {:<20} {}",
+                p.name,
+                p.constraint,
+                p.reason.as_deref().unwrap_or("")
+            );
+        }
+        return Ok(());
+    };
+
+    let pin = pins::pin(db, &name, constraint, reason)?;
+    println!("pinned {} to {}", pin.name, pin.constraint);
+    Ok(())
+}
+
+fn history(db: &Database, name: &str, as_json: bool) -> Result<(), String> {
+    let versions = version_history::history(db, name)?;
+
+    if as_json {
+        println!(
+            "{}",
+            serde_json::to_string(&versions).map_err(|e| e.to_string())?
+        );
+        return Ok(());
+    }
+
+    for v in &versions {
+        println!(
+            "{} {:<20} {} {}",
+            if v.current { "*" } else { " " },
+            if v.version.is_empty() {
+                "-"
+            } else {
+                &v.version
+            },
+            &v.archive_hash[..12.min(v.archive_hash.len())],
+            if v.archived { "stored" } else { "download" }
+        );
+    }
+    Ok(())
+}
+
+async fn rollback(db: Database, name: &str, target: Option<&str>) -> Result<(), String> {
+    let versions = version_history::history(&db, name)?;
+    let chosen = match target {
+        Some(target) => versions.iter().find(|v| {
+            v.version == target
+                || (target.len() >= 6 && v.archive_hash.starts_with(&target.to_lowercase()))
+        }),
+        None => versions.iter().find(|v| !v.current),
+    }
+    .ok_or_else(|| format!("No earlier version of {name} to roll back to, see `bmm history`"))?;
+
+    let archive_hash = chosen.archive_hash.clone();
+    let restored =
+        version_history::rollback(&Mutex::new(db), name, &archive_hash, &download_job()).await?;
+    println!(
+        "rolled back {name} to {}",
+        if restored.version.is_empty() {
+            &restored.archive_hash
+        } else {
+            &restored.version
+        }
+    );
+    Ok(())
+}
+
+fn print_log(
+    db: &Database,
+    page: u32,
+    mod_name: Option<&str>,
+    as_json: bool,
+) -> Result<(), String> {
+    let log = db.get_operations(page, operations::DEFAULT_PAGE_SIZE, mod_name)?;
+
+    if as_json {
+        println!(
+            "{}",
+            serde_json::to_string(&log).map_err(|e| e.to_string())?
+        );
+        return Ok(());
+    }
+
+    for op in &log.operations {
+        let when = i64::try_from(op.timestamp)
+            .ok()
+            .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+            .map_or_else(
+                || op.timestamp.to_string(),
+                |t| {
+                    t.with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string()
+                },
+            );
+        let mods = op
+            .mods
+            .iter()
+            .map(|m| match &m.version {
+                Some(version) => format!("{} {version}", m.name),
+                None => m.name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let outcome = match op.outcome {
+            Outcome::Succeeded => "ok",
+            Outcome::Failed => "FAILED",
+        };
+        println!("{when} {:<17} {outcome:<6} {mods}", op.kind.to_string());
+        if let Some(detail) = &op.detail {
+            println!("    {detail}");
+        }
+        if let Some(error) = &op.error {
+            println!("    {error}");
+        }
+    }
+
+    let pages = log.total.div_ceil(u64::from(log.per_page)).max(1);
+    if log.operations.is_empty() && log.total > 0 {
+        println!("page {} is past the last page, {pages}", log.page);
+    } else if pages > 1 {
+        println!("page {} of {pages}, see --page", log.page);
+    }
+    Ok(())
+}
+
+fn installation(db: &Database, command: InstallationCommand, as_json: bool) -> Result<(), String> {
+    let installations = db.installations();
+    match command {
+        InstallationCommand::List => {
+            let list = installations.list()?;
+            if as_json {
+                println!(
+                    "{}",
+                    serde_json::to_string(&list).map_err(|e| e.to_string())?
+                );
+                return Ok(());
+            }
+            for i in &list {
+                println!(
+                    "{} {:<24} {}{}",
+                    if i.active { "*" } else { " " },
+                    i.name,
+                    i.game_dir.as_deref().unwrap_or("(game not located)"),
+                    i.mods_dir
+                        .as_deref()
+                        .map(|dir| format!(" (mods in {dir})"))
+                        .unwrap_or_default()
+                );
+            }
+        }
+        InstallationCommand::Add {
+            name,
+            game_dir,
+            mods_dir,
+        } => {
+            let added = installations.add(&name, &game_dir, mods_dir.as_deref())?;
+            println!("added installation {}", added.name);
+        }
+        InstallationCommand::Switch { name } => {
+            let active = installations.switch(&name)?;
+            println!("switched to installation {}", active.name);
+        }
+        InstallationCommand::Rename { name, new_name } => {
+            installations.rename(&name, &new_name)?;
+            println!("renamed installation {name} to {new_name}");
+        }
+        InstallationCommand::ModsDir { name, mods_dir } => {
+            installations.set_mods_dir(&name, mods_dir.as_deref())?;
+            match mods_dir {
+                Some(dir) => println!("{name} now loads mods from {}", dir.display()),
+                None => println!("{name} now uses the usual Mods directory"),
+            }
+        }
+        InstallationCommand::Remove { name } => {
+            installations.remove(&name)?;
+            println!("removed installation {name}");
+        }
+    }
+    Ok(())
+}
+
+async fn lovely(db: Database, command: LovelyCommand, as_json: bool) -> Result<(), String> {
+    match command {
+        LovelyCommand::List => {
+            let releases = lovely::list_releases().await?;
+            if as_json {
+                println!(
+                    "{}",
+                    serde_json::to_string(&releases).map_err(|e| e.to_string())?
+                );
+                return Ok(());
+            }
+            for release in &releases {
+                println!(
+                    "{:<12} {}{}",
+                    release.tag,
+                    release
+                        .published_at
+                        .get(..10)
+                        .unwrap_or(&release.published_at),
+                    if release.prerelease {
+                        " (prerelease)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+        LovelyCommand::Status => {
+            let status = lovely::installation_status(&db)?;
+            if as_json {
+                println!(
+                    "{}",
+                    serde_json::to_string(&status).map_err(|e| e.to_string())?
+                );
+                return Ok(());
+            }
+            match status {
+                LovelyStatus::Missing => println!("lovely isn't installed"),
+                LovelyStatus::Untracked { sha256 } => {
+                    println!("lovely of an unknown version is installed (sha256 {sha256})");
+                }
+                LovelyStatus::Verified(install) => {
+                    println!("lovely {} is installed and unchanged", install.version);
+                }
+                LovelyStatus::Modified { install, sha256 } => println!(
+                    "lovely {} was installed, but the file has changed since \
+                     (sha256 {sha256}, expected {}); `bmm lovely install {}` puts it back",
+                    install.version, install.sha256, install.version
+                ),
+            }
+        }
+        LovelyCommand::Install { tag } => {
+            let installed =
+                lovely::install_release(&Mutex::new(db), tag.as_deref(), &download_job()).await?;
+            println!(
+                "installed lovely {} to {} (sha256 {})",
+                installed.version, installed.path, installed.sha256
+            );
+        }
+    }
+    Ok(())
+}
+
+fn settings(db: &Database, command: SettingsCommand, as_json: bool) -> Result<(), String> {
+    let current = match command {
+        SettingsCommand::Show => db.get_settings()?,
+        SettingsCommand::Export { file } => {
+            settings::export(db, &file)?;
+            println!("wrote settings to {}", file.display());
+            return Ok(());
+        }
+        SettingsCommand::Import { file } => {
+            let imported = settings::import(db, &file)?;
+            println!("imported settings from {}", file.display());
+            imported
+        }
+    };
+
+    if as_json {
+        println!(
+            "{}",
+            serde_json::to_string(&current).map_err(|e| e.to_string())?
+        );
+        return Ok(());
+    }
+    println!(
+        "installation_path             {}",
+        current.installation_path.as_deref().unwrap_or("(not set)")
+    );
+    println!(
+        "background_enabled            {}",
+        current.background_enabled
+    );
+    println!(
+        "security_warning_acknowledged {}",
+        current.security_warning_acknowledged
+    );
+    Ok(())
+}
+
+fn profile(db: &Database, command: ProfileCommand, as_json: bool) -> Result<(), String> {
+    let collections = db.collections();
+    let mods_dir = db.get_mods_dir()?;
+
+    match command {
+        ProfileCommand::List => {
+            let profiles = collections.list()?;
+            if as_json {
+                println!(
+                    "{}",
+                    serde_json::to_string(&profiles).map_err(|e| e.to_string())?
+                );
+                return Ok(());
+            }
+            for p in &profiles {
+                println!(
+                    "{} {:<32} {} mods",
+                    if p.active { "*" } else { " " },
+                    p.name,
+                    p.enabled_mods.len()
+                );
+            }
+        }
+        ProfileCommand::Create { name } => {
+            collections.create(&name, &mod_collections::enabled_mods(&mods_dir)?)?;
+            println!("created profile {name}");
+        }
+        ProfileCommand::Save { name } => {
+            collections.set_enabled_mods(&name, &mod_collections::enabled_mods(&mods_dir)?)?;
+            println!("saved profile {name}");
+        }
+        ProfileCommand::Switch { name, force } => {
+            let toggles = collections.switch(&name, &mods_dir, force)?;
+            mod_collections::record_switch(db, &name, toggles)?;
+            println!("switched to profile {name}");
+        }
+        ProfileCommand::Rename { name, new_name } => {
+            collections.rename(&name, &new_name)?;
+            println!("renamed profile {name} to {new_name}");
+        }
+        ProfileCommand::Clone { name, new_name } => {
+            collections.clone_collection(&name, &new_name)?;
+            println!("cloned profile {name} to {new_name}");
+        }
+        ProfileCommand::Delete { name } => {
+            collections.delete(&name)?;
+            println!("deleted profile {name}");
+        }
+    }
+    Ok(())
+}
+
+async fn run(cli: Cli) -> Result<(), String> {
+    if let Some(home) = &cli.home {
+        Paths::init(Paths::rooted(home, PathsOrigin::Override))?;
+    }
+    let db = Database::new()?;
+
+    match cli.command {
+        Command::List { available: false } => print_installed(&db, cli.json),
+        Command::List { available: true } => print_available(cli.json).await,
+        Command::Install {
+            target,
+            folder_name,
+            no_deps,
+            force,
+        } => install(db, &target, folder_name, no_deps, force).await,
+        Command::Uninstall { name, cascade } => uninstall(&db, &name, cascade),
+        Command::Enable { name, force } => set_enabled(&db, &name, true, force),
+        Command::Disable { name } => set_enabled(&db, &name, false, false),
+        Command::Reindex => {
+            let removed = db.remove_missing_mods()?;
+            if !removed.is_empty() {
+                operations::record(
+                    &db,
+                    OperationKind::Reindex,
+                    &removed.iter().map(AffectedMod::from).collect::<Vec<_>>(),
+                    Some("Directories no longer exist"),
+                    Ok(()),
+                )?;
+            }
+            println!("removed {} stale entries", removed.len());
+            Ok(())
+        }
+        Command::Log { page, mod_name } => print_log(&db, page, mod_name.as_deref(), cli.json),
+        Command::DetectLocal => detect_local(&db, cli.json),
+        Command::Patches => patches(&db, cli.json),
+        Command::PatchPreview { targets, source } => patch_preview(&db, source, &targets, cli.json),
+        Command::Source(command) => source(&db, command, cli.json),
+        Command::SetPath { path } => set_path(&db, path),
+        Command::Installation(command) => installation(&db, command, cli.json),
+        Command::Settings(command) => settings(&db, command, cli.json),
+        Command::Profile(command) => profile(&db, command, cli.json),
+        Command::Export { file } => export_lockfile(&db, &file).await,
+        Command::Import { file } => import_lockfile(db, &file, cli.json).await,
+        Command::Verify { names, repair } => verify(db, &names, repair, cli.json).await,
+        Command::Update { names, dry_run } => update(db, &names, dry_run, cli.json).await,
+        Command::Pin {
+            name,
+            constraint,
+            reason,
+        } => pin(&db, name, constraint.as_deref(), reason, cli.json),
+        Command::Unpin { name } => {
+            if db.remove_mod_pin(&name)? {
+                println!("unpinned {name}");
+                Ok(())
+            } else {
+                Err(format!("{name} isn't pinned"))
+            }
+        }
+        Command::History { name } => history(&db, &name, cli.json),
+        Command::Rollback { name, version } => rollback(db, &name, version.as_deref()).await,
+        Command::Launch { vanilla, force } => {
+            let path = game_dir(&db)?;
+            let db = Mutex::new(db);
+            let check =
+                launcher::launch_balatro(&db, &path, vanilla, force, &download_job()).await?;
+            if let Some(warning) = check.warning() {
+                eprintln!("warning: {warning}");
+            }
+            Ok(())
+        }
+        Command::GameVersion => game_version(&db, cli.json),
+        Command::Lovely(command) => lovely(db, command, cli.json).await,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::new().default_filter_or("warn")).init();
+
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
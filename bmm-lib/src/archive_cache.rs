@@ -0,0 +1,274 @@
+use crate::checksum;
+use crate::download::{self, DownloadJob, DownloadedFile};
+use crate::errors::AppError;
+use crate::paths::Paths;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How much space archives may take up before the least recently used ones
+/// are evicted.
+pub const DEFAULT_MAX_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Downloaded mod archives, stored under their SHA-256 so reinstalling or
+/// downgrading to a version that was downloaded before doesn't need the
+/// network. The URL each archive was last downloaded from is remembered too,
+/// as a fallback for when the network is unavailable.
+#[derive(Debug, Clone)]
+pub struct ArchiveCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+/// An archive ready to be extracted, either from the cache or freshly
+/// downloaded.
+#[derive(Debug)]
+pub struct Archive {
+    pub path: PathBuf,
+    pub sha256: String,
+    /// Keeps an uncached download alive until the archive is dropped.
+    _download: Option<DownloadedFile>,
+}
+
+impl Archive {
+    pub fn open(&self) -> Result<File, AppError> {
+        File::open(&self.path).map_err(|e| AppError::FileRead {
+            path: self.path.clone(),
+            source: e.to_string(),
+        })
+    }
+}
+
+impl ArchiveCache {
+    /// The cache in bromomethane's cache directory.
+    pub fn open() -> Result<Self, AppError> {
+        Self::at(Paths::get()?.archive_cache_dir(), DEFAULT_MAX_SIZE)
+    }
+
+    pub fn at(dir: PathBuf, max_size: u64) -> Result<Self, AppError> {
+        fs::create_dir_all(&dir).map_err(|e| AppError::DirCreate {
+            path: dir.clone(),
+            source: e.to_string(),
+        })?;
+        Ok(Self { dir, max_size })
+    }
+
+    /// The cached archive with this hash, if there is one and it's intact.
+    /// Corrupted entries are removed.
+    #[must_use]
+    pub fn get(&self, sha256: &str) -> Option<PathBuf> {
+        let sha256 = sha256.trim().to_lowercase();
+        if !is_archive_name(&sha256) {
+            return None;
+        }
+        let path = self.dir.join(&sha256);
+        let contents = fs::read(&path).ok()?;
+        if !checksum::matches(&sha256, &checksum::sha256_hex(&contents)) {
+            log::warn!("Removing corrupted cached archive {}", path.display());
+            _ = fs::remove_file(&path);
+            return None;
+        }
+
+        // Mark it as recently used so eviction keeps it around
+        if let Err(e) = File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(SystemTime::now()))
+        {
+            log::debug!("Failed to touch {}: {e}", path.display());
+        }
+        Some(path)
+    }
+
+    /// The hash of the archive last downloaded from `url`.
+    #[must_use]
+    pub fn lookup_url(&self, url: &str) -> Option<String> {
+        self.read_index().remove(url)
+    }
+
+    /// Gets the archive at `url`. An archive with `expected_hash` is taken
+    /// from the cache without touching the network. Anything else is
+    /// downloaded, since the same URL can serve different files over time,
+    /// but when the download fails the archive last downloaded from `url` is
+    /// used instead.
+    pub async fn fetch(
+        &self,
+        url: &str,
+        expected_hash: Option<&str>,
+        job: &DownloadJob,
+    ) -> Result<Archive, AppError> {
+        if let Some(expected) = expected_hash
+            && let Some(path) = self.get(expected)
+        {
+            log::info!("Using cached archive for {url}");
+            return Ok(Archive {
+                path,
+                sha256: expected.trim().to_lowercase(),
+                _download: None,
+            });
+        }
+
+        match download::download_in(url, &self.dir, job).await {
+            Ok(downloaded) => self.insert(url, downloaded),
+            Err(e @ AppError::NetworkRequest { .. }) => {
+                let cached = self
+                    .lookup_url(url)
+                    .and_then(|sha256| self.get(&sha256).map(|path| (path, sha256)));
+                let Some((path, sha256)) = cached else {
+                    return Err(e);
+                };
+                log::warn!("{e}, using the archive downloaded from there before");
+                Ok(Archive {
+                    path,
+                    sha256,
+                    _download: None,
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Copies the archive at `path` into the cache as if it had been
+    /// downloaded from `url`.
+    pub fn add(&self, url: &str, path: &Path) -> Result<(), AppError> {
+        let contents = fs::read(path).map_err(|e| AppError::FileRead {
+            path: path.to_path_buf(),
+            source: e.to_string(),
+        })?;
+        let sha256 = checksum::sha256_hex(&contents);
+        let target = self.dir.join(&sha256);
+        if !target.is_file() {
+            let temp = target.with_extension("tmp");
+            fs::write(&temp, &contents).map_err(|e| AppError::FileWrite {
+                path: temp.clone(),
+                source: e.to_string(),
+            })?;
+            fs::rename(&temp, &target).map_err(|e| AppError::FileWrite {
+                path: target,
+                source: e.to_string(),
+            })?;
+        }
+
+        let mut index = self.read_index();
+        index.insert(url.to_string(), sha256.clone());
+        self.write_index(&index)?;
+        self.evict(&sha256)
+    }
+
+    fn insert(&self, url: &str, downloaded: DownloadedFile) -> Result<Archive, AppError> {
+        let sha256 = downloaded.sha256.clone();
+        let path = self.dir.join(&sha256);
+        downloaded.persist(&path)?;
+
+        let mut index = self.read_index();
+        index.insert(url.to_string(), sha256.clone());
+        if let Err(e) = self.write_index(&index) {
+            log::warn!("Failed to update the archive cache index: {e}");
+        }
+        if let Err(e) = self.evict(&sha256) {
+            log::warn!("Failed to evict cached archives: {e}");
+        }
+
+        Ok(Archive {
+            path,
+            sha256,
+            _download: None,
+        })
+    }
+
+    /// Removes the least recently used archives until the cache fits in
+    /// `max_size`. The archive `keep` is never removed, even if it doesn't fit
+    /// on its own.
+    fn evict(&self, keep: &str) -> Result<(), AppError> {
+        let entries = fs::read_dir(&self.dir).map_err(|e| AppError::FileRead {
+            path: self.dir.clone(),
+            source: e.to_string(),
+        })?;
+        let mut archives = entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_str().is_some_and(is_archive_name))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                Some((entry.path(), metadata.len(), used))
+            })
+            .collect::<Vec<_>>();
+
+        let mut total = archives.iter().map(|(_, size, _)| size).sum::<u64>();
+        if total <= self.max_size {
+            return Ok(());
+        }
+
+        archives.sort_by_key(|(_, _, used)| *used);
+        let mut evicted = Vec::new();
+        for (path, size, _) in archives {
+            if total <= self.max_size {
+                break;
+            }
+            if path.file_name().is_some_and(|name| name == keep) {
+                continue;
+            }
+            log::info!("Evicting cached archive {}", path.display());
+            fs::remove_file(&path).map_err(|e| AppError::FileWrite {
+                path: path.clone(),
+                source: e.to_string(),
+            })?;
+            total -= size;
+            evicted.extend(path.file_name().and_then(|n| n.to_str()).map(String::from));
+        }
+
+        let mut index = self.read_index();
+        index.retain(|_, sha256| !evicted.contains(sha256));
+        self.write_index(&index)
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.dir.join("index.json")
+    }
+
+    fn read_index(&self) -> HashMap<String, String> {
+        fs::read_to_string(self.index_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn write_index(&self, index: &HashMap<String, String>) -> Result<(), AppError> {
+        let path = self.index_path();
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_string(index)?).map_err(|e| AppError::FileWrite {
+            path: temp.clone(),
+            source: e.to_string(),
+        })?;
+        fs::rename(&temp, &path).map_err(|e| AppError::FileWrite {
+            path,
+            source: e.to_string(),
+        })
+    }
+}
+
+pub(crate) fn is_archive_name(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Gets the archive at `url` through the [`ArchiveCache`], or downloads it
+/// without caching if the cache directory isn't usable.
+pub async fn fetch(
+    url: &str,
+    expected_hash: Option<&str>,
+    job: &DownloadJob,
+) -> Result<Archive, AppError> {
+    match ArchiveCache::open() {
+        Ok(cache) => cache.fetch(url, expected_hash, job).await,
+        Err(e) => {
+            log::warn!("Archive cache unavailable, downloading without it: {e}");
+            let downloaded = download::download(url, job).await?;
+            Ok(Archive {
+                path: downloaded.path().to_path_buf(),
+                sha256: downloaded.sha256.clone(),
+                _download: Some(downloaded),
+            })
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::errors::AppError;
+use crate::paths::{self, Paths};
 use flate2::Compression;
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
@@ -92,9 +93,7 @@ impl From<i32> for Category {
 }
 
 pub fn clear_cache() -> Result<(), AppError> {
-    let cache_dir = dirs::cache_dir()
-        .ok_or_else(|| AppError::DirNotFound(PathBuf::from("cache directory")))?
-        .join("bromomethane");
+    let cache_dir = &Paths::get()?.cache_dir;
 
     // Delete mods cache
     let mods_cache = cache_dir.join("mods.cache.bin.gz");
@@ -125,15 +124,7 @@ pub fn clear_cache() -> Result<(), AppError> {
 }
 
 pub fn save_versions_cache(mod_type: &str, versions: &[String]) -> Result<(), AppError> {
-    let mut path = dirs::cache_dir()
-        .ok_or_else(|| AppError::DirNotFound(PathBuf::from("cache directory")))?
-        .join("bromomethane");
-
-    std::fs::create_dir_all(&path).map_err(|e| AppError::DirCreate {
-        path: path.clone(),
-        source: e.to_string(),
-    })?;
-
+    let mut path = paths::ensure_dir(Paths::get()?.cache_dir.clone())?;
     path.push(format!("versions-{mod_type}.cache.bin.gz"));
 
     let file = File::create(&path).map_err(|e| AppError::FileWrite {
@@ -172,9 +163,8 @@ pub fn save_versions_cache(mod_type: &str, versions: &[String]) -> Result<(), Ap
 }
 
 pub fn load_versions_cache(mod_type: &str) -> Result<Option<Vec<String>>, AppError> {
-    let path = dirs::cache_dir()
-        .ok_or_else(|| AppError::DirNotFound(PathBuf::from("cache directory")))?
-        .join("bromomethane")
+    let path = Paths::get()?
+        .cache_dir
         .join(format!("versions-{mod_type}.cache.bin.gz"));
 
     let mut file = match File::open(&path) {
@@ -227,15 +217,7 @@ struct VersionCache {
 }
 
 pub fn get_cache_path() -> Result<PathBuf, AppError> {
-    let mut path = dirs::cache_dir()
-        .ok_or_else(|| AppError::DirNotFound(PathBuf::from("cache directory")))?
-        .join("bromomethane");
-
-    std::fs::create_dir_all(&path).map_err(|e| AppError::DirCreate {
-        path: path.clone(),
-        source: e.to_string(),
-    })?;
-
+    let mut path = paths::ensure_dir(Paths::get()?.cache_dir.clone())?;
     path.push("mods.cache.bin.gz");
     Ok(path)
 }
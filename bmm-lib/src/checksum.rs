@@ -0,0 +1,13 @@
+use sha2::{Digest, Sha256};
+
+/// Lowercase hex SHA-256 of `data`, the form hashes are stored and compared in.
+#[must_use]
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Compares two hex digests, ignoring case and surrounding whitespace.
+#[must_use]
+pub fn matches(expected: &str, actual: &str) -> bool {
+    expected.trim().eq_ignore_ascii_case(actual.trim())
+}
@@ -0,0 +1,129 @@
+use crate::dependencies::{DependencySpec, InstalledSet};
+use crate::errors::AppError;
+use crate::installer;
+use crate::local_mod_detection::{self, DetectedMod};
+use std::fs;
+use std::path::Path;
+
+/// Fails with `AppError::ModConflict` if the mod at `candidate_dir` and any enabled
+/// mod in `mods_dir` declare a conflict with each other, in either
+/// direction. Mods without readable metadata can't declare anything and
+/// always pass.
+pub fn check_conflicts(mods_dir: &Path, candidate_dir: &Path) -> Result<(), AppError> {
+    check_against_enabled(mods_dir, candidate_dir, candidate_dir)
+}
+
+/// [`check_conflicts`] as a check for [`installer::install_mod_with_source`],
+/// run on the staged mod before it replaces anything. Whatever is installed
+/// where it's going now gets replaced, so it doesn't count as enabled.
+pub fn staged_check(mods_dir: &Path) -> impl Fn(&Path, &Path) -> Result<(), AppError> + Sync + '_ {
+    move |staged, target| check_against_enabled(mods_dir, staged, target)
+}
+
+fn check_against_enabled(
+    mods_dir: &Path,
+    candidate_dir: &Path,
+    installed_at: &Path,
+) -> Result<(), AppError> {
+    let Some(candidate) = local_mod_detection::detect_mod(candidate_dir) else {
+        return Ok(());
+    };
+    let candidate_set = InstalledSet::from_detected(&candidate);
+
+    let entries = match fs::read_dir(mods_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(AppError::FileRead {
+                path: mods_dir.to_path_buf(),
+                source: e.to_string(),
+            });
+        }
+    };
+
+    let mut conflicts = Vec::new();
+    for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+        if !path.is_dir() || is_same_dir(&path, installed_at) || !installer::is_mod_enabled(&path) {
+            continue;
+        }
+        let Some(other) = local_mod_detection::detect_mod(&path) else {
+            continue;
+        };
+        if other.id == candidate.id {
+            // another copy of the same mod is a duplicate, not a conflict
+            continue;
+        }
+
+        if declares_conflict(&candidate, &InstalledSet::from_detected(&other))
+            || declares_conflict(&other, &candidate_set)
+        {
+            conflicts.push(describe(&other));
+        }
+    }
+
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::ModConflict {
+            mod_name: candidate.name,
+            conflicts,
+        })
+    }
+}
+
+/// Fails with `AppError::ModConflict` for the first of the mods named in
+/// `names`, directories in `mods_dir`, that conflicts with another one of
+/// them. For enabling a whole set at once, where what's enabled right now
+/// doesn't matter.
+pub fn check_set(mods_dir: &Path, names: &[String]) -> Result<(), AppError> {
+    let detected = names
+        .iter()
+        .filter_map(|name| local_mod_detection::detect_mod(&mods_dir.join(name)))
+        .collect::<Vec<_>>();
+    for candidate in &detected {
+        let candidate_set = InstalledSet::from_detected(candidate);
+        let conflicts = detected
+            .iter()
+            .filter(|other| {
+                other.id != candidate.id
+                    && (declares_conflict(candidate, &InstalledSet::from_detected(other))
+                        || declares_conflict(other, &candidate_set))
+            })
+            .map(describe)
+            .collect::<Vec<_>>();
+        if !conflicts.is_empty() {
+            return Err(AppError::ModConflict {
+                mod_name: candidate.name.clone(),
+                conflicts,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn describe(detected: &DetectedMod) -> String {
+    match &detected.version {
+        Some(version) => format!("{} {version}", detected.name),
+        None => detected.name.clone(),
+    }
+}
+
+fn declares_conflict(declaring: &DetectedMod, other: &InstalledSet) -> bool {
+    declaring
+        .conflicts
+        .iter()
+        .any(|spec| match spec.parse::<DependencySpec>() {
+            Ok(spec) => spec.alternatives.iter().any(|req| other.satisfies(req)),
+            Err(e) => {
+                log::warn!("Ignoring conflict declared by {}: {e}", declaring.name);
+                false
+            }
+        })
+}
+
+fn is_same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
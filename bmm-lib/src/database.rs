@@ -1,7 +1,22 @@
+use crate::dependencies;
 use crate::errors::AppError;
-use rusqlite::Connection;
+use crate::game_version::GameVersion;
+use crate::installations::InstallationManager;
+use crate::installer::ModSource;
+use crate::integrity::FileHash;
+use crate::lovely::LovelyInstall;
+use crate::migrations;
+use crate::mod_collections::ModCollectionManager;
+use crate::operations::{AffectedMod, Operation, OperationKind, OperationsPage, Outcome};
+use crate::paths::{self, Paths};
+use crate::pins::ModPin;
+use crate::settings::Settings;
+use crate::version_history::{self, KEPT_VERSIONS, ModVersion};
+use rusqlite::{Connection, OptionalExtension};
 use serde::Serialize;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug)]
 pub struct Database {
@@ -17,347 +32,33 @@ pub struct InstalledMod {
 }
 
 impl Database {
-    const CURRENT_DB_VERSION: &'static str = "1.1"; // Update this when schema changes
-
     pub fn new() -> Result<Self, AppError> {
-        let config_dir = dirs::config_dir()
-            .ok_or_else(|| AppError::DirNotFound(PathBuf::from("config directory")))?;
-        let balatro_dir = config_dir.join("Balatro");
-
-        // Create directory if it doesn't exist
-        if !balatro_dir.exists() {
-            std::fs::create_dir_all(&balatro_dir).map_err(|e| {
-                AppError::DirNotFound(format!("Failed to create config directory: {e}").into())
-            })?;
-        }
-
-        // Create the Balatro config directory if it doesn't exist
-        let balatro_dir = config_dir.join("Balatro");
-        std::fs::create_dir_all(&balatro_dir).map_err(|e| AppError::DirCreate {
-            path: balatro_dir.clone(),
-            source: e.to_string(),
-        })?;
-
-        let storage_path = balatro_dir.join("bmm_storage.db");
-
-        // Now check if the database exists and open it
-        let db_exists = storage_path.exists();
-        let conn =
-            Connection::open(&storage_path).map_err(|e| AppError::DatabaseInit(e.to_string()))?;
-
-        if !db_exists {
-            Self::initialize_database(&conn)?;
-        }
-
-        // Try to open the database with a retry mechanism
-        let mut retry_count = 0;
-        let max_retries = 3;
-
-        while retry_count < max_retries {
-            // Try to open or create the database
-            let conn_result = if storage_path.exists() {
-                Connection::open(&storage_path)
-            } else {
-                // Create a new database
-                let conn = Connection::open(&storage_path)
-                    .map_err(|e| AppError::DatabaseInit(e.to_string()))?;
-                Self::initialize_database(&conn)?;
-                Ok(conn)
-            };
-
-            match conn_result {
-                Ok(conn) => {
-                    // Check if database needs migration
-                    if Self::needs_migration(&conn)? {
-                        // Close the connection before migration
-                        drop(conn);
-
-                        // Perform migration
-                        Self::migrate_database(&storage_path)?;
-
-                        // Reopen the database after migration
-                        let conn = Connection::open(&storage_path)
-                            .map_err(|e| AppError::DatabaseInit(e.to_string()))?;
-                        return Ok(Database { conn });
-                    }
-
-                    return Ok(Database { conn });
-                }
-                Err(e) => {
-                    if retry_count == max_retries - 1 {
-                        return Err(AppError::DatabaseInit(format!(
-                            "Failed to open database after {max_retries} attempts: {e}"
-                        )));
-                    }
-
-                    log::warn!(
-                        "Failed to open database, retrying ({}/{}): {}",
-                        retry_count + 1,
-                        max_retries,
-                        e
-                    );
-                    std::thread::sleep(std::time::Duration::from_millis(500));
-                    retry_count += 1;
-                }
-            }
-        }
-
-        Err(AppError::DatabaseInit(
-            "Failed to open database after maximum retries".to_string(),
-        ))
+        let paths = Paths::get()?;
+        paths::ensure_dir(paths.config_dir.clone())?;
+        Self::open(&paths.database())
     }
 
-    // Check if database needs migration
-    fn needs_migration(conn: &Connection) -> Result<bool, AppError> {
-        // First check if the version table exists
-        let has_version_setting = match conn.query_row(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='settings'",
-            [],
-            |row| row.get::<_, i64>(0),
-        ) {
-            Ok(count) if count > 0 => {
-                // Now check if the version setting exists
-                match conn.query_row(
-                    "SELECT COUNT(*) FROM settings WHERE setting = 'db_version'",
-                    [],
-                    |row| row.get::<_, i64>(0),
-                ) {
-                    Ok(count) => count > 0,
-                    Err(_) => false,
-                }
-            }
-            _ => false,
-        };
-
-        // If no version in settings, this is an old database needing migration
-        if !has_version_setting {
-            return Ok(true);
-        }
-
-        // Check if the version matches
-        match conn.query_row(
-            "SELECT value FROM settings WHERE setting = 'db_version'",
-            [],
-            |row| row.get::<_, String>(0),
-        ) {
-            Ok(version) => Ok(version != Self::CURRENT_DB_VERSION),
-            Err(_) => Ok(true), // If we can't get the version, assume migration is needed
-        }
+    /// Opens or creates the database at `path` and migrates it to the latest
+    /// schema, see [`migrations::migrate`].
+    pub fn open(path: &Path) -> Result<Self, AppError> {
+        let mut conn = Connection::open(path).map_err(|e| AppError::DatabaseInit(e.to_string()))?;
+        migrations::migrate(&mut conn, path)?;
+        Ok(Database { conn })
     }
 
-    fn migrate_database(db_path: &PathBuf) -> Result<(), AppError> {
-        // Create a temporary database path
-        let temp_db_path = db_path.with_file_name("bmm_storage_new.db");
-
-        // If the old database exists but we can't access it, try with a retry mechanism
-        let max_retries = 3;
-        let mut retry_count = 0;
-
-        while retry_count < max_retries {
-            // Open connections to both databases
-            let old_conn_result = Connection::open(db_path);
-
-            if let Err(e) = old_conn_result {
-                if retry_count == max_retries - 1 {
-                    return Err(AppError::DatabaseInit(format!(
-                        "Failed to open old database after {max_retries} retries: {e}"
-                    )));
-                }
-
-                // Wait before retrying
-                std::thread::sleep(std::time::Duration::from_millis(500));
-                retry_count += 1;
-                continue;
-            }
-
-            let old_conn = old_conn_result.unwrap();
-            let new_conn = Connection::open(&temp_db_path).map_err(|e| {
-                AppError::DatabaseInit(format!("Failed to create new database: {e}"))
-            })?;
-
-            // Initialize the new database with current schema
-            Self::initialize_database(&new_conn)?;
-
-            // Migrate data
-            Self::migrate_settings(&old_conn, &new_conn)?;
-            Self::migrate_installed_mods(&old_conn, &new_conn)?;
-
-            // IMPORTANT: Explicitly close connections before file operations
-            drop(old_conn);
-            drop(new_conn);
-
-            // Add a small delay to ensure all handles are released
-            std::thread::sleep(std::time::Duration::from_millis(100));
-
-            // Try to backup the old database
-            let backup_path = db_path.with_extension("db.bak");
-
-            // If backup fails, just log a warning but continue
-            match std::fs::rename(db_path, &backup_path) {
-                Ok(()) => {}
-                Err(e) => {
-                    log::warn!("Failed to backup old database, continuing anyway: {e}");
-                    // Try to directly remove the old file if we can't rename it
-                    if let Err(e) = std::fs::remove_file(db_path) {
-                        log::warn!("Failed to remove old database: {e}");
-                    }
-                }
-            }
-
-            // Replace with the new one
-            match std::fs::rename(&temp_db_path, db_path) {
-                Ok(()) => return Ok(()),
-                Err(e) => {
-                    return Err(AppError::DatabaseInit(format!(
-                        "Failed to install new database: {e}"
-                    )));
-                }
-            }
-        }
-
-        Err(AppError::DatabaseInit(
-            "Failed to access database after maximum retries".to_string(),
-        ))
-    }
-
-    fn migrate_settings(old_conn: &Connection, new_conn: &Connection) -> Result<(), AppError> {
-        // Check if settings table exists in old database
-        let has_settings = match old_conn.query_row(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='settings'",
-            [],
-            |row| row.get::<_, i64>(0),
-        ) {
-            Ok(count) => count > 0,
-            Err(_) => false,
-        };
-
-        if !has_settings {
-            return Ok(()); // No settings to migrate
-        }
-
-        // Get all settings except db_version (which will be set by initialize_database)
-        let mut stmt = match old_conn
-            .prepare("SELECT setting, value FROM settings WHERE setting != 'db_version'")
-        {
-            Ok(stmt) => stmt,
-            Err(_) => return Ok(()), // If query fails, just continue
-        };
-
-        for (setting, value) in stmt
-            .query_map([], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-            })?
-            .flatten()
-        {
-            new_conn.execute(
-                "INSERT OR REPLACE INTO settings (setting, value) VALUES (?1, ?2)",
-                [&setting, &value],
-            )?;
-        }
-
-        if let Ok(value) = old_conn.query_row(
-            "SELECT value FROM settings WHERE setting = 'security_warning_acknowledged'",
-            [],
-            |row| row.get::<_, String>(0),
-        ) {
-            new_conn.execute(
-            "INSERT OR REPLACE INTO settings (setting, value) VALUES ('security_warning_acknowledged', ?1)",
-            [&value],
-        )?;
-        }
-
-        Ok(())
-    }
-    // Migrate installed mods from old database to new one
-    fn migrate_installed_mods(
-        old_conn: &Connection,
-        new_conn: &Connection,
-    ) -> Result<(), AppError> {
-        // Check if installed_mods table exists in old database
-        let has_installed_mods = match old_conn.query_row(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='installed_mods'",
-            [],
-            |row| row.get::<_, i64>(0),
-        ) {
-            Ok(count) => count > 0,
-            Err(_) => false,
-        };
-
-        if !has_installed_mods {
-            return Ok(()); // No mods to migrate
-        }
-
-        // Get all installed mods
-        let mut stmt = match old_conn
-            .prepare("SELECT name, path, dependencies, current_version FROM installed_mods")
-        {
-            Ok(stmt) => stmt,
-            Err(_) => return Ok(()), // If query fails, just continue
-        };
-
-        for (name, path, dependencies, current_version) in stmt
-            .query_map([], |row| {
-                Ok((
-                    row.get::<_, String>(0)?,
-                    row.get::<_, String>(1)?,
-                    row.get::<_, String>(2)?,
-                    row.get::<_, Option<String>>(3)?,
-                ))
-            })?
-            .flatten()
-        {
-            new_conn.execute(
-            "INSERT INTO installed_mods (name, path, dependencies, current_version) VALUES (?1, ?2, ?3, ?4)",
-            [&name, &path, &dependencies, &current_version.unwrap_or_default()],
-        )?;
-        }
-
-        Ok(())
-    }
-
-    fn initialize_database(conn: &Connection) -> Result<(), AppError> {
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS settings (
-                setting TEXT PRIMARY KEY,
-                value TEXT NOT NULL
-            )",
-            [],
-        )
-        .map_err(|e| AppError::DatabaseInit(e.to_string()))?;
-
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS installed_mods (
-                name TEXT PRIMARY KEY,
-                path TEXT NOT NULL,
-                dependencies TEXT NOT NULL DEFAULT '[]',
-                current_version TEXT
-            )",
-            [],
-        )
-        .map_err(|e| AppError::DatabaseInit(e.to_string()))?;
-
-        // Set the database version
-        conn.execute(
-            "INSERT OR REPLACE INTO settings (setting, value) VALUES ('db_version', ?1)",
-            [Self::CURRENT_DB_VERSION],
-        )
-        .map_err(|e| AppError::DatabaseInit(e.to_string()))?;
-
-        conn.execute(
-            "INSERT OR REPLACE INTO settings (setting, value) VALUES ('security_warning_acknowledged', ?1)",
-            ["no"],
-        )
-        .map_err(|e| AppError::DatabaseInit(e.to_string()))?;
-
-        Ok(())
+    /// The id of the active installation, which installed mods and
+    /// operations belong to.
+    fn installation_id(&self) -> Result<i64, AppError> {
+        self.installations().active_id()
     }
 
     pub fn get_mod_details(&self, mod_name: &str) -> Result<InstalledMod, AppError> {
         let mut stmt = self.conn.prepare(
-            "SELECT name, path, dependencies, current_version FROM installed_mods WHERE name = ?1",
+            "SELECT name, path, dependencies, current_version FROM installed_mods
+             WHERE installation_id = ?1 AND name = ?2",
         )?;
 
-        let mut rows = stmt.query([mod_name])?;
+        let mut rows = stmt.query(rusqlite::params![self.installation_id()?, mod_name])?;
 
         if let Some(row) = rows.next()? {
             Ok(InstalledMod {
@@ -395,11 +96,12 @@ impl Database {
     }
 
     pub fn get_installed_mods(&self) -> Result<Vec<InstalledMod>, AppError> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT name, path, dependencies, current_version FROM installed_mods")?;
+        let mut stmt = self.conn.prepare(
+            "SELECT name, path, dependencies, current_version FROM installed_mods
+             WHERE installation_id = ?1",
+        )?;
         let mut mods = Vec::new();
-        let mut rows = stmt.query([])?;
+        let mut rows = stmt.query([self.installation_id()?])?;
 
         while let Some(row) = rows.next()? {
             mods.push(InstalledMod {
@@ -421,72 +123,456 @@ impl Database {
         current_version: Option<String>,
     ) -> Result<(), AppError> {
         let deps_json = serde_json::to_string(dependencies)?;
-        self.conn.execute(
-            "INSERT OR REPLACE INTO installed_mods (name, path, dependencies, current_version) VALUES (?1, ?2, ?3, ?4)",
-            [name, path, &deps_json, &current_version.unwrap_or_default()],
-        )?;
-        Ok(())
+        write_installed_mod(
+            &self.conn,
+            self.installation_id()?,
+            name,
+            path,
+            &deps_json,
+            &current_version.unwrap_or_default(),
+        )
     }
 
-    pub fn get_dependents(&self, mod_name: &str) -> Result<Vec<String>, AppError> {
-        let mut stmt = self.conn.prepare(
-            "SELECT name FROM installed_mods
-            WHERE EXISTS (
-                SELECT 1 FROM json_each(dependencies)
-                WHERE TRIM(json_each.value, '\"') = ?1
-            )",
-        )?;
+    /// Tracks a mod that was just installed from `source` and adds it to the
+    /// version history, in one transaction so the history never pairs a
+    /// version with another install's archive. A version installed again
+    /// just moves to the front. Only the newest [`KEPT_VERSIONS`] are kept.
+    pub fn record_install(
+        &self,
+        name: &str,
+        path: &str,
+        dependencies: &[String],
+        current_version: Option<String>,
+        source: &ModSource,
+    ) -> Result<(), AppError> {
+        self.write_install(None, name, path, dependencies, current_version, source)
+    }
 
-        let mut rows = stmt.query([mod_name])?;
-        let mut dependents = Vec::new();
+    /// [`Self::record_install`] for a mod that takes the place of the one
+    /// tracked as `old_name`, which is forgotten in the same transaction. A
+    /// failure leaves the old mod tracked instead of neither.
+    pub fn replace_install(
+        &self,
+        old_name: &str,
+        name: &str,
+        path: &str,
+        dependencies: &[String],
+        current_version: Option<String>,
+        source: &ModSource,
+    ) -> Result<(), AppError> {
+        self.write_install(
+            Some(old_name),
+            name,
+            path,
+            dependencies,
+            current_version,
+            source,
+        )
+    }
 
-        while let Some(row) = rows.next()? {
-            dependents.push(row.get(0)?);
+    fn write_install(
+        &self,
+        replaces: Option<&str>,
+        name: &str,
+        path: &str,
+        dependencies: &[String],
+        current_version: Option<String>,
+        source: &ModSource,
+    ) -> Result<(), AppError> {
+        let installation_id = self.installation_id()?;
+        let deps_json = serde_json::to_string(dependencies)?;
+        let version = current_version.unwrap_or_default();
+
+        let tx = self.conn.unchecked_transaction()?;
+        if let Some(old_name) = replaces {
+            delete_installed_mod(&tx, installation_id, old_name)?;
         }
+        write_installed_mod(&tx, installation_id, name, path, &deps_json, &version)?;
+        write_mod_source(&tx, path, source)?;
+        let unreferenced = write_version(&tx, installation_id, name, &version, &deps_json, source)?;
+        tx.commit()?;
 
-        Ok(dependents)
+        version_history::retain_archives(&source.archive_hash, &unreferenced);
+        Ok(())
+    }
+
+    /// Tracked mods that list `mod_name` in any alternative of their
+    /// dependencies, ignoring version constraints.
+    pub fn get_dependents(&self, mod_name: &str) -> Result<Vec<String>, AppError> {
+        Ok(self
+            .get_installed_mods()?
+            .into_iter()
+            .filter(|m| {
+                m.dependencies
+                    .iter()
+                    .any(|spec| dependencies::spec_mentions(spec, mod_name))
+            })
+            .map(|m| m.name)
+            .collect())
     }
 
     pub fn remove_installed_mod(&self, name: &str) -> Result<(), AppError> {
-        self.conn
-            .execute("DELETE FROM installed_mods WHERE name = ?1", [name])?;
+        let installation_id = self.installation_id()?;
+        let tx = self.conn.unchecked_transaction()?;
+        delete_installed_mod(&tx, installation_id, name)?;
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn get_installation_path(&self) -> Result<Option<String>, AppError> {
+    /// Remembers where the mod installed at `path` was downloaded from, and
+    /// replaces its file manifest with `source.files`.
+    pub fn set_mod_source(&self, path: &str, source: &ModSource) -> Result<(), AppError> {
+        let tx = self.conn.unchecked_transaction()?;
+        write_mod_source(&tx, path, source)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_mod_source(&self, path: &str) -> Result<Option<ModSource>, AppError> {
+        let Some((download_url, archive_hash)) = self
+            .conn
+            .query_row(
+                "SELECT download_url, archive_hash FROM mod_sources WHERE path = ?1",
+                [path],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+        else {
+            return Ok(None);
+        };
+
         let mut stmt = self
             .conn
-            .prepare("SELECT value FROM settings WHERE setting = 'installation_path'")?;
-        let mut rows = stmt.query([])?;
+            .prepare("SELECT path, sha256 FROM mod_files WHERE mod_path = ?1 ORDER BY path")?;
+        let files = stmt
+            .query_map([path], |row| {
+                Ok(FileHash {
+                    path: row.get(0)?,
+                    sha256: row.get(1)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
 
-        if let Some(row) = rows.next()? {
-            Ok(Some(row.get(0)?))
-        } else {
-            Ok(None)
-        }
+        Ok(Some(ModSource {
+            download_url,
+            archive_hash,
+            files,
+        }))
     }
 
-    pub fn set_installation_path(&self, path: &str) -> Result<(), AppError> {
+    /// The archive most recently recorded for `version` of a mod downloaded
+    /// from `url`, in any installation.
+    pub fn recorded_archive_hash(
+        &self,
+        url: &str,
+        version: &str,
+    ) -> Result<Option<String>, AppError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT archive_hash FROM mod_versions WHERE download_url = ?1 AND version = ?2
+                 ORDER BY installed_at DESC, rowid DESC LIMIT 1",
+                [url, version],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// The remembered versions of `name`, newest first.
+    pub fn get_mod_versions(&self, name: &str) -> Result<Vec<ModVersion>, AppError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT v.version, v.download_url, v.archive_hash, v.dependencies, v.installed_at,
+                    v.archive_hash = (SELECT s.archive_hash FROM mod_sources s
+                                      JOIN installed_mods i ON i.path = s.path
+                                      WHERE i.installation_id = ?2 AND i.name = v.mod_name)
+             FROM mod_versions v WHERE v.installation_id = ?2 AND v.mod_name = ?1
+             ORDER BY v.installed_at DESC, v.rowid DESC",
+        )?;
+        let rows = stmt
+            .query_map(rusqlite::params![name, self.installation_id()?], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, u64>(4)?,
+                    row.get::<_, Option<bool>>(5)?.unwrap_or(false),
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.into_iter()
+            .map(
+                |(version, download_url, archive_hash, dependencies, installed_at, current)| {
+                    Ok(ModVersion {
+                        name: name.to_string(),
+                        version,
+                        download_url,
+                        archive_hash,
+                        dependencies: serde_json::from_str(&dependencies)?,
+                        installed_at,
+                        current,
+                        archived: false,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    pub fn set_mod_pin(&self, pin: &ModPin) -> Result<(), AppError> {
         self.conn.execute(
-            "INSERT OR REPLACE INTO settings (setting, value) VALUES (?1, ?2)",
-            ["installation_path", path],
+            "INSERT OR REPLACE INTO mod_pins (installation_id, mod_name, version_constraint, reason)
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![self.installation_id()?, pin.name, pin.constraint, pin.reason],
         )?;
         Ok(())
     }
 
-    pub fn remove_installation_path(&self) -> Result<(), AppError> {
+    /// Removes the pin on `name`, returning whether there was one.
+    pub fn remove_mod_pin(&self, name: &str) -> Result<bool, AppError> {
+        Ok(self.conn.execute(
+            "DELETE FROM mod_pins WHERE installation_id = ?1 AND mod_name = ?2",
+            rusqlite::params![self.installation_id()?, name],
+        )? > 0)
+    }
+
+    pub fn get_mod_pin(&self, name: &str) -> Result<Option<ModPin>, AppError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT mod_name, version_constraint, reason FROM mod_pins
+                 WHERE installation_id = ?1 AND mod_name = ?2",
+                rusqlite::params![self.installation_id()?, name],
+                |row| {
+                    Ok(ModPin {
+                        name: row.get(0)?,
+                        constraint: row.get(1)?,
+                        reason: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    pub fn get_mod_pins(&self) -> Result<Vec<ModPin>, AppError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT mod_name, version_constraint, reason FROM mod_pins WHERE installation_id = ?1
+             ORDER BY mod_name",
+        )?;
+        let pins = stmt
+            .query_map([self.installation_id()?], |row| {
+                Ok(ModPin {
+                    name: row.get(0)?,
+                    constraint: row.get(1)?,
+                    reason: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(pins)
+    }
+
+    /// Drops tracked mods whose directory no longer exists, returning the
+    /// entries that were removed. The filesystem is left untouched.
+    pub fn remove_missing_mods(&self) -> Result<Vec<InstalledMod>, AppError> {
+        let missing = self
+            .get_installed_mods()?
+            .into_iter()
+            .filter(|m| !PathBuf::from(&m.path).exists())
+            .collect::<Vec<_>>();
+
+        for installed_mod in &missing {
+            self.remove_installed_mod(&installed_mod.name)?;
+        }
+
+        Ok(missing)
+    }
+
+    /// Appends an entry to the operation log. It failed when there's an
+    /// `error`.
+    pub fn log_operation(
+        &self,
+        kind: OperationKind,
+        mods: &[AffectedMod],
+        detail: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<(), AppError> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let outcome = if error.is_some() {
+            "failed"
+        } else {
+            "succeeded"
+        };
         self.conn.execute(
-            "DELETE FROM settings WHERE setting = 'installation_path'",
-            [],
+            "INSERT INTO operations (installation_id, timestamp, kind, mods, outcome, detail, error)
+             VALUES (?7, ?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![
+                timestamp,
+                kind.as_str(),
+                serde_json::to_string(mods)?,
+                outcome,
+                detail,
+                error,
+                self.installation_id()?
+            ],
         )?;
         Ok(())
     }
 
+    /// A page of the active installation's operation log, newest first.
+    /// `page` starts at 1. With `mod_name`, only operations that touched that
+    /// mod are included.
+    pub fn get_operations(
+        &self,
+        page: u32,
+        per_page: u32,
+        mod_name: Option<&str>,
+    ) -> Result<OperationsPage, AppError> {
+        let page = page.max(1);
+        let per_page = per_page.max(1);
+        let installation_id = self.installation_id()?;
+        let filter = "?1 IS NULL OR EXISTS (
+            SELECT 1 FROM json_each(operations.mods)
+            WHERE json_extract(json_each.value, '$.name') = ?1 COLLATE NOCASE
+        )";
+
+        let total = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM operations WHERE installation_id = ?2 AND ({filter})"),
+            rusqlite::params![mod_name, installation_id],
+            |row| row.get::<_, u64>(0),
+        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, timestamp, kind, mods, outcome, detail, error FROM operations
+             WHERE installation_id = ?4 AND ({filter}) ORDER BY id DESC LIMIT ?2 OFFSET ?3"
+        ))?;
+        let rows = stmt
+            .query_map(
+                rusqlite::params![
+                    mod_name,
+                    per_page,
+                    u64::from(page - 1) * u64::from(per_page),
+                    installation_id
+                ],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, u64>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(4)?,
+                        row.get::<_, Option<String>>(5)?,
+                        row.get::<_, Option<String>>(6)?,
+                    ))
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let operations = rows
+            .into_iter()
+            .map(|(id, timestamp, kind, mods, outcome, detail, error)| {
+                Ok(Operation {
+                    id,
+                    timestamp,
+                    kind: OperationKind::parse(&kind)?,
+                    mods: serde_json::from_str(&mods)?,
+                    outcome: if outcome == "failed" {
+                        Outcome::Failed
+                    } else {
+                        Outcome::Succeeded
+                    },
+                    detail,
+                    error,
+                })
+            })
+            .collect::<Result<Vec<_>, AppError>>()?;
+
+        Ok(OperationsPage {
+            operations,
+            total,
+            page,
+            per_page,
+        })
+    }
+
+    #[must_use]
+    pub fn collections(&self) -> ModCollectionManager<'_> {
+        ModCollectionManager::new(&self.conn)
+    }
+
+    #[must_use]
+    pub fn installations(&self) -> InstallationManager<'_> {
+        InstallationManager::new(&self.conn)
+    }
+
+    /// The game directory of the active installation.
+    pub fn get_installation_path(&self) -> Result<Option<String>, AppError> {
+        Ok(self.installations().active()?.game_dir)
+    }
+
+    /// The Mods directory of the active installation, see
+    /// [`Installation::resolved_mods_dir`](crate::installations::Installation::resolved_mods_dir).
+    pub fn get_mods_dir(&self) -> Result<PathBuf, AppError> {
+        self.installations().active()?.resolved_mods_dir()
+    }
+
+    pub fn get_settings(&self) -> Result<Settings, AppError> {
+        let mut stmt = self.conn.prepare("SELECT setting, value FROM settings")?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<HashMap<String, String>, _>>()?;
+        Ok(Settings::from_rows(&rows, self.get_installation_path()?))
+    }
+
+    /// Validates `settings` and stores them in one go. A new installation
+    /// path switches to the installation at that path if there is one, and
+    /// otherwise moves the active installation there.
+    pub fn set_settings(&self, settings: &Settings) -> Result<(), AppError> {
+        let current = self.get_settings()?;
+        settings.validate(&current)?;
+        let tx = self.conn.unchecked_transaction()?;
+        for (setting, value) in settings.to_rows() {
+            tx.execute(
+                "INSERT OR REPLACE INTO settings (setting, value) VALUES (?1, ?2)",
+                [setting, value],
+            )?;
+        }
+        if settings.installation_path != current.installation_path {
+            let installations = InstallationManager::new(&tx);
+            match settings
+                .installation_path
+                .as_deref()
+                .map(|path| installations.by_game_dir(path))
+                .transpose()?
+                .flatten()
+            {
+                Some(existing) => {
+                    installations.switch(&existing.name)?;
+                }
+                None => installations.set_game_dir(
+                    installations.active_id()?,
+                    settings.installation_path.as_deref(),
+                )?,
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Changes some of the settings, keeping the rest, and returns the result.
+    pub fn update_settings(
+        &self,
+        update: impl FnOnce(&mut Settings),
+    ) -> Result<Settings, AppError> {
+        let mut settings = self.get_settings()?;
+        update(&mut settings);
+        self.set_settings(&settings)?;
+        Ok(settings)
+    }
+
     pub fn get_last_installed_version(&self, mod_name: &str) -> Result<String, AppError> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT current_version FROM installed_mods WHERE name = ?1")?;
-        let mut rows = stmt.query([mod_name])?;
+        let mut stmt = self.conn.prepare(
+            "SELECT current_version FROM installed_mods WHERE installation_id = ?1 AND name = ?2",
+        )?;
+        let mut rows = stmt.query(rusqlite::params![self.installation_id()?, mod_name])?;
 
         if let Some(row) = rows.next()? {
             Ok(row.get(0)?)
@@ -501,54 +587,202 @@ impl Database {
         version: &str,
     ) -> Result<(), AppError> {
         self.conn.execute(
-            "UPDATE installed_mods SET current_version = ?1 WHERE name = ?2",
-            [version, mod_name],
+            "UPDATE installed_mods SET current_version = ?1 WHERE installation_id = ?2 AND name = ?3",
+            rusqlite::params![version, self.installation_id()?, mod_name],
         )?;
         Ok(())
     }
 
-    pub fn set_background_enabled(&self, enabled: bool) -> Result<(), AppError> {
-        let enabled: &str = if enabled { "enabled" } else { "disabled" };
+    /// The lovely bromomethane installed at `path`, if it installed one.
+    pub fn get_lovely_install(&self, path: &Path) -> Result<Option<LovelyInstall>, AppError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT path, version, sha256, installed_at FROM lovely_installs WHERE path = ?1",
+                [path.to_string_lossy()],
+                |row| {
+                    Ok(LovelyInstall {
+                        path: row.get(0)?,
+                        version: row.get(1)?,
+                        sha256: row.get(2)?,
+                        installed_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    pub fn set_lovely_install(&self, install: &LovelyInstall) -> Result<(), AppError> {
         self.conn.execute(
-            "INSERT OR REPLACE INTO settings (setting, value) VALUES ('background_enabled', ?1)",
-            [enabled],
+            "INSERT OR REPLACE INTO lovely_installs (path, version, sha256, installed_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![
+                install.path,
+                install.version,
+                install.sha256,
+                install.installed_at
+            ],
         )?;
         Ok(())
     }
 
-    pub fn get_background_enabled(&self) -> Result<bool, AppError> {
-        let mut stmt = self
+    /// The game version last recorded for the active installation.
+    pub fn get_game_version(&self) -> Result<Option<GameVersion>, AppError> {
+        Ok(self
             .conn
-            .prepare("SELECT value FROM settings WHERE setting = 'background_enabled'")?;
-        let mut rows = stmt.query([])?;
-
-        if let Some(row) = rows.next()? {
-            Ok(row.get::<_, String>(0)? == "enabled")
-        } else {
-            Ok(false)
-        }
+            .query_row(
+                "SELECT version, build_id, branch, detected_at FROM game_versions
+                 WHERE installation_id = ?1",
+                [self.installation_id()?],
+                |row| {
+                    Ok(GameVersion {
+                        version: row.get(0)?,
+                        build_id: row.get(1)?,
+                        branch: row.get(2)?,
+                        detected_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?)
     }
 
-    pub fn set_security_warning_acknowledged(&self, acknowledged: bool) -> Result<(), AppError> {
-        let value = if acknowledged { "yes" } else { "no" };
+    pub fn set_game_version(&self, version: &GameVersion) -> Result<(), AppError> {
         self.conn.execute(
-        "INSERT OR REPLACE INTO settings (setting, value) VALUES ('security_warning_acknowledged', ?1)",
-        [value],
-    )?;
+            "INSERT OR REPLACE INTO game_versions
+                (installation_id, version, build_id, branch, detected_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                self.installation_id()?,
+                version.version,
+                version.build_id,
+                version.branch,
+                version.detected_at
+            ],
+        )?;
         Ok(())
     }
+}
 
-    pub fn is_security_warning_acknowledged(&self) -> Result<bool, AppError> {
-        let mut stmt = self.conn.prepare(
-            "SELECT value FROM settings WHERE setting = 'security_warning_acknowledged'",
+fn write_installed_mod(
+    conn: &Connection,
+    installation_id: i64,
+    name: &str,
+    path: &str,
+    deps_json: &str,
+    version: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO installed_mods (installation_id, name, path, dependencies, current_version)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![installation_id, name, path, deps_json, version],
+    )?;
+    Ok(())
+}
+
+/// Forgets the mod tracked as `name`, along with its source and file
+/// manifest.
+fn delete_installed_mod(
+    conn: &Connection,
+    installation_id: i64,
+    name: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        "DELETE FROM mod_sources WHERE path IN
+            (SELECT path FROM installed_mods WHERE installation_id = ?1 AND name = ?2)",
+        rusqlite::params![installation_id, name],
+    )?;
+    conn.execute(
+        "DELETE FROM mod_files WHERE mod_path IN
+            (SELECT path FROM installed_mods WHERE installation_id = ?1 AND name = ?2)",
+        rusqlite::params![installation_id, name],
+    )?;
+    conn.execute(
+        "DELETE FROM installed_mods WHERE installation_id = ?1 AND name = ?2",
+        rusqlite::params![installation_id, name],
+    )?;
+    Ok(())
+}
+
+fn write_mod_source(conn: &Connection, path: &str, source: &ModSource) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO mod_sources (path, download_url, archive_hash) VALUES (?1, ?2, ?3)",
+        [path, &source.download_url, &source.archive_hash],
+    )?;
+    conn.execute("DELETE FROM mod_files WHERE mod_path = ?1", [path])?;
+    let mut stmt =
+        conn.prepare("INSERT INTO mod_files (mod_path, path, sha256) VALUES (?1, ?2, ?3)")?;
+    for file in &source.files {
+        stmt.execute([path, &file.path, &file.sha256])?;
+    }
+    Ok(())
+}
+
+/// Puts `version` at the front of the history of `name` in installation
+/// `installation_id` and trims that history to [`KEPT_VERSIONS`], returning
+/// the hashes of dropped archives that no history refers to anymore.
+fn write_version(
+    conn: &Connection,
+    installation_id: i64,
+    name: &str,
+    version: &str,
+    deps_json: &str,
+    source: &ModSource,
+) -> Result<Vec<String>, AppError> {
+    let installed_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    // Replaced rather than updated, so a version installed again within the
+    // same second still sorts first
+    conn.execute(
+        "DELETE FROM mod_versions
+         WHERE installation_id = ?1 AND mod_name = ?2 AND archive_hash = ?3",
+        rusqlite::params![installation_id, name, source.archive_hash],
+    )?;
+    conn.execute(
+        "INSERT INTO mod_versions
+            (installation_id, mod_name, version, download_url, archive_hash, dependencies, installed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            installation_id,
+            name,
+            version,
+            source.download_url,
+            source.archive_hash,
+            deps_json,
+            installed_at
+        ],
+    )?;
+    let dropped = conn
+        .prepare(
+            "SELECT archive_hash FROM mod_versions WHERE installation_id = ?1 AND mod_name = ?2
+             ORDER BY installed_at DESC, rowid DESC LIMIT -1 OFFSET ?3",
+        )?
+        .query_map(
+            rusqlite::params![installation_id, name, KEPT_VERSIONS],
+            |row| row.get::<_, String>(0),
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    for sha256 in &dropped {
+        conn.execute(
+            "DELETE FROM mod_versions
+             WHERE installation_id = ?1 AND mod_name = ?2 AND archive_hash = ?3",
+            rusqlite::params![installation_id, name, sha256],
         )?;
+    }
 
-        let mut rows = stmt.query([])?;
-        if let Some(row) = rows.next()? {
-            Ok(row.get::<_, String>(0)? == "yes")
-        } else {
-            // Default to not acknowledged
-            Ok(false)
+    // Another mod's or installation's history may share an archive
+    let mut unreferenced = Vec::new();
+    for sha256 in dropped {
+        let references = conn.query_row(
+            "SELECT COUNT(*) FROM mod_versions WHERE archive_hash = ?1",
+            [&sha256],
+            |row| row.get::<_, i64>(0),
+        )?;
+        if references == 0 {
+            unreferenced.push(sha256);
         }
     }
+    Ok(unreferenced)
 }
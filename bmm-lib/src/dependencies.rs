@@ -0,0 +1,708 @@
+use crate::cache;
+use crate::conflicts;
+use crate::database::Database;
+use crate::download::DownloadJob;
+use crate::errors::AppError;
+use crate::installer::{self, StagedCheck, StagedMod};
+use crate::local_mod_detection::{self, DetectedMod};
+use crate::operations::{self, AffectedMod, OperationKind};
+use crate::version::Version;
+use crate::version_history;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// Ids that refer to the game or the injector rather than to a mod. Nothing in
+/// the catalog can satisfy them, so they're assumed to be present.
+const UNMANAGED_IDS: [&str; 2] = ["balatro", "lovely"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    /// Splits the operator off the front of a constraint such as `>=1.0.0`.
+    /// Steamodded spells strict comparisons `<<` and `>>`, single `<` and `>`
+    /// are accepted too. A bare version means an exact match.
+    fn split(constraint: &str) -> (Self, &str) {
+        const OPERATORS: [(&str, Comparison); 8] = [
+            ("<<", Comparison::Less),
+            ("<=", Comparison::LessOrEqual),
+            ("==", Comparison::Equal),
+            (">=", Comparison::GreaterOrEqual),
+            (">>", Comparison::Greater),
+            ("<", Comparison::Less),
+            ("=", Comparison::Equal),
+            (">", Comparison::Greater),
+        ];
+
+        OPERATORS
+            .iter()
+            .find_map(|(op, comparison)| {
+                constraint
+                    .strip_prefix(op)
+                    .map(|version| (*comparison, version))
+            })
+            .unwrap_or((Comparison::Equal, constraint))
+    }
+
+    #[must_use]
+    pub fn matches(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Less => ordering.is_lt(),
+            Comparison::LessOrEqual => ordering.is_le(),
+            Comparison::Equal => ordering.is_eq(),
+            Comparison::GreaterOrEqual => ordering.is_ge(),
+            Comparison::Greater => ordering.is_gt(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionConstraint {
+    pub comparison: Comparison,
+    pub version: Version,
+}
+
+impl VersionConstraint {
+    #[must_use]
+    pub fn matches(&self, version: &Version) -> bool {
+        self.comparison.matches(version.cmp(&self.version))
+    }
+}
+
+impl FromStr for VersionConstraint {
+    type Err = AppError;
+
+    /// Parses a single constraint such as `>=1.0.0` or `<<2.0`.
+    fn from_str(constraint: &str) -> Result<Self, Self::Err> {
+        let (comparison, version) = Comparison::split(constraint.trim());
+        Ok(Self {
+            comparison,
+            version: version.trim().parse()?,
+        })
+    }
+}
+
+/// A single mod id with the version constraints it has to satisfy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Requirement {
+    pub id: String,
+    pub constraints: Vec<VersionConstraint>,
+}
+
+impl Requirement {
+    #[must_use]
+    pub fn matches_id(&self, id: &str) -> bool {
+        normalize_id(&self.id) == normalize_id(id)
+    }
+
+    /// Most mods don't declare a version, so an unknown one is given the
+    /// benefit of the doubt.
+    #[must_use]
+    pub fn matches_version(&self, version: Option<&Version>) -> bool {
+        version.is_none_or(|v| self.constraints.iter().all(|c| c.matches(v)))
+    }
+
+    fn is_unmanaged(&self) -> bool {
+        UNMANAGED_IDS.contains(&normalize_id(&self.id).as_str())
+    }
+}
+
+/// A parsed entry of a mod's `dependencies` or `conflicts`, e.g.
+/// `Steamodded (>=1.0.0~BETA-0827c)` or `Talisman (>=2.0) | Cryptid`.
+///
+/// Alternatives are separated by `|`, and each one may be followed by any
+/// number of parenthesized, comma separated version constraints.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DependencySpec {
+    pub alternatives: Vec<Requirement>,
+}
+
+impl DependencySpec {
+    #[must_use]
+    pub fn mentions(&self, id: &str) -> bool {
+        self.alternatives.iter().any(|req| req.matches_id(id))
+    }
+}
+
+impl FromStr for DependencySpec {
+    type Err = AppError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let alternatives = spec
+            .split('|')
+            .map(|alternative| parse_requirement(spec, alternative))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { alternatives })
+    }
+}
+
+fn parse_requirement(spec: &str, alternative: &str) -> Result<Requirement, AppError> {
+    let invalid = |reason: &str| AppError::InvalidDependency {
+        spec: spec.to_string(),
+        reason: reason.to_string(),
+    };
+
+    let alternative = alternative.trim();
+    let (id, mut rest) = match alternative.find('(') {
+        Some(i) => (alternative[..i].trim(), &alternative[i..]),
+        None => (alternative, ""),
+    };
+    if id.is_empty() {
+        return Err(invalid("missing mod id"));
+    }
+    if id.contains(char::is_whitespace) {
+        return Err(invalid("mod ids can't contain whitespace"));
+    }
+
+    let mut constraints = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        let inner = rest
+            .strip_prefix('(')
+            .ok_or_else(|| invalid("expected `(` before a version constraint"))?;
+        let end = inner
+            .find(')')
+            .ok_or_else(|| invalid("unclosed parenthesis"))?;
+
+        for constraint in inner[..end].split(',').map(str::trim) {
+            if constraint.is_empty() {
+                continue;
+            }
+            constraints.push(constraint.parse()?);
+        }
+        rest = &inner[end + 1..];
+    }
+
+    Ok(Requirement {
+        id: id.to_string(),
+        constraints,
+    })
+}
+
+/// Parses an entry of a mod's `provides` list, `Id` or `Id (version)`.
+pub fn parse_provided(entry: &str) -> Result<(String, Option<Version>), AppError> {
+    let requirement = parse_requirement(entry, entry)?;
+    let version = match requirement.constraints.as_slice() {
+        [] => None,
+        [constraint] if constraint.comparison == Comparison::Equal => {
+            Some(constraint.version.clone())
+        }
+        _ => {
+            return Err(AppError::InvalidDependency {
+                spec: entry.to_string(),
+                reason: "a provided mod can only have a single version".to_string(),
+            });
+        }
+    };
+    Ok((requirement.id, version))
+}
+
+/// Whether a raw dependency string names `id` in any of its alternatives.
+/// Unparseable strings fall back to a plain comparison.
+#[must_use]
+pub fn spec_mentions(spec: &str, id: &str) -> bool {
+    spec.parse::<DependencySpec>().map_or_else(
+        |_| normalize_id(spec) == normalize_id(id),
+        |spec| spec.mentions(id),
+    )
+}
+
+/// Mod ids, catalog titles and folder names are compared case-insensitively
+/// and without punctuation, so `Jen's Almanac` matches `JensAlmanac`.
+fn normalize_id(id: &str) -> String {
+    id.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn parse_optional_version(version: Option<&str>) -> Option<Version> {
+    version
+        .filter(|v| !v.is_empty())
+        .and_then(|v| v.parse().ok())
+}
+
+#[derive(Debug, Clone)]
+struct AvailableMod {
+    id: String,
+    version: Option<Version>,
+}
+
+/// Every mod id that's currently present in the Mods directory, including the
+/// ids mods claim to `provide`.
+#[derive(Debug, Default)]
+pub struct InstalledSet {
+    mods: Vec<AvailableMod>,
+}
+
+impl InstalledSet {
+    /// Collects the tracked mods along with their on-disk metadata, and any
+    /// mods that were installed manually.
+    pub fn from_database(db: &Database) -> Result<Self, AppError> {
+        let mut set = Self::default();
+
+        for installed_mod in db.get_installed_mods()? {
+            set.insert(
+                &installed_mod.name,
+                parse_optional_version(installed_mod.current_version.as_deref()),
+            );
+            if let Some(detected) = local_mod_detection::detect_mod(Path::new(&installed_mod.path))
+            {
+                set.add_detected(&detected);
+            }
+        }
+
+        let mods_dir = db.get_mods_dir()?;
+        let manual_mods =
+            local_mod_detection::detect_manual_mods(db, &[]).map_err(|e| AppError::FileRead {
+                path: mods_dir,
+                source: e,
+            })?;
+        for detected in &manual_mods {
+            set.add_detected(detected);
+        }
+
+        Ok(set)
+    }
+
+    /// The ids a single mod answers to.
+    #[must_use]
+    pub fn from_detected(detected: &DetectedMod) -> Self {
+        let mut set = Self::default();
+        set.add_detected(detected);
+        set
+    }
+
+    pub fn add_detected(&mut self, detected: &DetectedMod) {
+        let version = parse_optional_version(detected.version.as_deref());
+        self.insert(&detected.id, version.clone());
+        self.insert(&detected.name, version.clone());
+
+        for entry in &detected.provides {
+            match parse_provided(entry) {
+                Ok((id, provided_version)) => {
+                    self.insert(&id, provided_version.or_else(|| version.clone()));
+                }
+                Err(e) => log::warn!("Ignoring `provides` entry of {}: {e}", detected.name),
+            }
+        }
+    }
+
+    /// Records a mod that's about to be at `version`, e.g. because an update
+    /// to it is planned.
+    pub fn add_version(&mut self, id: &str, version: &str) {
+        self.insert(id, version.parse().ok());
+    }
+
+    pub fn add_catalog(&mut self, catalog_mod: &cache::Mod) {
+        self.insert(
+            &catalog_mod.title,
+            parse_optional_version(catalog_mod.version.as_deref()),
+        );
+    }
+
+    fn insert(&mut self, id: &str, version: Option<Version>) {
+        if !id.is_empty() {
+            self.mods.push(AvailableMod {
+                id: id.to_string(),
+                version,
+            });
+        }
+    }
+
+    #[must_use]
+    pub fn satisfies(&self, requirement: &Requirement) -> bool {
+        self.mods.iter().any(|m| {
+            requirement.matches_id(&m.id) && requirement.matches_version(m.version.as_ref())
+        })
+    }
+}
+
+/// A dependency that neither the installed mods nor the catalog can satisfy.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnmetDependency {
+    pub dependent: String,
+    pub spec: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ResolutionPlan {
+    /// Catalog titles to install, each one after all of its dependencies.
+    pub install: Vec<String>,
+    pub unmet: Vec<UnmetDependency>,
+}
+
+/// The dependencies the catalog declares for a mod through its flags.
+#[must_use]
+pub fn catalog_dependencies(catalog_mod: &cache::Mod) -> Vec<String> {
+    let mut dependencies = Vec::new();
+    if catalog_mod.requires_steamodded {
+        dependencies.push("Steamodded".to_string());
+    }
+    if catalog_mod.requires_talisman {
+        dependencies.push("Talisman".to_string());
+    }
+    dependencies
+}
+
+/// The dependencies to track for an installed catalog mod: the ones the
+/// catalog declares, plus the ones from the mod's own metadata.
+#[must_use]
+pub fn tracked_dependencies(
+    catalog_mod: &cache::Mod,
+    detected: Option<&DetectedMod>,
+) -> Vec<String> {
+    let mut dependencies = catalog_dependencies(catalog_mod);
+    for spec in detected
+        .map(|d| d.dependencies.as_slice())
+        .unwrap_or_default()
+    {
+        let already_listed = spec
+            .parse::<DependencySpec>()
+            .is_ok_and(|parsed| dependencies.iter().any(|dep| parsed.mentions(dep)));
+        if !already_listed {
+            dependencies.push(spec.clone());
+        }
+    }
+    dependencies
+}
+
+#[must_use]
+pub fn find_in_catalog<'a>(catalog: &'a [cache::Mod], id: &str) -> Option<&'a cache::Mod> {
+    let id = normalize_id(id);
+    catalog
+        .iter()
+        .find(|m| normalize_id(&m.title) == id)
+        .or_else(|| {
+            catalog.iter().find(|m| {
+                m.folder_name
+                    .as_deref()
+                    .is_some_and(|folder| normalize_id(folder) == id)
+            })
+        })
+}
+
+/// Plans the installation of the catalog mod `requested` and everything it
+/// transitively needs that isn't installed yet. The requested mod is always
+/// the last entry of the plan.
+pub fn resolve(
+    requested: &str,
+    installed: &InstalledSet,
+    catalog: &[cache::Mod],
+) -> Result<ResolutionPlan, AppError> {
+    let root = find_in_catalog(catalog, requested).ok_or_else(|| AppError::ModNotFound {
+        mod_name: requested.to_string(),
+        version: "any".to_string(),
+    })?;
+
+    let mut resolver = Resolver::new(installed, catalog);
+    resolver.visit(root);
+    Ok(resolver.plan)
+}
+
+/// Plans what has to be installed for `dependent` to have all of `specs`,
+/// e.g. after its own metadata was read from disk.
+#[must_use]
+pub fn resolve_specs(
+    dependent: &str,
+    specs: &[String],
+    installed: &InstalledSet,
+    catalog: &[cache::Mod],
+) -> ResolutionPlan {
+    let mut resolver = Resolver::new(installed, catalog);
+    resolver.visiting.insert(normalize_id(dependent));
+    for spec in specs {
+        resolver.require(dependent, spec);
+    }
+    resolver.plan
+}
+
+struct Resolver<'a> {
+    installed: &'a InstalledSet,
+    catalog: &'a [cache::Mod],
+    plan: ResolutionPlan,
+    visiting: HashSet<String>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(installed: &'a InstalledSet, catalog: &'a [cache::Mod]) -> Self {
+        Self {
+            installed,
+            catalog,
+            plan: ResolutionPlan::default(),
+            visiting: HashSet::new(),
+        }
+    }
+
+    fn visit(&mut self, catalog_mod: &cache::Mod) {
+        let key = normalize_id(&catalog_mod.title);
+        // already planned, or a dependency cycle that's being resolved further up
+        if self.plan.install.iter().any(|t| normalize_id(t) == key)
+            || !self.visiting.insert(key.clone())
+        {
+            return;
+        }
+
+        for spec in catalog_dependencies(catalog_mod) {
+            self.require(&catalog_mod.title, &spec);
+        }
+
+        self.visiting.remove(&key);
+        self.plan.install.push(catalog_mod.title.clone());
+    }
+
+    fn require(&mut self, dependent: &str, raw_spec: &str) {
+        let spec = match raw_spec.parse::<DependencySpec>() {
+            Ok(spec) => spec,
+            Err(e) => {
+                self.unmet(dependent, raw_spec, e.to_string());
+                return;
+            }
+        };
+
+        if spec
+            .alternatives
+            .iter()
+            .any(|req| req.is_unmanaged() || self.installed.satisfies(req) || self.is_planned(req))
+        {
+            return;
+        }
+
+        // fall back to the first alternative the catalog can satisfy
+        let mut reasons = Vec::new();
+        for requirement in &spec.alternatives {
+            match find_in_catalog(self.catalog, &requirement.id) {
+                Some(candidate)
+                    if requirement.matches_version(
+                        parse_optional_version(candidate.version.as_deref()).as_ref(),
+                    ) =>
+                {
+                    self.visit(candidate);
+                    return;
+                }
+                Some(candidate) => reasons.push(format!(
+                    "the catalog only has {} {}",
+                    candidate.title,
+                    candidate.version.as_deref().unwrap_or("(unknown version)")
+                )),
+                None => reasons.push(format!("{} is not in the catalog", requirement.id)),
+            }
+        }
+        self.unmet(dependent, raw_spec, reasons.join("; "));
+    }
+
+    fn is_planned(&self, requirement: &Requirement) -> bool {
+        self.plan.install.iter().any(|title| {
+            requirement.matches_id(title)
+                && find_in_catalog(self.catalog, title).is_some_and(|m| {
+                    requirement
+                        .matches_version(parse_optional_version(m.version.as_deref()).as_ref())
+                })
+        })
+    }
+
+    fn unmet(&mut self, dependent: &str, spec: &str, reason: String) {
+        self.plan.unmet.push(UnmetDependency {
+            dependent: dependent.to_string(),
+            spec: spec.to_string(),
+            reason,
+        });
+    }
+}
+
+fn describe_unmet(unmet: &[UnmetDependency]) -> String {
+    unmet
+        .iter()
+        .map(|u| format!("{} needs `{}` ({})", u.dependent, u.spec, u.reason))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Installs the catalog mod `requested` after every missing dependency it
+/// has, and tracks all of them in the database. Every mod is extracted into a
+/// staging directory first, so dependencies that a mod only declares in its
+/// own metadata are found and staged too before anything is installed. A
+/// dependency that can't be met refuses the whole installation, and so does a
+/// mod that conflicts with an enabled one, unless `force` is set.
+/// Returns the installed titles in order.
+pub async fn install_with_dependencies(
+    db: &Mutex<Database>,
+    catalog: &[cache::Mod],
+    requested: &str,
+    force: bool,
+    job: &DownloadJob,
+) -> Result<Vec<String>, AppError> {
+    let (mut installed, mods_dir) = {
+        let db = db.lock()?;
+        (InstalledSet::from_database(&db)?, db.get_mods_dir()?)
+    };
+
+    let plan = resolve(requested, &installed, catalog)?;
+    if !plan.unmet.is_empty() {
+        return Err(AppError::ModInstall {
+            mod_name: requested.to_string(),
+            source: format!("unmet dependencies: {}", describe_unmet(&plan.unmet)),
+        });
+    }
+
+    let detail = |title: &str| (title != requested).then(|| format!("Dependency of {requested}"));
+    let check = conflicts::staged_check(&mods_dir);
+    // The order the staged mods are installed in, where the dependencies a
+    // mod's metadata adds go right before it
+    let mut order = plan.install.clone();
+    let mut queue = VecDeque::from(plan.install);
+    let mut staged = HashMap::new();
+
+    while let Some(title) = queue.pop_front() {
+        if staged.contains_key(&title) {
+            continue;
+        }
+        let catalog_mod =
+            find_in_catalog(catalog, &title).ok_or_else(|| AppError::ModNotFound {
+                mod_name: title.clone(),
+                version: "any".to_string(),
+            })?;
+        let affected = [AffectedMod::new(
+            &catalog_mod.title,
+            catalog_mod.version.clone(),
+        )];
+
+        log::info!("Staging {title} as part of {requested}");
+        let staged_mod =
+            match stage_catalog_mod(db, &mods_dir, catalog_mod, (!force).then_some(&check), job)
+                .await
+            {
+                Ok(staged_mod) => staged_mod,
+                Err(e) => {
+                    let detail = detail(&title);
+                    return operations::record_locked(
+                        db,
+                        OperationKind::Install,
+                        &affected,
+                        detail.as_deref(),
+                        Err(e),
+                    );
+                }
+            };
+        let detected = local_mod_detection::detect_mod(staged_mod.path());
+        installed.add_catalog(catalog_mod);
+
+        if let Some(detected) = &detected {
+            installed.add_detected(detected);
+            let extra = resolve_specs(&title, &detected.dependencies, &installed, catalog);
+            if !extra.unmet.is_empty() {
+                let detail = detail(&title);
+                return operations::record_locked(
+                    db,
+                    OperationKind::Install,
+                    &affected,
+                    detail.as_deref(),
+                    Err(AppError::ModInstall {
+                        mod_name: title.clone(),
+                        source: format!("unmet dependencies: {}", describe_unmet(&extra.unmet)),
+                    }),
+                );
+            }
+            for extra_title in extra.install.iter().rev() {
+                if !staged.contains_key(extra_title) {
+                    queue.push_front(extra_title.clone());
+                }
+            }
+            order.retain(|t| !extra.install.contains(t));
+            let at = order
+                .iter()
+                .position(|t| *t == title)
+                .unwrap_or(order.len());
+            order.splice(at..at, extra.install);
+        }
+
+        staged.insert(title, (catalog_mod, staged_mod, detected));
+    }
+
+    let mut installed_titles = Vec::new();
+    for title in order {
+        let Some((catalog_mod, staged_mod, detected)) = staged.remove(&title) else {
+            continue;
+        };
+        log::info!("Installing {title} as part of {requested}");
+        let result = commit_catalog_mod(db, catalog_mod, staged_mod, detected.as_ref());
+        operations::record_locked(
+            db,
+            OperationKind::Install,
+            &[AffectedMod::new(
+                &catalog_mod.title,
+                catalog_mod.version.clone(),
+            )],
+            detail(&title).as_deref(),
+            result,
+        )?;
+        installed_titles.push(title);
+    }
+
+    Ok(installed_titles)
+}
+
+/// Extracts a single catalog mod into a staging directory and checks it
+/// against the enabled mods.
+async fn stage_catalog_mod(
+    db: &Mutex<Database>,
+    mods_dir: &Path,
+    catalog_mod: &cache::Mod,
+    check: Option<StagedCheck<'_>>,
+    job: &DownloadJob,
+) -> Result<StagedMod, AppError> {
+    let folder_name = catalog_mod
+        .folder_name
+        .clone()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| catalog_mod.title.replace(' ', ""));
+    let expected_hash = version_history::recorded_archive(
+        &*db.lock()?,
+        &catalog_mod.download_url,
+        catalog_mod.version.as_deref(),
+    )?;
+    let staged = installer::stage_mod(
+        mods_dir,
+        catalog_mod.download_url.clone(),
+        Some(folder_name),
+        expected_hash.as_deref(),
+        job,
+    )
+    .await?;
+    if let Some(check) = check {
+        check(staged.path(), staged.target())?;
+    }
+    Ok(staged)
+}
+
+/// Installs a staged catalog mod and tracks it with the dependencies its
+/// metadata declares.
+fn commit_catalog_mod(
+    db: &Mutex<Database>,
+    catalog_mod: &cache::Mod,
+    staged: StagedMod,
+    detected: Option<&DetectedMod>,
+) -> Result<(), AppError> {
+    let (path, source) = staged.commit()?;
+    db.lock()?.record_install(
+        &catalog_mod.title,
+        &path.to_string_lossy(),
+        &tracked_dependencies(catalog_mod, detected),
+        catalog_mod.version.clone(),
+        &source,
+    )
+}
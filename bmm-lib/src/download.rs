@@ -0,0 +1,299 @@
+use crate::errors::AppError;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tempfile::NamedTempFile;
+use tokio::sync::Notify;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a download may go without receiving any data.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+    pub job_id: String,
+    pub downloaded: u64,
+    /// Unknown when the server doesn't send a `Content-Length`.
+    pub total: Option<u64>,
+    pub bytes_per_second: u64,
+}
+
+type ProgressCallback = Arc<dyn Fn(&DownloadProgress) + Send + Sync>;
+
+/// Reports progress for, and can cancel, the downloads it's passed to.
+/// Clones share the same cancellation state, and a cancelled job stays
+/// cancelled, so every later download with it fails right away too.
+#[derive(Clone, Default)]
+pub struct DownloadJob {
+    id: String,
+    cancelled: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+    on_progress: Option<ProgressCallback>,
+}
+
+impl fmt::Debug for DownloadJob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DownloadJob")
+            .field("id", &self.id)
+            .field("cancelled", &self.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl DownloadJob {
+    #[must_use]
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn on_progress(
+        mut self,
+        on_progress: impl Fn(&DownloadProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn check_cancelled(&self, subject: &str) -> Result<(), AppError> {
+        if self.is_cancelled() {
+            Err(AppError::DownloadCancelled {
+                url: subject.to_string(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn cancelled(&self) {
+        loop {
+            // Created before checking the flag so a cancel in between isn't missed
+            let notified = self.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    fn report(&self, progress: &DownloadProgress) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(progress);
+        }
+    }
+}
+
+/// The downloads that are currently running, so they can be cancelled by id.
+#[derive(Debug, Default)]
+pub struct DownloadJobs {
+    jobs: Mutex<HashMap<String, DownloadJob>>,
+    next_id: AtomicU64,
+}
+
+impl DownloadJobs {
+    /// Registers `job` until the returned guard is dropped. Jobs without an
+    /// id get a generated one.
+    pub fn start(&self, mut job: DownloadJob) -> Result<ActiveDownload<'_>, AppError> {
+        if job.id.is_empty() {
+            job.id = format!("download-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        }
+        self.jobs
+            .lock()
+            .map_err(|_| AppError::LockPoisoned("Download jobs lock poisoned".to_string()))?
+            .insert(job.id.clone(), job.clone());
+        Ok(ActiveDownload { jobs: self, job })
+    }
+
+    /// Cancels the job with `id`, returning whether there was one.
+    pub fn cancel(&self, id: &str) -> Result<bool, AppError> {
+        let jobs = self
+            .jobs
+            .lock()
+            .map_err(|_| AppError::LockPoisoned("Download jobs lock poisoned".to_string()))?;
+        Ok(jobs.get(id).map(DownloadJob::cancel).is_some())
+    }
+}
+
+/// A job registered in [`DownloadJobs`], unregistered on drop.
+#[derive(Debug)]
+pub struct ActiveDownload<'a> {
+    jobs: &'a DownloadJobs,
+    job: DownloadJob,
+}
+
+impl Deref for ActiveDownload<'_> {
+    type Target = DownloadJob;
+
+    fn deref(&self) -> &DownloadJob {
+        &self.job
+    }
+}
+
+impl Drop for ActiveDownload<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut jobs) = self.jobs.jobs.lock() {
+            jobs.remove(&self.job.id);
+        }
+    }
+}
+
+/// A finished download in a temporary file, which is deleted on drop.
+#[derive(Debug)]
+pub struct DownloadedFile {
+    file: NamedTempFile,
+    /// SHA-256 of the contents, see [`checksum::sha256_hex`](crate::checksum::sha256_hex).
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl DownloadedFile {
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    pub fn open(&self) -> Result<File, AppError> {
+        self.file.reopen().map_err(|e| AppError::FileRead {
+            path: self.path().to_path_buf(),
+            source: e.to_string(),
+        })
+    }
+
+    /// Moves the file to `path` instead of deleting it on drop.
+    pub fn persist(self, path: &Path) -> Result<(), AppError> {
+        self.file
+            .persist(path)
+            .map(drop)
+            .map_err(|e| AppError::FileWrite {
+                path: path.to_path_buf(),
+                source: e.to_string(),
+            })
+    }
+}
+
+/// Streams `url` into a temporary file, reporting progress to `job` and
+/// hashing the contents as they arrive. If the job is cancelled, or the
+/// download fails or stalls, the partial file is removed.
+pub async fn download(url: &str, job: &DownloadJob) -> Result<DownloadedFile, AppError> {
+    download_in(url, &std::env::temp_dir(), job).await
+}
+
+/// Like [`download`], but puts the temporary file in `dir`, so it can be
+/// persisted there with a rename.
+pub async fn download_in(
+    url: &str,
+    dir: &Path,
+    job: &DownloadJob,
+) -> Result<DownloadedFile, AppError> {
+    let network_error = |e: reqwest::Error| AppError::NetworkRequest {
+        url: url.to_string(),
+        source: e.to_string(),
+    };
+    job.check_cancelled(url)?;
+
+    let client = reqwest::Client::builder()
+        .user_agent("Balatro-Mod-Manager/1.0")
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT)
+        .build()
+        .map_err(network_error)?;
+    let mut response = tokio::select! {
+        response = client.get(url).send() => response.and_then(reqwest::Response::error_for_status).map_err(network_error)?,
+        () = job.cancelled() => return Err(AppError::DownloadCancelled { url: url.to_string() }),
+    };
+
+    let mut file = NamedTempFile::new_in(dir).map_err(|e| AppError::FileWrite {
+        path: dir.to_path_buf(),
+        source: e.to_string(),
+    })?;
+    let mut hasher = Sha256::new();
+    let mut progress = DownloadProgress {
+        job_id: job.id.clone(),
+        downloaded: 0,
+        total: response.content_length(),
+        bytes_per_second: 0,
+    };
+    let started = Instant::now();
+    let mut last_report = started;
+
+    loop {
+        let chunk = tokio::select! {
+            chunk = response.chunk() => chunk.map_err(network_error)?,
+            () = job.cancelled() => {
+                log::info!("Cancelled download of {url}");
+                return Err(AppError::DownloadCancelled { url: url.to_string() });
+            }
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+
+        hasher.update(&chunk);
+        file.write_all(&chunk).map_err(|e| AppError::FileWrite {
+            path: file.path().to_path_buf(),
+            source: e.to_string(),
+        })?;
+        progress.downloaded += chunk.len() as u64;
+
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            progress.bytes_per_second = rate(progress.downloaded, started.elapsed());
+            job.report(&progress);
+            last_report = Instant::now();
+        }
+    }
+
+    file.flush().map_err(|e| AppError::FileWrite {
+        path: file.path().to_path_buf(),
+        source: e.to_string(),
+    })?;
+    progress.bytes_per_second = rate(progress.downloaded, started.elapsed());
+    progress.total = Some(progress.downloaded);
+    job.report(&progress);
+
+    Ok(DownloadedFile {
+        file,
+        sha256: hex::encode(hasher.finalize()),
+        size: progress.downloaded,
+    })
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+fn rate(bytes: u64, elapsed: Duration) -> u64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 {
+        (bytes as f64 / seconds) as u64
+    } else {
+        bytes
+    }
+}
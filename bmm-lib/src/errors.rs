@@ -55,10 +55,24 @@ pub enum AppError {
         mod_name: String,
         conflicts: Vec<String>,
     },
+    IncompatibleGameVersion {
+        installed: String,
+        mods: Vec<String>,
+    },
     ModNotFound {
         mod_name: String,
         version: String,
     },
+    InvalidDependency {
+        spec: String,
+        reason: String,
+    },
+    InvalidVersion(String),
+    ChecksumMismatch {
+        subject: String,
+        expected: String,
+        actual: String,
+    },
     GitOperation(String),
 
     // Network/API
@@ -66,6 +80,9 @@ pub enum AppError {
         url: String,
         source: String,
     },
+    DownloadCancelled {
+        url: String,
+    },
     ApiLimitExceeded,
     InvalidApiResponse(String),
 
@@ -123,10 +140,44 @@ impl fmt::Display for AppError {
                 write!(f, "Failed to install mod '{mod_name}': {source}")
             }
 
+            AppError::ModConflict {
+                mod_name,
+                conflicts,
+            } => write!(
+                f,
+                "Mod '{mod_name}' conflicts with enabled mods: {}",
+                conflicts.join(", ")
+            ),
+
+            AppError::IncompatibleGameVersion { installed, mods } => write!(
+                f,
+                "Mods that don't support Balatro {installed}: {}",
+                mods.join(", ")
+            ),
+
+            AppError::InvalidDependency { spec, reason } => {
+                write!(f, "Invalid dependency '{spec}': {reason}")
+            }
+
+            AppError::InvalidVersion(version) => write!(f, "Invalid version '{version}'"),
+
+            AppError::ChecksumMismatch {
+                subject,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Checksum mismatch for '{subject}': expected {expected}, got {actual}"
+            ),
+
             AppError::NetworkRequest { url, source } => {
                 write!(f, "Network request to '{url}' failed: {source}")
             }
 
+            AppError::DownloadCancelled { url } => {
+                write!(f, "Download of '{url}' was cancelled")
+            }
+
             AppError::MacOsLibrary { lib_name, source } => {
                 write!(f, "MacOS library '{lib_name}' error: {source}")
             }
@@ -135,6 +186,10 @@ impl fmt::Display for AppError {
                 write!(f, "Invalid path '{}': {}", path.display(), reason)
             }
 
+            AppError::SystemDetection(msg) => write!(f, "{msg}"),
+
+            AppError::Serialization { format, source } => write!(f, "Invalid {format}: {source}"),
+
             // Handle all variants similarly
             _ => write!(f, "{self:?}"),
         }
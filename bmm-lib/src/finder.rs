@@ -1,16 +1,7 @@
-use log::error;
+use crate::errors::AppError;
+use crate::steam;
 use log::info;
-use std::path::PathBuf;
-
-#[cfg(target_os = "windows")]
-fn read_path_from_registry() -> Result<String, std::io::Error> {
-    use winreg::enums::HKEY_LOCAL_MACHINE;
-
-    let hklm = winreg::RegKey::predef(HKEY_LOCAL_MACHINE);
-    let steam_path = hklm.open_subkey("SOFTWARE\\WOW6432Node\\Valve\\Steam")?;
-
-    Ok(steam_path.get_value("InstallPath")?)
-}
+use std::path::{Path, PathBuf};
 
 fn remove_unexisting_paths(paths: &mut Vec<PathBuf>) {
     let mut i = 0;
@@ -24,109 +15,57 @@ fn remove_unexisting_paths(paths: &mut Vec<PathBuf>) {
     info!("Found {} Balatro installations.", paths.len());
 }
 
-#[cfg(target_os = "windows")]
-pub fn get_balatro_paths() -> Vec<PathBuf> {
-    use std::fs::File;
-    use std::io::{BufReader, Read};
-    use std::path::Path;
-
-    let steam_path = read_path_from_registry();
-    let mut steam_path = steam_path.unwrap_or_else(|_| {
-        error!("Could not read steam install path from Registry! Trying standard installation path in C:\\");
-        String::from("C:\\Program Files (x86)\\Steam")
-    });
-
-    steam_path.push_str("\\steamapps\\libraryfolders.vdf");
-    let libraryfolders_path = Path::new(&steam_path);
-    if !libraryfolders_path.exists() {
-        error!("'{}' not found.", libraryfolders_path.to_str().unwrap());
-        return vec![];
-    }
-
-    let libraryfolders_file =
-        File::open(libraryfolders_path).expect("Failed to open libraryfolders.vdf");
-    let mut libraryfolders_contents = String::new();
-    let mut libraryfolders_reader = BufReader::new(libraryfolders_file);
-    libraryfolders_reader
-        .read_to_string(&mut libraryfolders_contents)
-        .expect("Failed to read libraryfolders.vdf");
-
-    let mut paths: Vec<PathBuf> = vec![];
-    let libraryfolders_contents = libraryfolders_contents.split('\n').collect::<Vec<&str>>();
-    let mut libraryfolders_contents = libraryfolders_contents.iter();
-    while let Some(line) = libraryfolders_contents.next() {
-        if line.contains("\t\t\"path\"\t\t") {
-            let path = line.split('\"').collect::<Vec<&str>>()[3];
-            paths.push(PathBuf::from(path).join("steamapps\\common\\Balatro"));
-        }
-    }
-    remove_unexisting_paths(&mut paths);
-    paths
-}
-
-#[cfg(target_os = "macos")]
+/// The game directories of every copy of Balatro installed through Steam,
+/// in any of its library folders.
+#[must_use]
 pub fn get_balatro_paths() -> Vec<PathBuf> {
-    let mut paths: Vec<PathBuf> = vec![];
-    match home::home_dir() {
-        Some(path) => {
-            let mut path = path;
-            path.push("Library/Application Support/Steam/steamapps/common/Balatro");
-            paths.push(path);
-        }
-        None => error!("Impossible to get your home dir!"),
-    }
+    let mut paths: Vec<PathBuf> = steam::find_app(steam::BALATRO_APP_ID)
+        .into_iter()
+        .map(|app| app.game_dir)
+        .collect();
     remove_unexisting_paths(&mut paths);
     paths
 }
 
+/// The directory lovely loads mods from by default: `Balatro/Mods` in the
+/// roaming app data directory, which on Linux is inside the game's Proton
+/// prefix. Installations with a Mods directory of their own use that instead,
+/// see [`Installation::resolved_mods_dir`](crate::installations::Installation::resolved_mods_dir).
 pub fn get_lovely_mods_dir(
     #[cfg(target_os = "linux")] installation_path: Option<&String>,
     #[cfg(not(target_os = "linux"))] _installation_path: Option<&String>,
-) -> PathBuf {
+) -> Result<PathBuf, AppError> {
     #[cfg(target_os = "linux")]
     {
-        // probably ~/.steam/steam/steamapps/
-        let prefix = {
-            let installation_path = installation_path.map_or(PathBuf::new(), PathBuf::from);
-            log::debug!("installation_path: {installation_path:?}");
-            if installation_path.ends_with("steamapps/common/Balatro/") {
-                installation_path
-                    .parent()
-                    .unwrap()
-                    .parent()
-                    .unwrap()
-                    .to_path_buf()
-            } else {
-                dirs::home_dir().unwrap().join(".steam/steam/steamapps/")
-            }
-        };
-        log::info!("Assuming steam wineprefix: `{}`", prefix.to_string_lossy());
+        log::debug!("installation_path: {installation_path:?}");
+        let prefix = steam::proton_prefix(steam::BALATRO_APP_ID, installation_path.map(Path::new))?;
+        log::info!("Using the Proton prefix `{}`", prefix.display());
 
-        prefix.join("compatdata/2379780/pfx/drive_c/users/steamuser/AppData/Roaming/Balatro/Mods")
+        Ok(prefix.join("drive_c/users/steamuser/AppData/Roaming/Balatro/Mods"))
     }
     #[cfg(not(target_os = "linux"))]
     {
-        let mut path = dirs::config_dir().unwrap();
-        path.push("Balatro");
-        path.push("Mods");
-        path
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| AppError::DirNotFound(PathBuf::from("config directory")))?;
+        Ok(config_dir.join("Balatro").join("Mods"))
     }
 }
 
-#[cfg(target_os = "linux")]
 #[must_use]
-pub fn get_balatro_paths() -> Vec<PathBuf> {
-    let mut paths: Vec<PathBuf> = vec![];
-    match home::home_dir() {
-        Some(path) => {
-            let mut path = path;
-            path.push(".local/share/Steam/steamapps/common/Balatro");
-            paths.push(path);
-        }
-        None => error!("Impossible to get your home dir!"),
+pub fn is_valid_game_dir(game_dir: &Path) -> bool {
+    #[cfg(target_os = "linux")]
+    return game_dir.join("Balatro.exe").exists();
+
+    #[cfg(target_os = "macos")]
+    return game_dir
+        .join("Balatro.app/Contents/Resources/Balatro.love")
+        .exists();
+
+    #[cfg(target_os = "windows")]
+    {
+        let dll_files = ["love.dll", "lua51.dll", "SDL2.dll"];
+        dll_files.iter().any(|dll| game_dir.join(dll).exists())
     }
-    remove_unexisting_paths(&mut paths);
-    paths
 }
 
 #[must_use]
@@ -177,7 +116,9 @@ pub fn get_installed_mods(installation_path: Option<&String>) -> Vec<String> {
     //     .unwrap_or_else(|| panic!("Failed to find Balatro installation path. Is it installed?"))
     //     .to_path_buf();
 
-    let mod_dir = get_lovely_mods_dir(installation_path);
+    let Ok(mod_dir) = get_lovely_mods_dir(installation_path) else {
+        return Vec::new();
+    };
 
     // dbg!(&mod_dir);
 
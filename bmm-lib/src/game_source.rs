@@ -0,0 +1,304 @@
+use crate::checksum;
+use crate::errors::AppError;
+use crate::paths::{self, Paths};
+use crate::version::Version;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::ZipArchive;
+
+/// Written into each extracted copy once everything else is in place, so a
+/// directory without one is an interrupted extraction.
+const INDEX_FILE: &str = "source.json";
+
+/// The game's Lua sources, extracted from one release of the game into
+/// `<cache>/game_source/<version>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSource {
+    /// The version in the sources, such as `1.0.1o-FULL`. When it couldn't
+    /// be found, or doesn't make a directory name, `dir` is named after the
+    /// archive's hash instead.
+    pub version: Option<String>,
+    pub dir: PathBuf,
+    /// `Balatro.exe` or `Balatro.love` the sources came from.
+    pub archive: PathBuf,
+    pub archive_sha256: String,
+    /// Where the zip starts in `archive`, past the LÖVE executable in front
+    /// of it. Zero for a plain `.love` file.
+    pub archive_offset: u64,
+    /// Every Lua file, by the name lovely patches target, sorted.
+    pub files: Vec<String>,
+    pub extracted_at: u64,
+}
+
+/// Where the game's sources can be, relative to the game directory, in the
+/// order they're tried.
+#[cfg(target_os = "macos")]
+const ARCHIVES: [&str; 2] = [
+    "Balatro.app/Contents/Resources/Balatro.love",
+    "Balatro.love",
+];
+#[cfg(not(target_os = "macos"))]
+const ARCHIVES: [&str; 2] = ["Balatro.exe", "Balatro.love"];
+
+/// The file holding the game's sources in `game_dir`: `Balatro.love` inside
+/// the app bundle on macOS, otherwise `Balatro.exe`, a LÖVE executable with
+/// the game zipped onto its end. A bare `Balatro.love` next to the game is
+/// used as a last resort.
+pub fn game_archive(game_dir: &Path) -> Result<PathBuf, AppError> {
+    ARCHIVES
+        .iter()
+        .map(|name| game_dir.join(name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| AppError::FileNotFound {
+            path: game_dir.join(ARCHIVES[0]),
+            source: "The game's Balatro.exe or Balatro.love wasn't found".to_string(),
+        })
+}
+
+fn open_archive(archive: &Path) -> Result<ZipArchive<fs::File>, AppError> {
+    let file = fs::File::open(archive).map_err(|e| AppError::FileRead {
+        path: archive.to_path_buf(),
+        source: e.to_string(),
+    })?;
+    // The zip reader finds the archive from the central directory at the end
+    // of the file, so whatever comes before it, like the LÖVE executable, is
+    // skipped
+    ZipArchive::new(file).map_err(|e| AppError::FileRead {
+        path: archive.to_path_buf(),
+        source: format!("No game archive found: {e}"),
+    })
+}
+
+/// Reads the game version out of the sources in `archive` without
+/// extracting anything.
+pub fn read_version(archive: &Path) -> Result<Option<String>, AppError> {
+    version_in(&mut open_archive(archive)?, archive)
+}
+
+fn version_in(zip: &mut ZipArchive<fs::File>, archive: &Path) -> Result<Option<String>, AppError> {
+    let Ok(mut globals) = zip.by_name("globals.lua") else {
+        return Ok(None);
+    };
+    let mut text = String::new();
+    globals
+        .read_to_string(&mut text)
+        .map_err(|e| AppError::FileRead {
+            path: archive.join("globals.lua"),
+            source: e.to_string(),
+        })?;
+    Ok(parse_version(&text))
+}
+
+/// Follows the top-level `VERSION = '...'` assignment in `globals.lua` and
+/// the `VERSION = VERSION..'...'` lines that add to it.
+#[must_use]
+pub fn parse_version(globals: &str) -> Option<String> {
+    let mut version: Option<String> = None;
+    for line in globals.lines() {
+        let Some(value) = line
+            .strip_prefix("VERSION")
+            .and_then(|rest| rest.trim_start().strip_prefix('='))
+            .map(str::trim)
+        else {
+            continue;
+        };
+        match value.strip_prefix("VERSION") {
+            Some(suffix) => {
+                if let (Some(current), Some(suffix)) = (
+                    version.as_mut(),
+                    suffix.trim_start().strip_prefix("..").and_then(lua_string),
+                ) {
+                    current.push_str(suffix);
+                }
+            }
+            None => version = lua_string(value).map(str::to_string),
+        }
+    }
+    version.filter(|version| !version.is_empty())
+}
+
+/// The contents of a single or double quoted Lua string literal at the start
+/// of `value`.
+fn lua_string(value: &str) -> Option<&str> {
+    let value = value.trim_start();
+    let quote = value.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    let rest = &value[1..];
+    rest.find(quote).map(|end| &rest[..end])
+}
+
+/// The directory extracted sources are kept in, one subdirectory per
+/// version.
+pub fn cache_dir() -> Result<PathBuf, AppError> {
+    Ok(Paths::get()?.game_source_dir())
+}
+
+/// Extracts the Lua sources of the game in `game_dir`, unless the same
+/// archive was already extracted. Another archive with the same version
+/// replaces the earlier copy.
+pub fn extract(game_dir: &Path) -> Result<GameSource, AppError> {
+    let archive = game_archive(game_dir)?;
+    let sha256 = fs::read(&archive)
+        .map(|contents| checksum::sha256_hex(&contents))
+        .map_err(|e| AppError::FileRead {
+            path: archive.clone(),
+            source: e.to_string(),
+        })?;
+    let mut zip = open_archive(&archive)?;
+    let version = version_in(&mut zip, &archive)?;
+
+    let root = paths::ensure_dir(cache_dir()?)?;
+    let dir = root.join(dir_name(version.as_deref(), &sha256));
+    if let Some(cached) = read_index(&dir)
+        && checksum::matches(&cached.archive_sha256, &sha256)
+    {
+        return Ok(cached);
+    }
+
+    let staging = tempfile::Builder::new()
+        .prefix(".extracting-")
+        .tempdir_in(&root)
+        .map_err(|e| AppError::DirCreate {
+            path: root.clone(),
+            source: e.to_string(),
+        })?;
+    let files = extract_lua(&mut zip, &archive, staging.path())?;
+    let source = GameSource {
+        version,
+        dir: dir.clone(),
+        archive: archive.clone(),
+        archive_sha256: sha256,
+        archive_offset: zip.offset(),
+        files,
+        extracted_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    };
+    write_index(staging.path(), &source)?;
+
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| AppError::FileWrite {
+            path: dir.clone(),
+            source: format!("Failed to remove the previous copy: {e}"),
+        })?;
+    }
+    fs::rename(staging.path(), &dir).map_err(|e| AppError::FileWrite {
+        path: dir.clone(),
+        source: e.to_string(),
+    })?;
+    log::info!(
+        "Extracted {} Lua files from {} to {}",
+        source.files.len(),
+        archive.display(),
+        dir.display()
+    );
+    Ok(source)
+}
+
+/// The version with anything but letters, digits, `.`, `-` and `_` replaced,
+/// or the archive's hash when there's no version or it would leave nothing
+/// but dots, which name the cache or its parent rather than a directory in it.
+fn dir_name(version: Option<&str>, sha256: &str) -> String {
+    let name = version.map(|version| {
+        version
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>()
+    });
+    match name {
+        Some(name) if !name.trim_start_matches('.').is_empty() => name,
+        _ => format!("unknown-{}", &sha256[..12.min(sha256.len())]),
+    }
+}
+
+/// Unpacks every `.lua` file in `zip` into `dest` and returns their names.
+/// Textures, sounds and fonts are left in the archive.
+fn extract_lua(
+    zip: &mut ZipArchive<fs::File>,
+    archive: &Path,
+    dest: &Path,
+) -> Result<Vec<String>, AppError> {
+    let mut files = Vec::new();
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(|e| AppError::FileRead {
+            path: archive.to_path_buf(),
+            source: format!("Zip entry error: {e}"),
+        })?;
+        let Some(name) = entry.enclosed_name() else {
+            continue;
+        };
+        if !entry.is_file() || name.extension().is_none_or(|ext| ext != "lua") {
+            continue;
+        }
+
+        let path = dest.join(&name);
+        if let Some(parent) = path.parent() {
+            paths::ensure_dir(parent.to_path_buf())?;
+        }
+        let mut out = fs::File::create(&path).map_err(|e| AppError::FileWrite {
+            path: path.clone(),
+            source: e.to_string(),
+        })?;
+        std::io::copy(&mut entry, &mut out).map_err(|e| AppError::FileWrite {
+            path: path.clone(),
+            source: e.to_string(),
+        })?;
+        files.push(
+            name.components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+        );
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn read_index(dir: &Path) -> Option<GameSource> {
+    let text = fs::read_to_string(dir.join(INDEX_FILE)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn write_index(dir: &Path, source: &GameSource) -> Result<(), AppError> {
+    let path = dir.join(INDEX_FILE);
+    fs::write(&path, serde_json::to_string_pretty(source)?).map_err(|e| AppError::FileWrite {
+        path,
+        source: e.to_string(),
+    })
+}
+
+/// Every extracted copy of the sources, newest version first, then the ones
+/// without a version that parses.
+pub fn list() -> Result<Vec<GameSource>, AppError> {
+    let root = cache_dir()?;
+    let Ok(entries) = fs::read_dir(&root) else {
+        return Ok(Vec::new());
+    };
+    let mut sources = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| read_index(&entry.path()))
+        .collect::<Vec<_>>();
+    sources.sort_by_cached_key(|source| {
+        Reverse(
+            source
+                .version
+                .as_deref()
+                .and_then(|version| version.parse::<Version>().ok()),
+        )
+    });
+    Ok(sources)
+}
+
+/// The extracted sources of `version`, if they're in the cache.
+pub fn get(version: &str) -> Result<Option<GameSource>, AppError> {
+    Ok(list()?
+        .into_iter()
+        .find(|source| source.version.as_deref() == Some(version)))
+}
@@ -0,0 +1,304 @@
+use crate::database::Database;
+use crate::dependencies::{DependencySpec, Requirement};
+use crate::errors::AppError;
+use crate::game_source;
+use crate::installer;
+use crate::local_mod_detection::{self, DetectedMod};
+use crate::steam::{self, AppManifest, BALATRO_APP_ID};
+use crate::version::Version;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Which build of the game an installation has, as last detected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GameVersion {
+    /// The version in the game's sources, such as `1.0.1o-FULL`.
+    pub version: Option<String>,
+    /// Steam's build id, for copies installed through Steam.
+    pub build_id: Option<String>,
+    /// The Steam beta branch, `None` for the default branch.
+    pub branch: Option<String>,
+    pub detected_at: u64,
+}
+
+impl GameVersion {
+    /// Whether `other` is the same build, whenever each was detected.
+    #[must_use]
+    pub fn same_build(&self, other: &Self) -> bool {
+        self.version == other.version
+            && self.build_id == other.build_id
+            && self.branch == other.branch
+    }
+}
+
+impl fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.version.as_deref().unwrap_or("unknown version")
+        )?;
+        match (&self.build_id, &self.branch) {
+            (Some(build_id), Some(branch)) => write!(f, " (build {build_id}, branch {branch})"),
+            (Some(build_id), None) => write!(f, " (build {build_id})"),
+            (None, Some(branch)) => write!(f, " (branch {branch})"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// The version for comparing against mod requirements, without the `-FULL`
+/// or `-DEMO` edition.
+fn parse_game_version(version: &str) -> Option<Version> {
+    version.split('-').next()?.parse().ok()
+}
+
+/// Reads the version of the game in `game_dir` from its sources, and the
+/// build id and branch from `appmanifest_2379780.acf` if Steam installed
+/// it. Whatever can't be read is left out.
+#[must_use]
+pub fn detect(game_dir: &Path) -> GameVersion {
+    let version = game_source::game_archive(game_dir)
+        .and_then(|archive| game_source::read_version(&archive))
+        .unwrap_or_else(|e| {
+            log::warn!("Couldn't read the game version: {e}");
+            None
+        });
+    let manifest = steam::steamapps_of(game_dir)
+        .map(|steamapps| steamapps.join(format!("appmanifest_{BALATRO_APP_ID}.acf")))
+        .filter(|path| path.is_file())
+        .and_then(|path| {
+            AppManifest::read(&path)
+                .inspect_err(|e| log::warn!("Couldn't read {}: {e}", path.display()))
+                .ok()
+        });
+
+    GameVersion {
+        version,
+        build_id: manifest.as_ref().and_then(|m| m.build_id.clone()),
+        branch: manifest.and_then(|m| m.branch),
+        detected_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    }
+}
+
+/// The game version of the active installation compared to the one
+/// recorded the last time it was launched.
+#[derive(Debug, Clone, Serialize)]
+pub struct VersionCheck {
+    pub current: GameVersion,
+    pub previous: Option<GameVersion>,
+    /// Whether the game changed since then. Nothing has changed the first
+    /// time an installation is checked.
+    pub changed: bool,
+}
+
+/// Detects the game version of the active installation at `game_dir`
+/// without recording it, which launching the game does.
+pub fn compare(db: &Database, game_dir: &Path) -> Result<VersionCheck, AppError> {
+    let current = detect(game_dir);
+    let previous = db.get_game_version()?;
+    Ok(VersionCheck {
+        changed: previous
+            .as_ref()
+            .is_some_and(|previous| !previous.same_build(&current)),
+        current,
+        previous,
+    })
+}
+
+impl VersionCheck {
+    /// What to tell the user when the game changed since the last launch.
+    #[must_use]
+    pub fn warning(&self) -> Option<String> {
+        let previous = self.previous.as_ref().filter(|_| self.changed)?;
+        Some(format!(
+            "Balatro changed from {previous} to {} since the last launch, mods may need updating",
+            self.current
+        ))
+    }
+}
+
+/// A mod that requires a different version of the game than the one
+/// installed.
+#[derive(Debug, Clone, Serialize)]
+pub struct Incompatibility {
+    pub mod_name: String,
+    pub mod_path: String,
+    /// The dependency the game doesn't satisfy, e.g. `Balatro (>=1.0.2)`.
+    pub requirement: String,
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} needs {}", self.mod_name, self.requirement)
+    }
+}
+
+/// The dependency of `detected` that `game` doesn't satisfy, if any. Only
+/// entries that name nothing but `Balatro` count, another alternative might
+/// be what the mod actually uses.
+#[must_use]
+pub fn unmet_requirement(detected: &DetectedMod, game: &Version) -> Option<Incompatibility> {
+    detected
+        .dependencies
+        .iter()
+        .find(|spec| {
+            spec.parse::<DependencySpec>().is_ok_and(|parsed| {
+                !parsed.alternatives.is_empty()
+                    && parsed.alternatives.iter().all(is_game)
+                    && !parsed.alternatives.iter().any(|req| satisfies(req, game))
+            })
+        })
+        .map(|spec| Incompatibility {
+            mod_name: detected.name.clone(),
+            mod_path: detected.path.clone(),
+            requirement: spec.clone(),
+        })
+}
+
+fn is_game(requirement: &Requirement) -> bool {
+    requirement.matches_id("Balatro")
+}
+
+fn satisfies(requirement: &Requirement, game: &Version) -> bool {
+    requirement.constraints.iter().all(|c| {
+        c.comparison
+            .matches(compare_game_versions(game, &c.version))
+    })
+}
+
+/// Balatro's letter after the patch number marks a later build of the same
+/// release rather than a pre-release, so `1.0.1o` comes after `1.0.1`.
+fn compare_game_versions(a: &Version, b: &Version) -> Ordering {
+    (a.major, a.minor, a.patch)
+        .cmp(&(b.major, b.minor, b.patch))
+        .then_with(|| match (&a.rev, &b.rev) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a), Some(b)) => a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase()),
+        })
+}
+
+/// Fails with `AppError::IncompatibleGameVersion` if the mod at `mod_dir`
+/// requires a version of the game other than the one installed. Passes when
+/// either version is unknown.
+pub fn check_mod(installation_path: Option<&String>, mod_dir: &Path) -> Result<(), AppError> {
+    let Some((installed, game)) = installed_version(installation_path) else {
+        return Ok(());
+    };
+    let Some(detected) = local_mod_detection::detect_mod(mod_dir) else {
+        return Ok(());
+    };
+    match unmet_requirement(&detected, &game) {
+        Some(incompatibility) => Err(AppError::IncompatibleGameVersion {
+            installed,
+            mods: vec![incompatibility.to_string()],
+        }),
+        None => Ok(()),
+    }
+}
+
+/// The enabled mods in `mods_dir` that require a version of the game other
+/// than the one installed at `installation_path`.
+pub fn incompatible_mods(
+    installation_path: Option<&String>,
+    mods_dir: &Path,
+) -> Result<Vec<Incompatibility>, AppError> {
+    if installed_version(installation_path).is_none() {
+        return Ok(Vec::new());
+    }
+    let entries = match fs::read_dir(mods_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(AppError::FileRead {
+                path: mods_dir.to_path_buf(),
+                source: e.to_string(),
+            });
+        }
+    };
+
+    let mut paths = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && installer::is_mod_enabled(path))
+        .collect::<Vec<_>>();
+    paths.sort();
+    Ok(incompatible_among(installation_path, &paths))
+}
+
+/// The mods at `paths` that require a version of the game other than the
+/// one installed at `installation_path`.
+fn incompatible_among(
+    installation_path: Option<&String>,
+    paths: &[PathBuf],
+) -> Vec<Incompatibility> {
+    let Some((_, game)) = installed_version(installation_path) else {
+        return Vec::new();
+    };
+    paths
+        .iter()
+        .filter_map(|path| local_mod_detection::detect_mod(path))
+        .filter_map(|detected| unmet_requirement(&detected, &game))
+        .collect()
+}
+
+/// Fails with `AppError::IncompatibleGameVersion` listing every enabled mod
+/// that [`incompatible_mods`] finds.
+pub fn check_enabled_mods(
+    installation_path: Option<&String>,
+    mods_dir: &Path,
+) -> Result<(), AppError> {
+    refuse_incompatible(
+        installation_path,
+        &incompatible_mods(installation_path, mods_dir)?,
+    )
+}
+
+/// Fails with `AppError::IncompatibleGameVersion` listing the mods named in
+/// `names`, directories in `mods_dir`, that require a version of the game
+/// other than the one installed, for enabling them all at once.
+pub fn check_set(
+    installation_path: Option<&String>,
+    mods_dir: &Path,
+    names: &[String],
+) -> Result<(), AppError> {
+    let paths = names
+        .iter()
+        .map(|name| mods_dir.join(name))
+        .collect::<Vec<_>>();
+    refuse_incompatible(
+        installation_path,
+        &incompatible_among(installation_path, &paths),
+    )
+}
+
+fn refuse_incompatible(
+    installation_path: Option<&String>,
+    incompatible: &[Incompatibility],
+) -> Result<(), AppError> {
+    if incompatible.is_empty() {
+        return Ok(());
+    }
+    Err(AppError::IncompatibleGameVersion {
+        installed: installed_version(installation_path)
+            .map(|(version, _)| version)
+            .unwrap_or_default(),
+        mods: incompatible.iter().map(ToString::to_string).collect(),
+    })
+}
+
+fn installed_version(installation_path: Option<&String>) -> Option<(String, Version)> {
+    let game_dir = Path::new(installation_path?);
+    let archive = game_source::game_archive(game_dir).ok()?;
+    let version = game_source::read_version(&archive).ok()??;
+    let parsed = parse_game_version(&version)?;
+    Some((version, parsed))
+}
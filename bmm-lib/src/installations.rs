@@ -0,0 +1,376 @@
+use crate::errors::AppError;
+use crate::finder::{self, get_lovely_mods_dir};
+use crate::lovely;
+use crate::version_history;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// A copy of the game bromomethane manages, with its own Mods directory and
+/// its own set of installed mods. Databases from before installations
+/// existed have their one game directory in an installation named
+/// `Default`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Installation {
+    pub id: i64,
+    pub name: String,
+    /// `None` until the game has been located, which only the `Default`
+    /// installation can be.
+    pub game_dir: Option<String>,
+    /// The Mods directory lovely is pointed at instead of the usual one.
+    pub mods_dir: Option<String>,
+    pub active: bool,
+    /// Whether lovely is installed for this copy of the game.
+    pub lovely_installed: bool,
+}
+
+impl Installation {
+    /// The Mods directory the installed mods of this installation live in.
+    pub fn resolved_mods_dir(&self) -> Result<PathBuf, AppError> {
+        match &self.mods_dir {
+            Some(mods_dir) => Ok(PathBuf::from(mods_dir)),
+            None => get_lovely_mods_dir(self.game_dir.as_ref()),
+        }
+    }
+}
+
+/// The installations stored in the main database, borrowed from
+/// [`Database::installations`](crate::database::Database::installations).
+#[derive(Debug, Clone)]
+pub struct InstallationManager<'db> {
+    conn: &'db Connection,
+}
+
+impl<'db> InstallationManager<'db> {
+    pub(crate) fn new(conn: &'db Connection) -> Self {
+        Self { conn }
+    }
+
+    pub fn list(&self) -> Result<Vec<Installation>, AppError> {
+        let active = self.active_id()?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, game_dir, mods_dir FROM installations ORDER BY id")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows
+            .into_iter()
+            .map(|(id, name, game_dir, mods_dir)| {
+                installation(id, name, game_dir, mods_dir, active)
+            })
+            .collect())
+    }
+
+    pub fn get(&self, name: &str) -> Result<Installation, AppError> {
+        self.list()?
+            .into_iter()
+            .find(|i| i.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| not_found(name))
+    }
+
+    /// The installation commands act on.
+    pub fn active(&self) -> Result<Installation, AppError> {
+        let active = self.active_id()?;
+        self.list()?
+            .into_iter()
+            .find(|i| i.id == active)
+            .ok_or_else(|| AppError::InvalidState("The active installation is missing".to_string()))
+    }
+
+    pub(crate) fn active_id(&self) -> Result<i64, AppError> {
+        let id = self
+            .conn
+            .query_row(
+                "SELECT value FROM settings WHERE setting = 'active_installation'",
+                [],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(1);
+        Ok(id)
+    }
+
+    /// Adds the copy of the game at `game_dir`, optionally with a Mods
+    /// directory of its own. The new installation doesn't become active.
+    pub fn add(
+        &self,
+        name: &str,
+        game_dir: &Path,
+        mods_dir: Option<&Path>,
+    ) -> Result<Installation, AppError> {
+        let name = validate_name(name)?;
+        let game_dir = validate_game_dir(game_dir)?;
+        if self.exists(name)? {
+            return Err(AppError::InvalidState(format!(
+                "An installation named '{name}' already exists"
+            )));
+        }
+        if let Some(other) = self.by_game_dir(&game_dir)? {
+            return Err(AppError::InvalidState(format!(
+                "{game_dir} is already the installation '{}'",
+                other.name
+            )));
+        }
+        let mods_dir = mods_dir.map(|dir| dir.to_string_lossy().into_owned());
+        self.check_mods_dir_unused(None, Some(&game_dir), mods_dir.as_deref())?;
+
+        self.conn.execute(
+            "INSERT INTO installations (name, game_dir, mods_dir) VALUES (?1, ?2, ?3)",
+            rusqlite::params![name, game_dir, mods_dir],
+        )?;
+        self.get(name)
+    }
+
+    /// Points installation `id` at a different game directory, e.g. after
+    /// the game was moved. The directory is validated with the rest of the
+    /// [`Settings`](crate::settings::Settings).
+    pub(crate) fn set_game_dir(&self, id: i64, game_dir: Option<&str>) -> Result<(), AppError> {
+        let current = self
+            .list()?
+            .into_iter()
+            .find(|i| i.id == id)
+            .ok_or_else(|| not_found(&id.to_string()))?;
+        let game_dir = game_dir.map(str::to_string);
+        if let Some(game_dir) = &game_dir
+            && let Some(other) = self.by_game_dir(game_dir)?
+            && other.id != current.id
+        {
+            return Err(AppError::InvalidState(format!(
+                "{game_dir} is already the installation '{}'",
+                other.name
+            )));
+        }
+        if game_dir.is_none() && current.mods_dir.is_some() {
+            return Err(AppError::InvalidState(format!(
+                "The installation '{}' has its own Mods directory, so it needs a game directory",
+                current.name
+            )));
+        }
+        self.check_mods_dir_unused(
+            Some(current.id),
+            game_dir.as_deref(),
+            current.mods_dir.as_deref(),
+        )?;
+
+        self.conn.execute(
+            "UPDATE installations SET game_dir = ?2 WHERE id = ?1",
+            rusqlite::params![current.id, game_dir],
+        )?;
+        Ok(())
+    }
+
+    /// Gives installation `name` a Mods directory of its own, or puts it
+    /// back on the usual one with `None`. Mods already installed aren't
+    /// moved.
+    pub fn set_mods_dir(&self, name: &str, mods_dir: Option<&Path>) -> Result<(), AppError> {
+        let current = self.get(name)?;
+        let mods_dir = mods_dir.map(|dir| dir.to_string_lossy().into_owned());
+        if mods_dir.is_some() && current.game_dir.is_none() {
+            return Err(AppError::InvalidState(format!(
+                "Set the game directory of '{}' before giving it its own Mods directory",
+                current.name
+            )));
+        }
+        self.check_mods_dir_unused(
+            Some(current.id),
+            current.game_dir.as_deref(),
+            mods_dir.as_deref(),
+        )?;
+
+        self.conn.execute(
+            "UPDATE installations SET mods_dir = ?2 WHERE id = ?1",
+            rusqlite::params![current.id, mods_dir],
+        )?;
+        Ok(())
+    }
+
+    pub fn rename(&self, name: &str, new_name: &str) -> Result<(), AppError> {
+        let current = self.get(name)?;
+        let new_name = validate_name(new_name)?;
+        if !current.name.eq_ignore_ascii_case(new_name) && self.exists(new_name)? {
+            return Err(AppError::InvalidState(format!(
+                "An installation named '{new_name}' already exists"
+            )));
+        }
+        self.conn.execute(
+            "UPDATE installations SET name = ?2 WHERE id = ?1",
+            rusqlite::params![current.id, new_name],
+        )?;
+        Ok(())
+    }
+
+    /// Forgets installation `name` along with the mods tracked in it, its
+    /// collections, pins and version history. The game and its Mods
+    /// directory are left alone. The active installation can't be removed.
+    pub fn remove(&self, name: &str) -> Result<(), AppError> {
+        let current = self.get(name)?;
+        if current.active {
+            return Err(AppError::InvalidState(format!(
+                "'{}' is the active installation, switch to another one first",
+                current.name
+            )));
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM mod_files WHERE mod_path IN
+                (SELECT path FROM installed_mods WHERE installation_id = ?1)",
+            [current.id],
+        )?;
+        tx.execute(
+            "DELETE FROM mod_sources WHERE path IN
+                (SELECT path FROM installed_mods WHERE installation_id = ?1)",
+            [current.id],
+        )?;
+        tx.execute(
+            "DELETE FROM installed_mods WHERE installation_id = ?1",
+            [current.id],
+        )?;
+        tx.execute(
+            "DELETE FROM game_versions WHERE installation_id = ?1",
+            [current.id],
+        )?;
+        tx.execute(
+            "DELETE FROM mod_collections WHERE installation_id = ?1",
+            [current.id],
+        )?;
+        tx.execute(
+            "DELETE FROM mod_pins WHERE installation_id = ?1",
+            [current.id],
+        )?;
+        let archives = tx
+            .prepare("SELECT DISTINCT archive_hash FROM mod_versions WHERE installation_id = ?1")?
+            .query_map([current.id], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        tx.execute(
+            "DELETE FROM mod_versions WHERE installation_id = ?1",
+            [current.id],
+        )?;
+        // Other installations' histories may share an archive
+        let mut unreferenced = Vec::new();
+        for sha256 in archives {
+            let references = tx.query_row(
+                "SELECT COUNT(*) FROM mod_versions WHERE archive_hash = ?1",
+                [&sha256],
+                |row| row.get::<_, i64>(0),
+            )?;
+            if references == 0 {
+                unreferenced.push(sha256);
+            }
+        }
+        tx.execute("DELETE FROM installations WHERE id = ?1", [current.id])?;
+        tx.commit()?;
+
+        version_history::forget_archives(&unreferenced);
+        Ok(())
+    }
+
+    /// Makes installation `name` the one commands act on.
+    pub fn switch(&self, name: &str) -> Result<Installation, AppError> {
+        let installation = self.get(name)?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO settings (setting, value) VALUES ('active_installation', ?1)",
+            [installation.id.to_string()],
+        )?;
+        Ok(Installation {
+            active: true,
+            ..installation
+        })
+    }
+
+    pub(crate) fn by_game_dir(&self, game_dir: &str) -> Result<Option<Installation>, AppError> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .find(|i| i.game_dir.as_deref().map(Path::new) == Some(Path::new(game_dir))))
+    }
+
+    /// Refuses a Mods directory that another installation already uses, as
+    /// their installed mods would get mixed up.
+    fn check_mods_dir_unused(
+        &self,
+        id: Option<i64>,
+        game_dir: Option<&str>,
+        mods_dir: Option<&str>,
+    ) -> Result<(), AppError> {
+        let resolved = match mods_dir {
+            Some(mods_dir) => PathBuf::from(mods_dir),
+            None => match get_lovely_mods_dir(game_dir.map(str::to_string).as_ref()) {
+                Ok(mods_dir) => mods_dir,
+                // Nothing to compare against until the game has been run
+                Err(_) => return Ok(()),
+            },
+        };
+        for other in self.list()?.into_iter().filter(|i| Some(i.id) != id) {
+            if other.resolved_mods_dir().is_ok_and(|dir| dir == resolved) {
+                return Err(AppError::InvalidState(format!(
+                    "The installation '{}' already uses the Mods directory {}; give this one a \
+                     Mods directory of its own",
+                    other.name,
+                    resolved.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, AppError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT 1 FROM installations WHERE name = ?1",
+                [name],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+}
+
+fn installation(
+    id: i64,
+    name: String,
+    game_dir: Option<String>,
+    mods_dir: Option<String>,
+    active: i64,
+) -> Installation {
+    let lovely_installed = game_dir
+        .as_deref()
+        .is_some_and(|dir| lovely::lovely_path(Path::new(dir)).is_ok_and(|path| path.is_file()));
+    Installation {
+        id,
+        name,
+        game_dir,
+        mods_dir,
+        active: id == active,
+        lovely_installed,
+    }
+}
+
+fn validate_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidState(
+            "Installation names can't be empty".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+fn validate_game_dir(game_dir: &Path) -> Result<String, AppError> {
+    if !finder::is_valid_game_dir(game_dir) {
+        return Err(AppError::invalid_path(
+            game_dir,
+            "Not a Balatro installation",
+        ));
+    }
+    Ok(game_dir.to_string_lossy().into_owned())
+}
+
+fn not_found(name: &str) -> AppError {
+    AppError::InvalidState(format!("No installation named '{name}'"))
+}
@@ -1,43 +1,150 @@
+use crate::archive_cache;
+use crate::checksum;
+use crate::database::{Database, InstalledMod};
+use crate::download::DownloadJob;
 use crate::errors::AppError;
-use crate::finder::get_lovely_mods_dir;
+use crate::integrity::{self, FileHash};
+use crate::operations::{self, AffectedMod, OperationKind};
+use crate::staging::Staging;
 use flate2::read::GzDecoder;
-use reqwest::Client;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fs;
+use std::io;
 use std::io::Read;
-use std::io::{self, Cursor};
 use std::path::Path;
 use std::path::PathBuf;
 use tar::Archive;
 use zip::ZipArchive;
 
+/// Where an installed mod's files came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModSource {
+    pub download_url: String,
+    /// SHA-256 of the downloaded archive, see [`checksum::sha256_hex`].
+    pub archive_hash: String,
+    /// Every file the archive installed, see [`integrity::manifest`].
+    #[serde(default)]
+    pub files: Vec<FileHash>,
+}
+
+/// Checks a mod staged at the first path before it replaces anything at the
+/// second, where it's about to be installed.
+pub type StagedCheck<'a> = &'a (dyn Fn(&Path, &Path) -> Result<(), AppError> + Sync);
+
 pub async fn install_mod(
-    installation_path: Option<&String>,
+    mods_dir: &Path,
     url: String,
     folder_name: Option<String>,
 ) -> Result<PathBuf, AppError> {
-    let client = Client::new();
-    let response = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(|e| AppError::NetworkRequest {
-            url: url.clone(),
-            source: e.to_string(),
-        })?;
+    install_mod_with_source(
+        mods_dir,
+        url,
+        folder_name,
+        None,
+        None,
+        &DownloadJob::default(),
+    )
+    .await
+    .map(|(path, _)| path)
+}
 
-    let file = response
-        .bytes()
-        .await
-        .map_err(|e| AppError::NetworkRequest {
-            url: url.clone(),
-            source: e.to_string(),
-        })?;
+/// Like [`install_mod`], but also returns where the mod came from, and
+/// reports the download's progress to `job`. When `expected_hash` is given,
+/// an archive that doesn't match it is rejected before anything in the Mods
+/// directory is touched, and a cached archive with that hash is installed
+/// without downloading it again. An error from `check` refuses the install
+/// the same way, leaving any installed copy of the mod in place.
+pub async fn install_mod_with_source(
+    mods_dir: &Path,
+    url: String,
+    folder_name: Option<String>,
+    expected_hash: Option<&str>,
+    check: Option<StagedCheck<'_>>,
+    job: &DownloadJob,
+) -> Result<(PathBuf, ModSource), AppError> {
+    let staged = stage_mod(mods_dir, url, folder_name, expected_hash, job).await?;
+    if let Some(check) = check {
+        check(staged.path(), staged.target())?;
+    }
+    staged.commit()
+}
+
+/// A mod extracted next to the Mods dir by [`stage_mod`], which replaces
+/// whatever is installed under its name once it's committed. Dropping it
+/// instead leaves the Mods dir untouched.
+#[derive(Debug)]
+pub struct StagedMod {
+    staging: Staging,
+    staged_path: PathBuf,
+    target: PathBuf,
+    url: String,
+    archive_hash: String,
+}
 
-    let file_type = infer::get(&file)
+impl StagedMod {
+    /// Where the mod was extracted to.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.staged_path
+    }
+
+    /// Where the mod will be installed.
+    #[must_use]
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Moves the mod into the Mods dir, returning where it was installed and
+    /// where it came from.
+    pub fn commit(self) -> Result<(PathBuf, ModSource), AppError> {
+        self.staging.commit(&self.staged_path, &self.target)?;
+        let files = integrity::manifest(&self.target)?;
+
+        log::info!("Mod installed successfully at: {:?}", self.target);
+        Ok((
+            self.target,
+            ModSource {
+                download_url: self.url,
+                archive_hash: self.archive_hash,
+                files,
+            },
+        ))
+    }
+}
+
+/// The first half of [`install_mod_with_source`]: downloads the archive at
+/// `url` and extracts it into a staging directory, without touching the Mods
+/// dir yet.
+pub async fn stage_mod(
+    mods_dir: &Path,
+    url: String,
+    folder_name: Option<String>,
+    expected_hash: Option<&str>,
+    job: &DownloadJob,
+) -> Result<StagedMod, AppError> {
+    let archive = archive_cache::fetch(&url, expected_hash, job).await?;
+
+    let archive_hash = archive.sha256.clone();
+    if let Some(expected) = expected_hash
+        && !checksum::matches(expected, &archive_hash)
+    {
+        return Err(AppError::ChecksumMismatch {
+            subject: url,
+            expected: expected.to_string(),
+            actual: archive_hash,
+        });
+    }
+
+    let file_type = infer::get_from_path(&archive.path)
+        .map_err(|e| AppError::FileRead {
+            path: archive.path.clone(),
+            source: e.to_string(),
+        })?
         .ok_or_else(|| AppError::InvalidState("Unknown file type".into()))?
         .mime_type();
-
-    let mod_dir = get_lovely_mods_dir(installation_path);
+    let file = archive.open()?;
 
     let mod_name = {
         if let Some(name) = folder_name.filter(|n| !n.is_empty()) {
@@ -66,33 +173,33 @@ pub async fn install_mod(
         }
     };
 
-    // Uninstall old mod folder if it exists
-    let target_dir = mod_dir.join(&mod_name);
-    if target_dir.exists() {
-        log::info!("Uninstalling existing mod at: {target_dir:?}");
-        uninstall_mod(installation_path, target_dir.clone())?;
-    }
-
     log::info!("Installing mod: {url}");
 
-    let installed_path = match file_type {
-        "application/zip" => handle_zip(file, &mod_dir, &mod_name)?,
-        "application/x-tar" => handle_tar(file, &mod_dir, &mod_name)?, // Updated
-        "application/gzip" => handle_tar_gz(file, &mod_dir, &mod_name)?, // Updated
+    // Extract next to the Mods dir first, so a bad archive or a failure
+    // halfway through leaves the installed copy alone
+    let staging = Staging::new(mods_dir, &mod_name)?;
+    let staged_path = match file_type {
+        "application/zip" => handle_zip(file, staging.path(), &mod_name)?,
+        "application/x-tar" => handle_tar(file, staging.path(), &mod_name)?, // Updated
+        "application/gzip" => handle_tar_gz(file, staging.path(), &mod_name)?, // Updated
         _ => {
             return Err(AppError::InvalidState(format!(
                 "Unsupported file type: {file_type}"
-            )))
+            )));
         }
     };
 
-    log::info!("Mod installed successfully at: {installed_path:?}");
-    Ok(installed_path)
+    Ok(StagedMod {
+        staging,
+        staged_path,
+        target: mods_dir.join(&mod_name),
+        url,
+        archive_hash,
+    })
 }
 
-fn handle_zip(file: bytes::Bytes, mod_dir: &Path, mod_name: &str) -> Result<PathBuf, AppError> {
-    let cursor = Cursor::new(file);
-    let mut zip = ZipArchive::new(cursor).map_err(|e| AppError::FileWrite {
+fn handle_zip(file: fs::File, mod_dir: &Path, mod_name: &str) -> Result<PathBuf, AppError> {
+    let mut zip = ZipArchive::new(file).map_err(|e| AppError::FileWrite {
         path: mod_dir.to_path_buf(),
         source: format!("Invalid zip archive: {e}"),
     })?;
@@ -109,14 +216,6 @@ fn handle_zip(file: bytes::Bytes, mod_dir: &Path, mod_name: &str) -> Result<Path
     // The target directory where the mod will be installed
     let target_dir = mod_dir.join(mod_name);
 
-    // Remove target directory if it exists
-    if target_dir.exists() {
-        fs::remove_dir_all(&target_dir).map_err(|e| AppError::FileWrite {
-            path: target_dir.clone(),
-            source: e.to_string(),
-        })?;
-    }
-
     if has_root_files {
         // For ZIPs with root files
         fs::create_dir_all(&target_dir).map_err(|e| AppError::DirCreate {
@@ -164,10 +263,7 @@ fn handle_zip(file: bytes::Bytes, mod_dir: &Path, mod_name: &str) -> Result<Path
     Ok(target_dir)
 }
 
-fn extract_zip_root(
-    zip: &mut ZipArchive<Cursor<bytes::Bytes>>,
-    path: &PathBuf,
-) -> Result<(), AppError> {
+fn extract_zip_root(zip: &mut ZipArchive<fs::File>, path: &PathBuf) -> Result<(), AppError> {
     fs::create_dir_all(path).map_err(|e| AppError::DirCreate {
         path: path.clone(),
         source: e.to_string(),
@@ -199,10 +295,7 @@ fn extract_zip_root(
     Ok(())
 }
 
-fn get_zip_root_dir(
-    zip: &mut ZipArchive<Cursor<bytes::Bytes>>,
-    mod_dir: &Path,
-) -> Result<String, AppError> {
+fn get_zip_root_dir(zip: &mut ZipArchive<fs::File>, mod_dir: &Path) -> Result<String, AppError> {
     let first_entry = zip.by_index(0).map_err(|e| AppError::FileRead {
         path: mod_dir.to_path_buf(),
         source: format!("Zip entry error: {e}"),
@@ -215,7 +308,7 @@ fn get_zip_root_dir(
         .ok_or_else(|| AppError::InvalidState("Empty zip archive".into()))
 }
 
-fn extract_zip(zip: &mut ZipArchive<Cursor<bytes::Bytes>>, mod_dir: &Path) -> Result<(), AppError> {
+fn extract_zip(zip: &mut ZipArchive<fs::File>, mod_dir: &Path) -> Result<(), AppError> {
     for i in 0..zip.len() {
         let mut file = zip.by_index(i).map_err(|e| AppError::FileRead {
             path: mod_dir.to_path_buf(),
@@ -242,15 +335,13 @@ fn extract_zip(zip: &mut ZipArchive<Cursor<bytes::Bytes>>, mod_dir: &Path) -> Re
     Ok(())
 }
 
-fn handle_tar(file: bytes::Bytes, mod_dir: &Path, mod_name: &str) -> Result<PathBuf, AppError> {
-    let cursor = Cursor::new(file);
-    let mut tar = Archive::new(cursor);
+fn handle_tar(file: fs::File, mod_dir: &Path, mod_name: &str) -> Result<PathBuf, AppError> {
+    let mut tar = Archive::new(file);
     extract_tar(&mut tar, mod_dir, mod_name)
 }
 
-fn handle_tar_gz(file: bytes::Bytes, mod_dir: &Path, mod_name: &str) -> Result<PathBuf, AppError> {
-    let cursor = Cursor::new(file);
-    let gz = GzDecoder::new(cursor);
+fn handle_tar_gz(file: fs::File, mod_dir: &Path, mod_name: &str) -> Result<PathBuf, AppError> {
+    let gz = GzDecoder::new(file);
     let mut tar = Archive::new(gz);
     extract_tar(&mut tar, mod_dir, mod_name)
 }
@@ -336,12 +427,10 @@ fn ensure_safe_path(base: &Path, path: &Path) -> Result<(), AppError> {
     }
 }
 
-pub fn uninstall_mod(installation_path: Option<&String>, path: PathBuf) -> Result<(), AppError> {
+pub fn uninstall_mod(mods_dir: &Path, path: PathBuf) -> Result<(), AppError> {
     log::info!("Uninstalling mod: {path:?}");
 
-    let mods_dir = get_lovely_mods_dir(installation_path);
-
-    validate_uninstall_path(&path, &mods_dir)
+    validate_uninstall_path(&path, mods_dir)
         .inspect_err(|e| log::error!("Uninstall path validation error: {e}"))?;
 
     if let Some(dir_name) = path.file_name().and_then(|n| n.to_str()) {
@@ -356,7 +445,7 @@ pub fn uninstall_mod(installation_path: Option<&String>, path: PathBuf) -> Resul
     })
 }
 
-fn validate_uninstall_path(path: &PathBuf, mods_dir: &PathBuf) -> Result<(), AppError> {
+fn validate_uninstall_path(path: &PathBuf, mods_dir: &Path) -> Result<(), AppError> {
     if !path.exists() {
         return Err(AppError::PathValidation {
             path: path.clone(),
@@ -379,3 +468,115 @@ fn validate_uninstall_path(path: &PathBuf, mods_dir: &PathBuf) -> Result<(), App
 
     Ok(())
 }
+
+/// A mod is disabled when lovely finds a `.lovelyignore` file in its root.
+#[must_use]
+pub fn is_mod_enabled(mod_dir: &Path) -> bool {
+    !mod_dir.join(".lovelyignore").exists()
+}
+
+/// Toggles a mod by adding or removing `.lovelyignore` in its root and in each
+/// of its direct subdirectories, so bundled dependencies follow their parent.
+pub fn set_mod_enabled(mod_dir: &Path, enabled: bool) -> Result<(), AppError> {
+    if !mod_dir.exists() {
+        return Err(AppError::invalid_path(mod_dir, "Mod directory not found"));
+    }
+
+    // Collect entries first - this prevents the borrow of mod_dir from being split across threads
+    let entries: Vec<_> = fs::read_dir(mod_dir)
+        .and_then(Iterator::collect::<Result<_, _>>)
+        .map_err(|e| AppError::FileRead {
+            path: mod_dir.to_path_buf(),
+            source: e.to_string(),
+        })?;
+
+    let ignore_file_path = mod_dir.join(".lovelyignore");
+
+    if enabled {
+        // Process directories in parallel
+        entries
+            .par_iter()
+            .filter(|entry| entry.path().is_dir())
+            .try_for_each(|entry| {
+                let ignore_path = entry.path().join(".lovelyignore");
+                if ignore_path.exists() {
+                    fs::remove_file(&ignore_path).map_err(|e| AppError::FileWrite {
+                        path: ignore_path,
+                        source: e.to_string(),
+                    })
+                } else {
+                    Ok(())
+                }
+            })?;
+
+        // Handle the top-level ignore file
+        if ignore_file_path.exists() {
+            fs::remove_file(&ignore_file_path).map_err(|e| AppError::FileWrite {
+                path: ignore_file_path,
+                source: e.to_string(),
+            })?;
+        }
+    } else {
+        // Process directories in parallel for disabling
+        entries
+            .par_iter()
+            .filter(|entry| entry.path().is_dir())
+            .try_for_each(|entry| {
+                let ignore_path = entry.path().join(".lovelyignore");
+                fs::write(&ignore_path, "").map_err(|e| AppError::FileWrite {
+                    path: ignore_path,
+                    source: e.to_string(),
+                })
+            })?;
+
+        // Handle the top-level ignore file
+        fs::write(&ignore_file_path, "").map_err(|e| AppError::FileWrite {
+            path: ignore_file_path,
+            source: e.to_string(),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Uninstalls `root_mod` and every tracked mod that (transitively) depends on
+/// it, returning the names of the removed mods in removal order.
+pub fn cascade_uninstall(db: &Database, root_mod: &str) -> Result<Vec<String>, AppError> {
+    let mut removed = Vec::new();
+    let result = uninstall_with_dependents(db, root_mod, &mut removed);
+    operations::record(
+        db,
+        OperationKind::Uninstall,
+        &removed.iter().map(AffectedMod::from).collect::<Vec<_>>(),
+        Some(format!("{root_mod} and the mods depending on it").as_str()),
+        result,
+    )?;
+    Ok(removed.into_iter().map(|m| m.name).collect())
+}
+
+fn uninstall_with_dependents(
+    db: &Database,
+    root_mod: &str,
+    removed: &mut Vec<InstalledMod>,
+) -> Result<(), AppError> {
+    let mods_dir = db.get_mods_dir()?;
+    let mut to_uninstall = vec![root_mod.to_string()];
+    let mut processed = HashSet::new();
+
+    while let Some(current) = to_uninstall.pop() {
+        if !processed.insert(current.clone()) {
+            continue;
+        }
+
+        let mod_details = db.get_mod_details(&current)?;
+
+        // Add dependents to queue
+        to_uninstall.extend(db.get_dependents(&current)?);
+
+        uninstall_mod(&mods_dir, PathBuf::from(&mod_details.path))?;
+        db.remove_installed_mod(&current)?;
+        removed.push(mod_details);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,231 @@
+use crate::checksum;
+use crate::database::Database;
+use crate::download::DownloadJob;
+use crate::errors::AppError;
+use crate::installer;
+use crate::operations::{self, AffectedMod, OperationKind};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use walkdir::WalkDir;
+
+/// One file of an installed mod, as recorded at install time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileHash {
+    /// Path relative to the mod's directory, always `/`-separated.
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityStatus {
+    /// Every recorded file is there and unchanged.
+    Intact,
+    /// Recorded files were changed or removed.
+    Damaged,
+    /// The mod's directory is gone entirely.
+    Missing,
+    /// The mod was installed before manifests were recorded, or by hand.
+    Unrecorded,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModIntegrity {
+    pub name: String,
+    pub path: String,
+    pub status: IntegrityStatus,
+    pub modified: Vec<String>,
+    pub missing: Vec<String>,
+    /// Files that weren't part of the install. Mods commonly write their own
+    /// settings next to their code, so these alone don't make a mod damaged.
+    pub extra: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairFailure {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RepairReport {
+    pub repaired: Vec<String>,
+    pub failed: Vec<RepairFailure>,
+}
+
+/// Hashes every file under `mod_dir`. The `.lovelyignore` markers are left
+/// out, since enabling and disabling a mod adds and removes them.
+pub fn manifest(mod_dir: &Path) -> Result<Vec<FileHash>, AppError> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(mod_dir) {
+        let entry = entry.map_err(|e| AppError::FileRead {
+            path: mod_dir.to_path_buf(),
+            source: e.to_string(),
+        })?;
+        if !entry.file_type().is_file() || entry.file_name() == ".lovelyignore" {
+            continue;
+        }
+
+        let contents = fs::read(entry.path()).map_err(|e| AppError::FileRead {
+            path: entry.path().to_path_buf(),
+            source: e.to_string(),
+        })?;
+        files.push(FileHash {
+            path: relative_path(mod_dir, entry.path()),
+            sha256: checksum::sha256_hex(&contents),
+        });
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+fn relative_path(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Compares a mod's directory against the manifest recorded when it was
+/// installed.
+pub fn verify_mod(db: &Database, name: &str, path: &str) -> Result<ModIntegrity, AppError> {
+    let mut report = ModIntegrity {
+        name: name.to_string(),
+        path: path.to_string(),
+        status: IntegrityStatus::Intact,
+        modified: Vec::new(),
+        missing: Vec::new(),
+        extra: Vec::new(),
+    };
+
+    let recorded = match db.get_mod_source(path)? {
+        Some(source) if !source.files.is_empty() => source.files,
+        _ => {
+            report.status = IntegrityStatus::Unrecorded;
+            return Ok(report);
+        }
+    };
+    let mod_dir = Path::new(path);
+    if !mod_dir.is_dir() {
+        report.status = IntegrityStatus::Missing;
+        report.missing = recorded.into_iter().map(|f| f.path).collect();
+        return Ok(report);
+    }
+
+    let mut current = manifest(mod_dir)?
+        .into_iter()
+        .map(|f| (f.path, f.sha256))
+        .collect::<HashMap<_, _>>();
+    for file in recorded {
+        match current.remove(&file.path) {
+            Some(hash) if checksum::matches(&file.sha256, &hash) => {}
+            Some(_) => report.modified.push(file.path),
+            None => report.missing.push(file.path),
+        }
+    }
+    report.extra = current.into_keys().collect();
+    report.extra.sort();
+
+    if !report.modified.is_empty() || !report.missing.is_empty() {
+        report.status = IntegrityStatus::Damaged;
+    }
+    Ok(report)
+}
+
+/// Verifies every tracked mod.
+pub fn verify(db: &Database) -> Result<Vec<ModIntegrity>, AppError> {
+    db.get_installed_mods()?
+        .into_iter()
+        .map(|m| verify_mod(db, &m.name, &m.path))
+        .collect()
+}
+
+/// Reinstalls the damaged or missing mods among `names` (every tracked mod
+/// when `None`) from the archive they were originally installed from. The
+/// archive has to hash the same as it did back then, and each mod keeps its
+/// enabled state. Mods that fail don't stop the rest.
+pub async fn repair(
+    db: &Mutex<Database>,
+    names: Option<&[String]>,
+    job: &DownloadJob,
+) -> Result<RepairReport, AppError> {
+    let (mods_dir, damaged) = {
+        let db = db.lock()?;
+        let damaged = verify(&db)?
+            .into_iter()
+            .filter(|m| names.is_none_or(|names| names.contains(&m.name)))
+            .filter(|m| {
+                matches!(
+                    m.status,
+                    IntegrityStatus::Damaged | IntegrityStatus::Missing
+                )
+            })
+            .map(|m| {
+                let version = db
+                    .get_mod_details(&m.name)
+                    .ok()
+                    .and_then(|installed| installed.current_version);
+                (m, version)
+            })
+            .collect::<Vec<_>>();
+        (db.get_mods_dir()?, damaged)
+    };
+
+    let mut report = RepairReport::default();
+    for (damaged_mod, version) in damaged {
+        let result = repair_mod(db, &mods_dir, &damaged_mod.path, job).await;
+        match operations::record_locked(
+            db,
+            OperationKind::Repair,
+            &[AffectedMod::new(&damaged_mod.name, version)],
+            None,
+            result,
+        ) {
+            Ok(()) => report.repaired.push(damaged_mod.name),
+            Err(e) if job.is_cancelled() => return Err(e),
+            Err(e) => report.failed.push(RepairFailure {
+                name: damaged_mod.name,
+                reason: e.to_string(),
+            }),
+        }
+    }
+    Ok(report)
+}
+
+async fn repair_mod(
+    db: &Mutex<Database>,
+    mods_dir: &Path,
+    path: &str,
+    job: &DownloadJob,
+) -> Result<(), AppError> {
+    let source = db
+        .lock()?
+        .get_mod_source(path)?
+        .ok_or_else(|| AppError::InvalidState(format!("No recorded source for {path}")))?;
+    let mod_path = PathBuf::from(path);
+    let folder_name = mod_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| AppError::invalid_path(&mod_path, "Mod path has no directory name"))?;
+    let enabled = installer::is_mod_enabled(&mod_path);
+
+    log::info!("Repairing {path} from {}", source.download_url);
+    let (installed_path, new_source) = installer::install_mod_with_source(
+        mods_dir,
+        source.download_url.clone(),
+        Some(folder_name),
+        Some(&source.archive_hash),
+        None,
+        job,
+    )
+    .await?;
+    installer::set_mod_enabled(&installed_path, enabled)?;
+
+    db.lock()?
+        .set_mod_source(&installed_path.to_string_lossy(), &new_source)
+}
@@ -0,0 +1,243 @@
+use crate::database::Database;
+use crate::download::DownloadJob;
+use crate::errors::AppError;
+use crate::finder::get_lovely_mods_dir;
+use crate::game_version::{self, VersionCheck};
+use crate::lovely;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+#[cfg(target_os = "linux")]
+const BALATRO_STEAMID: &str = "2379780";
+
+/// An empty directory for `LOVELY_MOD_DIR`, so lovely starts the game without
+/// loading anything from the real Mods dir.
+fn vanilla_mods_dir() -> Result<PathBuf, AppError> {
+    tempfile::tempdir()
+        .map(|dir| dir.path().to_path_buf())
+        .map_err(|e| AppError::DirCreate {
+            path: std::env::temp_dir(),
+            source: e.to_string(),
+        })
+}
+
+/// Launches the game at `path`, installing lovely first if it's missing: at
+/// the version recorded in `db`, or the latest one if there's none. The
+/// lovely download reports its progress to `job`.
+///
+/// Unless `force` is set, enabled mods that require a different version of
+/// the game keep a modded launch from going ahead. Once nothing does, the
+/// game version is recorded for the next launch, and the returned check
+/// tells whether it changed since the last one.
+#[allow(clippy::too_many_lines)]
+pub async fn launch_balatro(
+    db: &Mutex<Database>,
+    path: &Path,
+    vanilla: bool,
+    force: bool,
+    job: &DownloadJob,
+) -> Result<VersionCheck, AppError> {
+    let game_dir = path.to_string_lossy().into_owned();
+    let (check, installation) = {
+        let db = db.lock()?;
+        (
+            game_version::compare(&db, path)?,
+            db.installations().by_game_dir(&game_dir)?,
+        )
+    };
+    if !vanilla && !force {
+        let mods_dir = match &installation {
+            Some(installation) => installation.resolved_mods_dir()?,
+            None => get_lovely_mods_dir(Some(&game_dir))?,
+        };
+        game_version::check_enabled_mods(Some(&game_dir), &mods_dir)?;
+    }
+    // An empty directory for vanilla launches, or the installation's own
+    // Mods directory if it has one
+    let lovely_mod_dir = if vanilla {
+        Some(vanilla_mods_dir()?)
+    } else {
+        installation
+            .and_then(|installation| installation.mods_dir)
+            .map(PathBuf::from)
+    };
+
+    let lovely_path = lovely::lovely_path(path)?;
+    let recorded = db.lock()?.get_lovely_install(&lovely_path)?;
+    if let Some(installed) = lovely::ensure_installed(&lovely_path, recorded.as_ref(), job).await? {
+        db.lock()?.set_lovely_install(&installed)?;
+    }
+    // Recorded only now, so a launch that was refused still warns about the
+    // change when it's retried
+    db.lock()?.set_game_version(&check.current)?;
+
+    #[cfg(target_os = "macos")]
+    {
+        let balatro_executable = path.join("Balatro.app/Contents/MacOS/love");
+
+        // Instead of using double quotes which cause conflicts in AppleScript,
+        // wrap the file paths in single quotes.
+        let command_line = format!(
+            "cd '{}' && {} DYLD_INSERT_LIBRARIES='{}' '{}'",
+            path.display(),
+            lovely_mod_dir
+                .as_ref()
+                .map(|dir| format!("LOVELY_MOD_DIR='{}'", dir.display()))
+                .unwrap_or_default(),
+            lovely_path.display(),
+            balatro_executable.display(),
+        );
+
+        // Construct the AppleScript command to run the command_line in Terminal.
+        let applescript = format!("tell application \"Terminal\" to do script \"{command_line}\"");
+
+        Command::new("osascript")
+            .arg("-e")
+            .arg(applescript)
+            .spawn()
+            .map_err(|e| AppError::ProcessExecution(e.to_string()))?;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // Find the executable file in the directory
+        let exe_path = find_executable_in_directory(path).ok_or_else(|| {
+            AppError::ProcessExecution(format!("No executable found in {}", path.display()))
+        })?;
+
+        let mut command = Command::new(&exe_path);
+        command.current_dir(path);
+        if let Some(dir) = &lovely_mod_dir {
+            command.env("LOVELY_MOD_DIR", dir);
+        }
+        command.spawn().map_err(|e| {
+            AppError::ProcessExecution(format!("Failed to launch {}: {}", exe_path.display(), e))
+        })?;
+
+        log::debug!("Launched game from {}", exe_path.display());
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        if let Ok(steam_exe) = which::which("steam") {
+            let mut command = Command::new(steam_exe);
+            command.args(vec!["-applaunch", BALATRO_STEAMID]);
+            if let Some(dir) = &lovely_mod_dir {
+                command.env("LOVELY_MOD_DIR", dir);
+            }
+            if command.spawn().is_ok() {
+                log::debug!("Launched Balatro through Steam executable");
+                return Ok(check);
+            }
+        }
+
+        let url_handler = Command::new("xdg-mime")
+            .args(["query", "default", "x-scheme-handler/steam"])
+            .output()
+            .map_err(|e| format!("Failed to query `steam://` handler: {e}"))
+            .and_then(|output| {
+                let output = String::from_utf8_lossy(&output.stdout);
+                if output.trim().is_empty() {
+                    return Err("No default `steam://` handler found".to_string());
+                }
+                if output.trim() != "steam.desktop" {
+                    log::warn!(
+                        "The system's default `steam://` handler is {output} instead of steam"
+                    );
+                }
+                Ok(())
+            });
+        if url_handler.is_ok() && {
+            let mut command = Command::new("xdg-open");
+            if let Some(dir) = &lovely_mod_dir {
+                command.env("LOVELY_MOD_DIR", dir);
+            }
+            command
+                .arg(format!("steam://run/{BALATRO_STEAMID}"))
+                .spawn()
+                .is_ok()
+        } {
+            log::debug!("launched balatro through steam url protocol");
+            return Ok(check);
+        }
+
+        let balatro_exe = find_executable_in_directory(path)
+            .ok_or_else(|| {
+                AppError::ProcessExecution(format!("No executable found in {}", path.display()))
+            })
+            .inspect_err(|e| log::error!("{e}"))?;
+        let mut command = Command::new(&balatro_exe);
+        command
+            .current_dir(path)
+            .env("WINEDLLOVERRIDES", "version=n,b");
+        if let Some(dir) = &lovely_mod_dir {
+            command.env("LOVELY_MOD_DIR", dir);
+        }
+
+        log::debug!("attempting to launch {}", balatro_exe.display());
+        command.spawn().map_err(|e| {
+            AppError::ProcessExecution(format!("failed to launch {}: {}", balatro_exe.display(), e))
+        })?;
+        log::debug!("launched Balatro.exe directly with WINEDLLOVERRIDES");
+    }
+
+    Ok(check)
+}
+
+#[cfg(target_os = "linux")]
+fn find_executable_in_directory(dir: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let entries = std::fs::read_dir(dir).ok()?;
+
+    // Collect all executable files
+    let executables: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && std::fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+        })
+        .collect();
+
+    let file_name = |exe: &PathBuf| {
+        exe.file_name()
+            .and_then(|n| n.to_str())
+            .map(str::to_lowercase)
+            .unwrap_or_default()
+    };
+
+    // First look for any executable with "balatro" in the name, then for a "love"
+    // executable, and if neither was found return the first one
+    executables
+        .iter()
+        .find(|exe| file_name(exe).contains("balatro"))
+        .or_else(|| executables.iter().find(|exe| file_name(exe) == "love"))
+        .or_else(|| executables.first())
+        .cloned()
+}
+
+#[cfg(target_os = "windows")]
+fn find_executable_in_directory(dir: &Path) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(dir).ok()?;
+
+    // First, collect all executable files in the directory
+    let executables: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension() == Some(std::ffi::OsStr::new("exe")))
+        .collect();
+
+    // Look for any executable with "balatro" in the name (case-insensitive), and
+    // if no Balatro-specific executable was found return the first executable
+    executables
+        .iter()
+        .find(|exe| {
+            exe.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.to_lowercase().contains("balatro"))
+        })
+        .or_else(|| executables.first())
+        .cloned()
+}
@@ -7,13 +7,37 @@
 #![warn(clippy::perf, clippy::pedantic)]
 #![allow(clippy::missing_errors_doc)]
 
+pub mod archive_cache;
 pub mod cache;
+pub mod checksum;
+pub mod conflicts;
 pub mod database;
+pub mod dependencies;
+pub mod download;
 pub mod errors;
 pub mod finder;
+pub mod game_source;
+pub mod game_version;
+pub mod installations;
 pub mod installer;
+pub mod integrity;
+pub mod launcher;
 pub mod local_mod_detection;
+pub mod lockfile;
 pub mod logging;
 pub mod lovely;
+pub mod lovely_patches;
+pub mod migrations;
 pub mod mod_collections;
+pub mod operations;
+pub mod patch_preview;
+pub mod paths;
+pub mod pins;
+pub mod settings;
 pub mod smods_installer;
+pub mod staging;
+pub mod steam;
+pub mod updates;
+pub mod vdf;
+pub mod version;
+pub mod version_history;
@@ -1,6 +1,5 @@
 use crate::cache;
 use crate::database::Database;
-use crate::finder::get_lovely_mods_dir;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fs::{self, File};
@@ -19,6 +18,8 @@ pub struct DetectedMod {
     pub path: String,
     pub dependencies: Vec<String>,
     pub conflicts: Vec<String>,
+    #[serde(default)]
+    pub provides: Vec<String>,
     pub catalog_match: Option<CatalogMatch>,
     pub is_duplicate: bool,
 }
@@ -85,6 +86,7 @@ fn parse_thunderstore_manifest(
             path: mod_path.to_string_lossy().to_string(),
             dependencies: manifest.dependencies.unwrap_or_default(),
             conflicts: Vec::new(),
+            provides: Vec::new(),
             catalog_match: None,
             is_duplicate: false,
         }));
@@ -107,6 +109,7 @@ fn parse_thunderstore_manifest(
         path: mod_path.to_string_lossy().to_string(),
         dependencies: manifest.dependencies.unwrap_or_default(),
         conflicts: Vec::new(),
+        provides: Vec::new(),
         catalog_match: None,
         is_duplicate: false,
     }))
@@ -116,10 +119,7 @@ pub fn detect_manual_mods(
     db: &Database,
     cached_catalog_mods: &[cache::Mod],
 ) -> Result<Vec<DetectedMod>, String> {
-    #[cfg(not(target_os = "linux"))]
-    let mods_dir = get_lovely_mods_dir(None);
-    #[cfg(target_os = "linux")]
-    let mods_dir = get_lovely_mods_dir(db.get_installation_path()?.as_ref());
+    let mods_dir = db.get_mods_dir()?;
 
     if !mods_dir.exists() {
         return Ok(Vec::new());
@@ -515,6 +515,15 @@ fn count_path_depth(path: &Path, base_path: &Path) -> usize {
         .count()
 }
 
+/// Reads the metadata of the single mod installed at `mod_path`, if there is one.
+#[must_use]
+pub fn detect_mod(mod_path: &Path) -> Option<DetectedMod> {
+    detect_mod_in_directory(mod_path)
+        .inspect_err(|e| log::warn!("Failed to detect mod in {}: {e}", mod_path.display()))
+        .ok()
+        .flatten()
+}
+
 fn detect_mod_in_directory(mod_path: &Path) -> Result<Option<DetectedMod>, String> {
     // Get directory name
     let dir_name = mod_path
@@ -574,6 +583,7 @@ fn detect_mod_in_directory(mod_path: &Path) -> Result<Option<DetectedMod>, Strin
                 path: mod_path.to_string_lossy().to_string(),
                 dependencies: Vec::new(),
                 conflicts: Vec::new(),
+                provides: Vec::new(),
                 catalog_match: None,
                 is_duplicate: false,
             }));
@@ -631,6 +641,7 @@ fn detect_mod_in_directory(mod_path: &Path) -> Result<Option<DetectedMod>, Strin
                 path: mod_path.to_string_lossy().to_string(),
                 dependencies: Vec::new(),
                 conflicts: Vec::new(),
+                provides: Vec::new(),
                 catalog_match: None,
                 is_duplicate: false,
             }));
@@ -762,6 +773,7 @@ fn parse_mod_json(json_path: &Path, mod_path: &Path) -> Result<Option<DetectedMo
         path: mod_path.to_string_lossy().to_string(),
         dependencies: mod_json.dependencies,
         conflicts: mod_json.conflicts,
+        provides: mod_json.provides,
         catalog_match: None,
         is_duplicate: false,
     }))
@@ -809,6 +821,7 @@ fn parse_mod_lua_header(lua_path: &Path, mod_path: &Path) -> Result<Option<Detec
                 path: mod_path.to_string_lossy().to_string(),
                 dependencies: Vec::new(),
                 conflicts: Vec::new(),
+                provides: Vec::new(),
                 catalog_match: None,
                 is_duplicate: false,
             }));
@@ -918,6 +931,7 @@ fn parse_mod_lua_header(lua_path: &Path, mod_path: &Path) -> Result<Option<Detec
         path: mod_path.to_string_lossy().to_string(),
         dependencies,
         conflicts,
+        provides: Vec::new(),
         catalog_match: None,
         is_duplicate: false,
     }))
@@ -0,0 +1,344 @@
+use crate::cache;
+use crate::database::Database;
+use crate::dependencies::find_in_catalog;
+use crate::download::DownloadJob;
+use crate::errors::AppError;
+use crate::installer;
+use crate::operations::{self, AffectedMod, OperationKind};
+use crate::smods_installer::{ModInstaller, ModType};
+use crate::version::Version;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Bumped whenever a lockfile written by this version couldn't be read by an
+/// older one.
+pub const LOCKFILE_VERSION: u32 = 1;
+
+/// A reproducible snapshot of the tracked mods, meant to be handed to someone
+/// else so they end up with the exact same setup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub lockfile_version: u32,
+    /// Steamodded, when it was installed from a release.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steamodded: Option<LockedRelease>,
+    /// Talisman, when it was installed from a release.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub talisman: Option<LockedRelease>,
+    pub mods: Vec<LockedMod>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedRelease {
+    /// The release tag, as `ModInstaller::install_version` takes it.
+    pub version: String,
+    pub download_url: String,
+    /// SHA-256 of the release archive that was installed.
+    pub archive_hash: String,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedMod {
+    pub name: String,
+    #[serde(default)]
+    pub catalog_id: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Where the installed archive came from. Mods installed before sources
+    /// were recorded don't have one; importing them falls back to the catalog
+    /// entry for `catalog_id`, but only while it still has `version`.
+    #[serde(default)]
+    pub download_url: Option<String>,
+    /// SHA-256 of the archive that was installed. Entries without one are
+    /// installed unverified.
+    #[serde(default)]
+    pub archive_hash: Option<String>,
+    pub folder_name: String,
+    pub enabled: bool,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportFailure {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub installed: Vec<String>,
+    pub failed: Vec<ImportFailure>,
+}
+
+impl Lockfile {
+    pub fn read(path: &Path) -> Result<Self, AppError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| AppError::FileRead {
+            path: path.to_path_buf(),
+            source: e.to_string(),
+        })?;
+        let lockfile: Self = serde_json::from_str(&contents).map_err(|e| AppError::JsonParse {
+            path: path.to_path_buf(),
+            source: e.to_string(),
+        })?;
+
+        if lockfile.lockfile_version > LOCKFILE_VERSION {
+            return Err(AppError::InvalidState(format!(
+                "{} was written by a newer version of bromomethane (lockfile version {})",
+                path.display(),
+                lockfile.lockfile_version
+            )));
+        }
+        Ok(lockfile)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), AppError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?).map_err(|e| AppError::FileWrite {
+            path: path.to_path_buf(),
+            source: e.to_string(),
+        })
+    }
+}
+
+/// Snapshots every tracked mod. `catalog` pairs index ids with catalog
+/// entries, and is only used to fill in ids. Mods whose source wasn't
+/// recorded when they were installed are exported without a download URL
+/// rather than with whatever the catalog serves now.
+pub fn export(db: &Database, catalog: &[(String, cache::Mod)]) -> Result<Lockfile, AppError> {
+    let catalog_mods = catalog.iter().map(|(_, m)| m.clone()).collect::<Vec<_>>();
+    let mut lockfile = Lockfile {
+        lockfile_version: LOCKFILE_VERSION,
+        steamodded: None,
+        talisman: None,
+        mods: Vec::new(),
+    };
+
+    for installed_mod in db.get_installed_mods()? {
+        let path = PathBuf::from(&installed_mod.path);
+        let enabled = installer::is_mod_enabled(&path);
+        let source = db.get_mod_source(&installed_mod.path)?;
+
+        let release = source.as_ref().and_then(|source| {
+            ModType::from_mod_name(&installed_mod.name).and_then(|mod_type| {
+                mod_type
+                    .release_tag_from_url(&source.download_url)
+                    .map(|tag| {
+                        let release = LockedRelease {
+                            version: tag.to_string(),
+                            download_url: source.download_url.clone(),
+                            archive_hash: source.archive_hash.clone(),
+                            enabled,
+                        };
+                        (mod_type, release)
+                    })
+            })
+        });
+        match release {
+            Some((ModType::Steamodded, release)) => {
+                lockfile.steamodded = Some(release);
+                continue;
+            }
+            Some((ModType::Talisman, release)) => {
+                lockfile.talisman = Some(release);
+                continue;
+            }
+            None => {}
+        }
+
+        let catalog_mod = find_in_catalog(&catalog_mods, &installed_mod.name);
+        let catalog_id = catalog_mod.and_then(|found| {
+            catalog
+                .iter()
+                .find(|(_, m)| m.title == found.title)
+                .map(|(id, _)| id.clone())
+        });
+        let folder_name = path.file_name().map_or_else(
+            || installed_mod.name.clone(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+
+        lockfile.mods.push(LockedMod {
+            catalog_id,
+            version: installed_mod.current_version.filter(|v| !v.is_empty()),
+            download_url: source.as_ref().map(|s| s.download_url.clone()),
+            archive_hash: source.map(|s| s.archive_hash),
+            folder_name,
+            enabled,
+            dependencies: installed_mod.dependencies,
+            name: installed_mod.name,
+        });
+    }
+
+    lockfile.mods.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(lockfile)
+}
+
+const IMPORT_DETAIL: &str = "Imported from a lockfile";
+
+/// Installs everything in `lockfile` at the locked versions. Archives are
+/// checked against their locked hash before they're extracted, so a download
+/// URL that now serves something else is reported instead of installed.
+/// Entries without a download URL are looked up in `catalog` by their
+/// `catalog_id`. Entries that fail don't stop the rest of the import. Mods
+/// that aren't in the lockfile are left alone. Cancelling `job` stops the
+/// import.
+pub async fn import(
+    db: &Mutex<Database>,
+    lockfile: &Lockfile,
+    catalog: &[(String, cache::Mod)],
+    job: &DownloadJob,
+) -> Result<ImportReport, AppError> {
+    let mods_dir = db.lock()?.get_mods_dir()?;
+    let mut report = ImportReport::default();
+
+    for (mod_type, release) in [
+        (ModType::Steamodded, &lockfile.steamodded),
+        (ModType::Talisman, &lockfile.talisman),
+    ] {
+        let Some(release) = release else {
+            continue;
+        };
+        let name = mod_type.to_string();
+        let result = import_release(db, &mods_dir, mod_type, release, job).await;
+        match operations::record_locked(
+            db,
+            OperationKind::FrameworkInstall,
+            &[AffectedMod::new(&name, Some(release.version.clone()))],
+            Some(IMPORT_DETAIL),
+            result,
+        ) {
+            Ok(()) => report.installed.push(name),
+            Err(e) if job.is_cancelled() => return Err(e),
+            Err(e) => report.failed.push(ImportFailure {
+                name,
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    for locked_mod in &lockfile.mods {
+        let result = import_mod(db, &mods_dir, locked_mod, catalog, job).await;
+        match operations::record_locked(
+            db,
+            OperationKind::Install,
+            &[AffectedMod::new(
+                &locked_mod.name,
+                locked_mod.version.clone(),
+            )],
+            Some(IMPORT_DETAIL),
+            result,
+        ) {
+            Ok(()) => report.installed.push(locked_mod.name.clone()),
+            Err(e) if job.is_cancelled() => return Err(e),
+            Err(e) => report.failed.push(ImportFailure {
+                name: locked_mod.name.clone(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    Ok(report)
+}
+
+async fn import_release(
+    db: &Mutex<Database>,
+    mods_dir: &Path,
+    mod_type: ModType,
+    release: &LockedRelease,
+    job: &DownloadJob,
+) -> Result<(), AppError> {
+    let name = mod_type.to_string();
+    let (path, source) = ModInstaller::new(Some(mods_dir), mod_type)
+        .install_archive_with_source(
+            release.download_url.clone(),
+            Some(&release.archive_hash),
+            job,
+        )
+        .await?;
+    installer::set_mod_enabled(Path::new(&path), release.enabled)?;
+
+    let db = db.lock()?;
+    db.record_install(&name, &path, &[], Some(release.version.clone()), &source)
+}
+
+async fn import_mod(
+    db: &Mutex<Database>,
+    mods_dir: &Path,
+    locked_mod: &LockedMod,
+    catalog: &[(String, cache::Mod)],
+    job: &DownloadJob,
+) -> Result<(), AppError> {
+    let url = match &locked_mod.download_url {
+        Some(url) => url.clone(),
+        None => catalog_download(locked_mod, catalog)?,
+    };
+    if locked_mod.archive_hash.is_none() {
+        log::warn!(
+            "Installing {} without verification, the lockfile has no archive hash for it",
+            locked_mod.name
+        );
+    }
+
+    let (path, source) = installer::install_mod_with_source(
+        mods_dir,
+        url,
+        Some(locked_mod.folder_name.clone()),
+        locked_mod.archive_hash.as_deref(),
+        None,
+        job,
+    )
+    .await?;
+    installer::set_mod_enabled(&path, locked_mod.enabled)?;
+
+    let path = path.to_string_lossy();
+    let db = db.lock()?;
+    db.record_install(
+        &locked_mod.name,
+        &path,
+        &locked_mod.dependencies,
+        locked_mod.version.clone(),
+        &source,
+    )
+}
+
+/// The catalog's download for an entry whose source wasn't recorded, as long
+/// as the catalog still has the locked version.
+fn catalog_download(
+    locked_mod: &LockedMod,
+    catalog: &[(String, cache::Mod)],
+) -> Result<String, AppError> {
+    let unreproducible = |reason: String| AppError::ModInstall {
+        mod_name: locked_mod.name.clone(),
+        source: format!("the lockfile has no download URL for it, and {reason}"),
+    };
+
+    let catalog_mod = locked_mod
+        .catalog_id
+        .as_ref()
+        .and_then(|id| catalog.iter().find(|(catalog_id, _)| catalog_id == id))
+        .map(|(_, catalog_mod)| catalog_mod)
+        .ok_or_else(|| unreproducible("it isn't in the catalog".to_string()))?;
+    let locked_version = locked_mod
+        .version
+        .as_deref()
+        .ok_or_else(|| unreproducible("no version to check the catalog against".to_string()))?;
+
+    match catalog_mod.version.as_deref() {
+        Some(current) if same_version(locked_version, current) => {
+            Ok(catalog_mod.download_url.clone())
+        }
+        current => Err(unreproducible(format!(
+            "the catalog has version {} instead of {locked_version}",
+            current.unwrap_or("unknown")
+        ))),
+    }
+}
+
+fn same_version(a: &str, b: &str) -> bool {
+    match (a.parse::<Version>(), b.parse::<Version>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a.trim() == b.trim(),
+    }
+}
@@ -1,4 +1,5 @@
 use crate::errors::AppError;
+use crate::paths::{self, Paths};
 use chrono::Local;
 use std::fs;
 use std::io::{self, Write};
@@ -14,14 +15,7 @@ pub fn init_logger() -> Result<(), AppError> {
     }
 
     // Create log directory in config dir
-    let config_dir = dirs::config_dir()
-        .ok_or_else(|| AppError::DirNotFound(PathBuf::from("config directory")))?;
-    let log_dir = config_dir.join("Balatro").join("logs");
-
-    fs::create_dir_all(&log_dir).map_err(|e| AppError::DirCreate {
-        path: log_dir.clone(),
-        source: e.to_string(),
-    })?;
+    let log_dir = paths::ensure_dir(Paths::get()?.log_dir())?;
 
     // Create a unique log file with timestamp
     let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
@@ -1,37 +1,416 @@
+use crate::archive_cache;
+use crate::checksum;
+use crate::database::Database;
+use crate::download::DownloadJob;
 use crate::errors::AppError;
+use reqwest::header::{ACCEPT, USER_AGENT};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LOVELY_REPO: &str = "ethangreen-dev/lovely-injector";
+
+/// Where lovely is installed for the game at `game_dir`: `version.dll` next
+/// to the game on Windows and Linux, and one `liblovely.dylib` for every
+/// installation on macOS.
+pub fn lovely_path(
+    #[cfg(any(target_os = "windows", target_os = "linux"))] game_dir: &Path,
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))] _game_dir: &Path,
+) -> Result<PathBuf, AppError> {
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    {
+        Ok(game_dir.join("version.dll"))
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+    {
+        Ok(crate::paths::Paths::get()?
+            .config_dir
+            .join("bins")
+            .join("liblovely.dylib"))
+    }
+}
+
+/// A lovely release with a build for this platform.
+#[derive(Debug, Clone, Serialize)]
+pub struct LovelyRelease {
+    pub tag: String,
+    pub published_at: String,
+    pub prerelease: bool,
+    /// The archive holding the build for this platform.
+    pub asset_url: String,
+    /// SHA-256 of that archive as published by GitHub, which only lists it
+    /// for releases from mid-2025 on.
+    pub asset_sha256: Option<String>,
+}
+
+/// The lovely build bromomethane installed at `path`, as recorded in the
+/// database.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LovelyInstall {
+    pub path: String,
+    pub version: String,
+    /// SHA-256 of the installed library itself, not of the archive.
+    pub sha256: String,
+    /// Seconds since the Unix epoch.
+    pub installed_at: u64,
+}
 
-use std::fs::File;
-use std::path::PathBuf;
+/// What's at the lovely path of an installation compared to the database.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LovelyStatus {
+    Missing,
+    /// Installed, but not by bromomethane, so its version isn't known.
+    Untracked {
+        sha256: String,
+    },
+    Verified(LovelyInstall),
+    /// The recorded build was replaced or changed since it was installed.
+    Modified {
+        install: LovelyInstall,
+        sha256: String,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    published_at: Option<String>,
+    prerelease: bool,
+    draft: bool,
+    assets: Vec<ReleaseAsset>,
+}
 
-static RENEWED_LOVELY: std::sync::OnceLock<()> = std::sync::OnceLock::new();
+#[derive(Debug, Deserialize)]
+struct ReleaseAsset {
+    name: String,
+    browser_download_url: String,
+    /// `sha256:<hex>`.
+    #[serde(default)]
+    digest: Option<String>,
+}
+
+/// The lovely releases that have a build for this platform, newest first.
+pub async fn list_releases() -> Result<Vec<LovelyRelease>, AppError> {
+    let releases: Vec<Release> = github_get(&format!(
+        "https://api.github.com/repos/{LOVELY_REPO}/releases"
+    ))
+    .await?;
+    let asset_name = asset_name()?;
+    Ok(releases
+        .into_iter()
+        .filter(|release| !release.draft)
+        .filter_map(|release| LovelyRelease::from_github(release, &asset_name))
+        .collect())
+}
+
+/// The release tagged `tag`, or the latest release with `None`.
+pub async fn get_release(tag: Option<&str>) -> Result<LovelyRelease, AppError> {
+    let url = match tag {
+        Some(tag) => format!("https://api.github.com/repos/{LOVELY_REPO}/releases/tags/{tag}"),
+        None => format!("https://api.github.com/repos/{LOVELY_REPO}/releases/latest"),
+    };
+    let release: Release = github_get(&url).await.map_err(|e| match (tag, &e) {
+        (Some(tag), AppError::NetworkRequest { source, .. }) if source.contains("404") => {
+            AppError::InvalidVersion(format!("lovely {tag}"))
+        }
+        _ => e,
+    })?;
+    let asset_name = asset_name()?;
+    let tag = release.tag_name.clone();
+    LovelyRelease::from_github(release, &asset_name)
+        .ok_or_else(|| AppError::InvalidState(format!("lovely {tag} has no {asset_name} build")))
+}
+
+impl LovelyRelease {
+    fn from_github(release: Release, asset_name: &str) -> Option<Self> {
+        let asset = release
+            .assets
+            .into_iter()
+            .find(|asset| asset.name == asset_name)?;
+        Some(Self {
+            tag: release.tag_name,
+            published_at: release.published_at.unwrap_or_default(),
+            prerelease: release.prerelease,
+            asset_url: asset.browser_download_url,
+            asset_sha256: asset
+                .digest
+                .and_then(|digest| digest.strip_prefix("sha256:").map(str::to_string)),
+        })
+    }
+}
+
+async fn github_get<T: serde::de::DeserializeOwned>(url: &str) -> Result<T, AppError> {
+    let response = reqwest::Client::new()
+        .get(url)
+        .header(ACCEPT, "application/vnd.github+json")
+        .header("X-GitHub-Api-Version", "2022-11-28")
+        .header(USER_AGENT, "Balatro-Mod-Manager/1.0")
+        .send()
+        .await?;
+    Ok(response.error_for_status()?.json().await?)
+}
+
+/// The name of the release asset with the build for this platform.
+#[cfg_attr(not(target_os = "macos"), allow(clippy::unnecessary_wraps))]
+fn asset_name() -> Result<String, AppError> {
+    #[cfg(target_os = "macos")]
+    {
+        Ok(format!(
+            "lovely-{}-apple-darwin.tar.gz",
+            detect_architecture()?
+        ))
+    }
+
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    {
+        Ok("lovely-x86_64-pc-windows-msvc.zip".to_string())
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        Err(AppError::InvalidState(
+            "Lovely injection is not supported on this platform.".into(),
+        ))
+    }
+}
+
+/// Compares the lovely at `path` with what the database says was installed
+/// there.
+pub fn status(path: &Path, recorded: Option<&LovelyInstall>) -> Result<LovelyStatus, AppError> {
+    if !path.is_file() {
+        return Ok(LovelyStatus::Missing);
+    }
+    let sha256 = file_sha256(path)?;
+    Ok(match recorded {
+        None => LovelyStatus::Untracked { sha256 },
+        Some(install) if checksum::matches(&install.sha256, &sha256) => {
+            LovelyStatus::Verified(install.clone())
+        }
+        Some(install) => LovelyStatus::Modified {
+            install: install.clone(),
+            sha256,
+        },
+    })
+}
 
-#[cfg(any(target_os = "windows", target_os = "linux"))]
-pub async fn ensure_version_dll_exists(
-    game_path: &std::path::Path,
-) -> Result<Option<PathBuf>, AppError> {
-    let dll_path = game_path.join("version.dll");
+/// Installs `release` at `path`. The archive is checked against the hash
+/// GitHub publishes for it, and the library inside against the file format
+/// of this platform, before the one at `path` is replaced, so a bad download
+/// never takes the place of a working lovely. The result is meant to be
+/// stored with [`Database::set_lovely_install`](crate::database::Database::set_lovely_install).
+pub async fn install(
+    path: &Path,
+    release: &LovelyRelease,
+    job: &DownloadJob,
+) -> Result<LovelyInstall, AppError> {
+    log::info!(
+        "Installing lovely {} from {} to {}",
+        release.tag,
+        release.asset_url,
+        path.display()
+    );
+    let archive =
+        archive_cache::fetch(&release.asset_url, release.asset_sha256.as_deref(), job).await?;
+    if let Some(expected) = &release.asset_sha256
+        && !checksum::matches(expected, &archive.sha256)
+    {
+        return Err(AppError::ChecksumMismatch {
+            subject: release.asset_url.clone(),
+            expected: expected.clone(),
+            actual: archive.sha256.clone(),
+        });
+    }
 
-    if RENEWED_LOVELY.get().is_some() && dll_path.exists() {
-        log::debug!("skipping version.dll refresh");
-        return Ok(Some(dll_path));
+    let library = extract_library(&archive.path, &release.asset_url)?;
+    check_library(&library, &release.tag)?;
+    let sha256 = checksum::sha256_hex(&library);
+    if path.is_file() && file_sha256(path).is_ok_and(|current| current == sha256) {
+        log::info!("lovely {} is already installed", release.tag);
+    } else {
+        replace(path, &library)?;
     }
 
-    match download_version_dll(&dll_path).await {
-        Ok(()) => {
-            _ = RENEWED_LOVELY.set(());
+    Ok(LovelyInstall {
+        path: path.to_string_lossy().into_owned(),
+        version: release.tag.clone(),
+        sha256,
+        installed_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    })
+}
+
+/// The lovely of the active installation, checked against the database.
+pub fn installation_status(db: &Database) -> Result<LovelyStatus, AppError> {
+    let path = lovely_path(&active_game_dir(db)?)?;
+    status(&path, db.get_lovely_install(&path)?.as_ref())
+}
+
+/// Installs lovely `tag`, or the latest release with `None`, for the active
+/// installation and records it. This is the only way an installed lovely
+/// gets updated.
+pub async fn install_release(
+    db: &Mutex<Database>,
+    tag: Option<&str>,
+    job: &DownloadJob,
+) -> Result<LovelyInstall, AppError> {
+    let path = lovely_path(&active_game_dir(&*db.lock()?)?)?;
+    let release = get_release(tag).await?;
+    let installed = install(&path, &release, job).await?;
+    db.lock()?.set_lovely_install(&installed)?;
+    Ok(installed)
+}
+
+fn active_game_dir(db: &Database) -> Result<PathBuf, AppError> {
+    db.get_installation_path()?
+        .map(PathBuf::from)
+        .ok_or_else(|| AppError::InvalidState("No installation path set".to_string()))
+}
+
+/// Makes sure lovely is at `path` before a launch. An existing lovely is
+/// left alone, so it only changes through [`install`]; if it doesn't match
+/// `recorded` a warning is logged. A missing one is installed at the
+/// recorded version, or the latest release if there's none, and returned so
+/// the caller can record it.
+pub async fn ensure_installed(
+    path: &Path,
+    recorded: Option<&LovelyInstall>,
+    job: &DownloadJob,
+) -> Result<Option<LovelyInstall>, AppError> {
+    match status(path, recorded)? {
+        LovelyStatus::Missing => {}
+        LovelyStatus::Modified { install, sha256 } => {
+            log::warn!(
+                "{} isn't the lovely {} bromomethane installed (SHA-256 {sha256}, expected {})",
+                path.display(),
+                install.version,
+                install.sha256
+            );
+            return Ok(None);
         }
-        Err(e) => {
-            if !(dll_path.exists()
-                && dll_path.is_file()
-                && dll_path.metadata().map(|m| m.len() > 0).unwrap_or(false))
-            {
-                return Err(e);
+        LovelyStatus::Untracked { .. } | LovelyStatus::Verified(_) => return Ok(None),
+    }
+
+    let release = get_release(recorded.map(|install| install.version.as_str())).await?;
+    install(path, &release, job)
+        .await
+        .inspect_err(|e| {
+            log::error!(
+                "Failed to install lovely to {}: {e}. Install it manually to play modded.",
+                path.display()
+            );
+        })
+        .map(Some)
+}
+
+fn file_sha256(path: &Path) -> Result<String, AppError> {
+    std::fs::read(path)
+        .map(|contents| checksum::sha256_hex(&contents))
+        .map_err(|e| AppError::FileRead {
+            path: path.to_path_buf(),
+            source: e.to_string(),
+        })
+}
+
+/// Reads the lovely library out of the release archive downloaded from
+/// `url`: `version.dll` from the Windows zip, `liblovely.dylib` from the
+/// macOS tarball.
+fn extract_library(archive: &Path, url: &str) -> Result<Vec<u8>, AppError> {
+    let read_error = |e: &dyn std::fmt::Display| AppError::FileRead {
+        path: archive.to_path_buf(),
+        source: e.to_string(),
+    };
+    let file = std::fs::File::open(archive).map_err(|e| read_error(&e))?;
+    let mut library = Vec::new();
+
+    if Path::new(url)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+    {
+        let mut zip = zip::ZipArchive::new(file).map_err(|e| read_error(&e))?;
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i).map_err(|e| read_error(&e))?;
+            if entry.name().ends_with("version.dll") {
+                entry
+                    .read_to_end(&mut library)
+                    .map_err(|e| read_error(&e))?;
+                return Ok(library);
             }
-            log::warn!("reusing old `version.dll`",);
         }
+        return Err(AppError::InvalidState(
+            "version.dll not found in downloaded zip".to_string(),
+        ));
+    }
+
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    for entry in tar.entries().map_err(|e| read_error(&e))? {
+        let mut entry = entry.map_err(|e| read_error(&e))?;
+        let is_library = entry.path().is_ok_and(|path| {
+            path.file_name()
+                .is_some_and(|name| name == "liblovely.dylib")
+        });
+        if is_library {
+            entry
+                .read_to_end(&mut library)
+                .map_err(|e| read_error(&e))?;
+            return Ok(library);
+        }
+    }
+    Err(AppError::InvalidState(
+        "liblovely.dylib not found in downloaded archive".to_string(),
+    ))
+}
+
+/// Refuses a library that isn't a Windows DLL or a Mach-O dylib, whichever
+/// this platform loads.
+fn check_library(library: &[u8], tag: &str) -> Result<(), AppError> {
+    #[cfg(target_os = "macos")]
+    let valid = [
+        [0xcf, 0xfa, 0xed, 0xfe], // 64-bit Mach-O
+        [0xca, 0xfe, 0xba, 0xbe], // universal binary
+    ]
+    .iter()
+    .any(|magic| library.starts_with(magic));
+    #[cfg(not(target_os = "macos"))]
+    let valid = library.starts_with(b"MZ");
+
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::InvalidState(format!(
+            "The lovely {tag} download doesn't contain a usable library"
+        )))
+    }
+}
+
+/// Writes `library` next to `path` and renames it into place, so a failed
+/// write leaves the previous lovely untouched.
+fn replace(path: &Path, library: &[u8]) -> Result<(), AppError> {
+    use std::io::Write;
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir).map_err(|e| AppError::DirCreate {
+        path: dir.to_path_buf(),
+        source: e.to_string(),
+    })?;
+    let write_error = |e: std::io::Error| AppError::FileWrite {
+        path: path.to_path_buf(),
+        source: e.to_string(),
+    };
+    let mut temp = tempfile::NamedTempFile::new_in(dir).map_err(write_error)?;
+    temp.write_all(library).map_err(write_error)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(temp.path(), std::fs::Permissions::from_mode(0o755))
+            .map_err(write_error)?;
     }
 
-    Ok(Some(dll_path))
+    temp.persist(path).map_err(|e| write_error(e.error))?;
+    Ok(())
 }
 
 #[cfg(target_os = "macos")]
@@ -84,231 +463,3 @@ fn detect_architecture() -> Result<&'static str, AppError> {
         other => Err(AppError::UnsupportedArchitecture(other.into())),
     }
 }
-
-pub async fn ensure_lovely_exists() -> Result<PathBuf, AppError> {
-    #[cfg(target_os = "macos")]
-    {
-        use std::fs;
-
-        let config_dir = dirs::config_dir()
-            .ok_or_else(|| AppError::DirNotFound(PathBuf::from("config directory")))?;
-
-        let bins_dir = config_dir.join("Balatro/bins");
-        fs::create_dir_all(&bins_dir).map_err(|e| AppError::DirCreate {
-            path: bins_dir.clone(),
-            source: e.to_string(),
-        })?;
-
-        let lovely_path = bins_dir.join("liblovely.dylib");
-
-        if !lovely_path.exists() {
-            download_and_install_lovely(&lovely_path).await?;
-        }
-
-        Ok(lovely_path)
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        let balatro_paths = crate::finder::get_balatro_paths();
-        if balatro_paths.is_empty() {
-            return Err(AppError::DirNotFound(PathBuf::from("Balatro installation")));
-        }
-
-        // Ensure version.dll exists in the game directory
-        let game_path = &balatro_paths[0];
-        ensure_version_dll_exists(game_path).await?;
-
-        Ok(game_path.join("Balatro.exe"))
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        let balatro_paths = crate::finder::get_balatro_paths();
-        if balatro_paths.is_empty() {
-            return Err(AppError::DirNotFound(PathBuf::from("Balatro installation")));
-        }
-
-        // Ensure version.dll exists in the game directory
-        let game_path = &balatro_paths[0];
-        ensure_version_dll_exists(game_path).await?;
-
-        // For Linux/Proton, we return the path to version.dll
-        Ok(game_path.join("version.dll"))
-    }
-
-    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
-    {
-        Err(AppError::InvalidState(
-            "Lovely injection is not supported on this platform.".into(),
-        ))
-    }
-}
-
-#[cfg(target_os = "macos")]
-async fn download_and_install_lovely(target_path: &Path) -> Result<(), AppError> {
-    use std::{fs, os::unix::fs::PermissionsExt};
-
-    let temp_dir = tempfile::tempdir().map_err(|e| AppError::FileWrite {
-        path: PathBuf::from("temp directory"),
-        source: e.to_string(),
-    })?;
-
-    let arch = detect_architecture()?;
-    let url = format!(
-        "https://github.com/ethangreen-dev/lovely-injector/releases/latest/download/\
-    lovely-{}-apple-darwin.tar.gz",
-        arch
-    );
-
-    // Download latest release
-    let client = reqwest::Client::new();
-    let response = client
-        .get(url)
-        .send()
-        .await
-        .map_err(|e| AppError::Network(e.to_string()))?;
-
-    // Save to temp file
-    let temp_tar_gz = temp_dir.path().join("lovely.tar.gz");
-    let mut file = File::create(&temp_tar_gz).map_err(|e| AppError::FileWrite {
-        path: temp_tar_gz.clone(),
-        source: e.to_string(),
-    })?;
-
-    let bytes = response
-        .bytes()
-        .await
-        .map_err(|e| AppError::Network(e.to_string()))?;
-    std::io::copy(&mut bytes.as_ref(), &mut file).map_err(|e| AppError::FileWrite {
-        path: temp_tar_gz.clone(),
-        source: e.to_string(),
-    })?;
-
-    // Extract and install
-    let tar_gz = File::open(&temp_tar_gz).map_err(|e| AppError::FileRead {
-        path: temp_tar_gz.clone(),
-        source: e.to_string(),
-    })?;
-    let tar = flate2::read::GzDecoder::new(tar_gz);
-    let mut archive = tar::Archive::new(tar);
-
-    archive.unpack(&temp_dir).map_err(|e| AppError::FileRead {
-        path: temp_tar_gz.clone(),
-        source: e.to_string(),
-    })?;
-
-    // Find the library in extracted files
-    let extracted_lib = temp_dir.path().join("liblovely.dylib");
-    fs::copy(&extracted_lib, target_path).map_err(|e| AppError::FileCopy {
-        source: extracted_lib.display().to_string(),
-        dest: target_path.display().to_string(),
-        source_error: e.to_string(),
-    })?;
-
-    // Set permissions
-    std::fs::set_permissions(target_path, std::fs::Permissions::from_mode(0o755))?;
-
-    Ok(())
-}
-
-#[cfg(any(target_os = "windows", target_os = "linux"))]
-async fn download_version_dll(dll_path: &std::path::Path) -> Result<(), AppError> {
-    const URL: &str = "https://github.com/ethangreen-dev/lovely-injector/releases/latest/download/lovely-x86_64-pc-windows-msvc.zip";
-
-    let temp_dir = tempfile::tempdir().map_err(|e| AppError::FileWrite {
-        path: PathBuf::from("temp directory"),
-        source: e.to_string(),
-    })?;
-
-    #[cfg(target_os = "windows")]
-    log::info!("Downloading lovely injector for Windows from {URL}");
-
-    #[cfg(target_os = "linux")]
-    log::info!("Downloading lovely injector for Linux/Proton from {URL}");
-
-    // Download the ZIP file
-    let client = reqwest::Client::new();
-    let response = client
-        .get(URL)
-        .send()
-        .await
-        .map_err(|e| AppError::Network(format!("Failed to download lovely injector: {e}")))?;
-
-    // Save to temp zip file
-    let temp_zip = temp_dir.path().join("lovely.zip");
-    let mut file = File::create(&temp_zip).map_err(|e| AppError::FileWrite {
-        path: temp_zip.clone(),
-        source: e.to_string(),
-    })?;
-
-    let bytes = response
-        .bytes()
-        .await
-        .map_err(|e| AppError::Network(format!("Failed to read download response: {e}")))?;
-
-    std::io::copy(&mut bytes.as_ref(), &mut file).map_err(|e| AppError::FileWrite {
-        path: temp_zip.clone(),
-        source: e.to_string(),
-    })?;
-
-    // Extract the ZIP file
-    let zip_file = File::open(&temp_zip).map_err(|e| AppError::FileRead {
-        path: temp_zip.clone(),
-        source: e.to_string(),
-    })?;
-
-    let mut archive = zip::ZipArchive::new(zip_file).map_err(|e| AppError::FileRead {
-        path: temp_zip.clone(),
-        source: e.to_string(),
-    })?;
-
-    // Find and extract version.dll from the ZIP
-    for i in 0..archive.len() {
-        let mut file = match archive.by_index(i) {
-            Ok(file) => file,
-            Err(e) => {
-                log::warn!("Failed to access zip entry: {e}");
-                continue;
-            }
-        };
-
-        let entry_name = file.name().to_string();
-
-        if entry_name.ends_with("version.dll") {
-            log::info!("Found version.dll in zip archive");
-
-            let mut outfile = match File::create(dll_path) {
-                Ok(f) => f,
-                Err(e) => {
-                    #[cfg(target_os = "linux")]
-                    if let Some(errno) = e.raw_os_error() {
-                        if errno == /* (30) - Read-only filesystem */ libc::EROFS
-                            || errno == /* (13) - Permission denied */ libc::EACCES
-                        {
-                            log::error!(
-                                "Failed to create file '{}', path not writable ({}). This error will be ignored and you have to manually install lovely instead.",
-                                dll_path.display(),
-                                errno,
-                            );
-                            return Ok(());
-                        }
-                    }
-                    return Err(AppError::FileWrite {
-                        path: dll_path.to_path_buf(),
-                        source: e.to_string(),
-                    })
-                    .inspect_err(|e| {
-                        log::error!("Failed to create file '{}': {}", dll_path.display(), e);
-                    });
-                }
-            };
-            std::io::copy(&mut file, &mut outfile)?;
-            return Ok(());
-        }
-    }
-
-    Err(AppError::InvalidState(
-        "version.dll not found in downloaded zip".to_string(),
-    ))
-}
@@ -0,0 +1,363 @@
+use crate::errors::AppError;
+use crate::installer;
+use crate::local_mod_detection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A lovely patch file: `lovely.toml` in a mod's root, or any `.toml` file
+/// directly in its `lovely` directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchManifest {
+    pub path: PathBuf,
+    /// Manifests with a higher priority are applied later.
+    pub priority: i32,
+    /// Values for `{{lovely:name}}` placeholders in payloads.
+    pub vars: HashMap<String, String>,
+    pub patches: Vec<Patch>,
+}
+
+/// One `[[patches]]` entry. `target` is the name lovely gives the Lua chunk,
+/// such as `game.lua` or `functions/common_events.lua`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Patch {
+    Pattern(PatternPatch),
+    Regex(RegexPatch),
+    Copy(CopyPatch),
+    Module(ModulePatch),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Position {
+    Before,
+    After,
+    At,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyPosition {
+    Prepend,
+    Append,
+}
+
+/// Inserts `payload` before or after, or in place of, every line that
+/// matches `pattern`, a whole-line match where `*` and `?` are wildcards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatternPatch {
+    pub target: String,
+    pub pattern: String,
+    pub position: Position,
+    #[serde(default)]
+    pub payload: String,
+    /// Indent the payload like the matched line.
+    #[serde(default)]
+    pub match_indent: bool,
+    /// Apply to at most this many matches.
+    #[serde(default)]
+    pub times: Option<usize>,
+}
+
+/// Like [`PatternPatch`], but `pattern` is a regex over the whole file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegexPatch {
+    pub target: String,
+    pub pattern: String,
+    pub position: Position,
+    #[serde(default)]
+    pub payload: String,
+    /// The capture group the payload is positioned against, instead of the
+    /// whole match.
+    #[serde(default)]
+    pub root_capture: Option<String>,
+    /// Put in front of every payload line; `$indent` and other captures are
+    /// expanded.
+    #[serde(default)]
+    pub line_prepend: String,
+    #[serde(default)]
+    pub times: Option<usize>,
+}
+
+/// Adds the contents of `sources`, files in the mod, or `payload` to the
+/// start or end of `target`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CopyPatch {
+    pub target: String,
+    pub position: CopyPosition,
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub payload: Option<String>,
+}
+
+/// Makes `source`, a file in the mod, available to `require` as `name` from
+/// before `before` is loaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModulePatch {
+    pub source: String,
+    pub before: String,
+    pub name: String,
+}
+
+impl Patch {
+    /// The game file the patch changes.
+    #[must_use]
+    pub fn target(&self) -> &str {
+        match self {
+            Self::Pattern(patch) => &patch.target,
+            Self::Regex(patch) => &patch.target,
+            Self::Copy(patch) => &patch.target,
+            Self::Module(patch) => &patch.before,
+        }
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Pattern(_) => "pattern",
+            Self::Regex(_) => "regex",
+            Self::Copy(_) => "copy",
+            Self::Module(_) => "module",
+        }
+    }
+
+    /// The pattern of a pattern or regex patch.
+    #[must_use]
+    pub fn pattern(&self) -> Option<&str> {
+        match self {
+            Self::Pattern(patch) => Some(&patch.pattern),
+            Self::Regex(patch) => Some(&patch.pattern),
+            Self::Copy(_) | Self::Module(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ManifestFile {
+    #[serde(default)]
+    manifest: ManifestHeader,
+    #[serde(default)]
+    vars: HashMap<String, String>,
+    #[serde(default)]
+    patches: Vec<Patch>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ManifestHeader {
+    #[serde(default)]
+    priority: i32,
+}
+
+/// Reads and parses the patch file at `path`.
+pub fn read_manifest(path: &Path) -> Result<PatchManifest, AppError> {
+    let text = fs::read_to_string(path).map_err(|e| AppError::FileRead {
+        path: path.to_path_buf(),
+        source: e.to_string(),
+    })?;
+    let file: ManifestFile = toml::from_str(&text).map_err(|e| AppError::Serialization {
+        format: "TOML".to_string(),
+        source: format!("{}: {e}", path.display()),
+    })?;
+    Ok(PatchManifest {
+        path: path.to_path_buf(),
+        priority: file.manifest.priority,
+        vars: file.vars,
+        patches: file.patches,
+    })
+}
+
+/// The patch files lovely loads for the mod at `mod_dir`, in the order it
+/// loads them.
+#[must_use]
+pub fn manifest_paths(mod_dir: &Path) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let root = mod_dir.join("lovely.toml");
+    if root.is_file() {
+        paths.push(root);
+    }
+    if let Ok(entries) = fs::read_dir(mod_dir.join("lovely")) {
+        let mut files = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
+            .collect::<Vec<_>>();
+        files.sort();
+        paths.extend(files);
+    }
+    paths
+}
+
+/// The lovely patches of one mod in the Mods directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModPatches {
+    pub name: String,
+    pub path: PathBuf,
+    pub enabled: bool,
+    pub manifests: Vec<PatchManifest>,
+    /// The game files the mod patches, sorted.
+    pub targets: Vec<String>,
+    /// Patch files that couldn't be read or parsed.
+    pub errors: Vec<String>,
+}
+
+impl ModPatches {
+    /// Reads every patch file of the mod at `mod_dir`. Files that fail to
+    /// parse are listed in `errors` rather than failing the whole mod.
+    #[must_use]
+    pub fn read(mod_dir: &Path) -> Self {
+        let mut manifests = Vec::new();
+        let mut errors = Vec::new();
+        for path in manifest_paths(mod_dir) {
+            match read_manifest(&path) {
+                Ok(manifest) => manifests.push(manifest),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        let mut targets = manifests
+            .iter()
+            .flat_map(|manifest| {
+                manifest
+                    .patches
+                    .iter()
+                    .map(|patch| patch.target().to_string())
+            })
+            .collect::<Vec<_>>();
+        targets.sort();
+        targets.dedup();
+
+        Self {
+            name: local_mod_detection::detect_mod(mod_dir).map_or_else(
+                || {
+                    mod_dir
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                },
+                |detected| detected.name,
+            ),
+            path: mod_dir.to_path_buf(),
+            enabled: installer::is_mod_enabled(mod_dir),
+            manifests,
+            targets,
+            errors,
+        }
+    }
+
+    pub fn patches(&self) -> impl Iterator<Item = &Patch> {
+        self.manifests.iter().flat_map(|manifest| &manifest.patches)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Several enabled mods patch the same file. Usually harmless, but the
+    /// first place to look when that file misbehaves.
+    SharedTarget,
+    /// Several enabled mods patch the same file at the same pattern, so the
+    /// order they're applied in decides what the game ends up with, and a
+    /// patch at `at` position can remove the line the others look for.
+    SamePattern,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchConflict {
+    pub kind: ConflictKind,
+    pub target: String,
+    /// The shared pattern for [`ConflictKind::SamePattern`].
+    pub pattern: Option<String>,
+    pub mods: Vec<String>,
+}
+
+/// The lovely patches of every mod in a Mods directory and where the
+/// enabled ones overlap.
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchReport {
+    pub mods: Vec<ModPatches>,
+    /// Identical patterns first, then shared targets.
+    pub conflicts: Vec<PatchConflict>,
+}
+
+/// Reads the patches of every mod in `mods_dir`. Mods without patch files
+/// are left out.
+pub fn analyse(mods_dir: &Path) -> Result<PatchReport, AppError> {
+    let entries = match fs::read_dir(mods_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(PatchReport {
+                mods: Vec::new(),
+                conflicts: Vec::new(),
+            });
+        }
+        Err(e) => {
+            return Err(AppError::FileRead {
+                path: mods_dir.to_path_buf(),
+                source: e.to_string(),
+            });
+        }
+    };
+    let mut dirs = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    dirs.sort();
+
+    let mods = dirs
+        .iter()
+        .map(|dir| ModPatches::read(dir))
+        .filter(|patches| !patches.manifests.is_empty() || !patches.errors.is_empty())
+        .collect::<Vec<_>>();
+    let conflicts = find_conflicts(&mods);
+    Ok(PatchReport { mods, conflicts })
+}
+
+/// Where the patches of the enabled mods in `mods` overlap.
+#[must_use]
+pub fn find_conflicts(mods: &[ModPatches]) -> Vec<PatchConflict> {
+    let mut by_target: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut by_pattern: BTreeMap<(&str, &str), Vec<&str>> = BTreeMap::new();
+    for patches in mods.iter().filter(|patches| patches.enabled) {
+        for patch in patches.patches() {
+            push_unique(by_target.entry(patch.target()).or_default(), &patches.name);
+            if let Some(pattern) = patch.pattern() {
+                push_unique(
+                    by_pattern
+                        .entry((patch.target(), pattern.trim()))
+                        .or_default(),
+                    &patches.name,
+                );
+            }
+        }
+    }
+
+    let same_pattern = by_pattern
+        .into_iter()
+        .filter(|(_, mods)| mods.len() > 1)
+        .map(|((target, pattern), mods)| PatchConflict {
+            kind: ConflictKind::SamePattern,
+            target: target.to_string(),
+            pattern: Some(pattern.to_string()),
+            mods: mods.into_iter().map(str::to_string).collect(),
+        });
+    let shared_target = by_target
+        .into_iter()
+        .filter(|(_, mods)| mods.len() > 1)
+        .map(|(target, mods)| PatchConflict {
+            kind: ConflictKind::SharedTarget,
+            target: target.to_string(),
+            pattern: None,
+            mods: mods.into_iter().map(str::to_string).collect(),
+        });
+    same_pattern.chain(shared_target).collect()
+}
+
+fn push_unique<'a>(names: &mut Vec<&'a str>, name: &'a str) {
+    if !names.contains(&name) {
+        names.push(name);
+    }
+}
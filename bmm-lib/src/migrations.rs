@@ -0,0 +1,394 @@
+use crate::errors::AppError;
+use rusqlite::{Connection, OptionalExtension, Transaction};
+use std::path::Path;
+
+/// A numbered change to the database schema. Steps run in order, each in its
+/// own transaction together with bumping `schema_version`, so an interrupted
+/// upgrade resumes from the last step that completed.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    apply: fn(&Transaction<'_>) -> rusqlite::Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "settings and installed mods",
+        apply: base_schema,
+    },
+    Migration {
+        version: 2,
+        description: "mod collections",
+        apply: mod_collections,
+    },
+    Migration {
+        version: 3,
+        description: "mod sources and file manifests",
+        apply: mod_sources,
+    },
+    Migration {
+        version: 4,
+        description: "version history",
+        apply: version_history,
+    },
+    Migration {
+        version: 5,
+        description: "version pins",
+        apply: version_pins,
+    },
+    Migration {
+        version: 6,
+        description: "operation log",
+        apply: operations,
+    },
+    Migration {
+        version: 7,
+        description: "installations",
+        apply: installations,
+    },
+    Migration {
+        version: 8,
+        description: "lovely installs",
+        apply: lovely_installs,
+    },
+    Migration {
+        version: 9,
+        description: "game versions",
+        apply: game_versions,
+    },
+];
+
+/// The schema version this build of bromomethane creates and understands.
+#[allow(clippy::cast_possible_truncation)]
+pub const LATEST_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// What releases before numbered migrations stored as `db_version`. They
+/// rebuild any database with a different `db_version` from scratch, dropping
+/// every table they don't know about, so it's kept at this value.
+const LEGACY_DB_VERSION: &str = "1.1";
+
+/// The schema version of the database behind `conn`. Databases that predate
+/// numbered migrations count as version 1 if they carry the last legacy
+/// `db_version`, and as version 0 otherwise, which every step copes with.
+pub fn schema_version(conn: &Connection) -> Result<u32, AppError> {
+    if !table_exists(conn, "settings")? {
+        return Ok(0);
+    }
+
+    let setting = |name: &str| {
+        conn.query_row(
+            "SELECT value FROM settings WHERE setting = ?1",
+            [name],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+    };
+    if let Some(version) = setting("schema_version")? {
+        return version.parse().map_err(|_| {
+            AppError::DatabaseInit(format!("Unrecognised schema version '{version}'"))
+        });
+    }
+    Ok(match setting("db_version")?.as_deref() {
+        Some(LEGACY_DB_VERSION) => 1,
+        _ => 0,
+    })
+}
+
+/// Brings the database at `db_path` up to [`LATEST_VERSION`]. An existing
+/// database is copied next to itself before it's changed, and one created by
+/// a newer bromomethane is refused rather than risk misreading it.
+pub fn migrate(conn: &mut Connection, db_path: &Path) -> Result<(), AppError> {
+    let version = schema_version(conn)?;
+    if version > LATEST_VERSION {
+        return Err(AppError::DatabaseInit(format!(
+            "{} was created by a newer version of bromomethane (schema version {version}, this \
+             version understands up to {LATEST_VERSION}). Update bromomethane to open it.",
+            db_path.display()
+        )));
+    }
+    if version == LATEST_VERSION {
+        return Ok(());
+    }
+
+    if has_tables(conn)? {
+        let backup_path = backup_path(db_path, version);
+        std::fs::copy(db_path, &backup_path).map_err(|e| AppError::FileCopy {
+            source: db_path.display().to_string(),
+            dest: backup_path.display().to_string(),
+            source_error: e.to_string(),
+        })?;
+        log::info!(
+            "Backed up the database to {} before upgrading it",
+            backup_path.display()
+        );
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        log::info!(
+            "Migrating the database to version {}: {}",
+            migration.version,
+            migration.description
+        );
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx).map_err(|e| {
+            AppError::DatabaseInit(format!(
+                "Migration {} ({}) failed: {e}",
+                migration.version, migration.description
+            ))
+        })?;
+        tx.execute(
+            "INSERT OR REPLACE INTO settings (setting, value) VALUES ('schema_version', ?1)",
+            [migration.version.to_string()],
+        )?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Where the copy of a database at `version` is kept, e.g.
+/// `bmm_storage.v1.db.bak`.
+#[must_use]
+pub fn backup_path(db_path: &Path, version: u32) -> std::path::PathBuf {
+    db_path.with_extension(format!("v{version}.db.bak"))
+}
+
+fn table_exists(conn: &Connection, name: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+}
+
+fn has_tables(conn: &Connection) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'",
+        [],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+}
+
+fn column_exists(tx: &Transaction<'_>, table: &str, column: &str) -> rusqlite::Result<bool> {
+    tx.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+}
+
+/// The tables every release has had. Early databases lack the `settings`
+/// table and some `installed_mods` columns, so those are added as needed.
+fn base_schema(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS settings (
+            setting TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS installed_mods (
+            name TEXT PRIMARY KEY,
+            path TEXT NOT NULL,
+            dependencies TEXT NOT NULL DEFAULT '[]',
+            current_version TEXT
+        );",
+    )?;
+    if !column_exists(tx, "installed_mods", "dependencies")? {
+        tx.execute(
+            "ALTER TABLE installed_mods ADD COLUMN dependencies TEXT NOT NULL DEFAULT '[]'",
+            [],
+        )?;
+    }
+    if !column_exists(tx, "installed_mods", "current_version")? {
+        tx.execute(
+            "ALTER TABLE installed_mods ADD COLUMN current_version TEXT",
+            [],
+        )?;
+    }
+    tx.execute(
+        "INSERT OR IGNORE INTO settings (setting, value) VALUES ('security_warning_acknowledged', 'no')",
+        [],
+    )?;
+    tx.execute(
+        "INSERT OR REPLACE INTO settings (setting, value) VALUES ('db_version', ?1)",
+        [LEGACY_DB_VERSION],
+    )?;
+    Ok(())
+}
+
+fn mod_collections(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS mod_collections (
+            name TEXT PRIMARY KEY,
+            enabled_mods TEXT NOT NULL DEFAULT '[]'
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn mod_sources(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS mod_sources (
+            path TEXT PRIMARY KEY,
+            download_url TEXT NOT NULL,
+            archive_hash TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS mod_files (
+            mod_path TEXT NOT NULL,
+            path TEXT NOT NULL,
+            sha256 TEXT NOT NULL,
+            PRIMARY KEY (mod_path, path)
+        );",
+    )
+}
+
+fn version_history(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS mod_versions (
+            mod_name TEXT NOT NULL,
+            version TEXT NOT NULL,
+            download_url TEXT NOT NULL,
+            archive_hash TEXT NOT NULL,
+            dependencies TEXT NOT NULL DEFAULT '[]',
+            installed_at INTEGER NOT NULL,
+            PRIMARY KEY (mod_name, archive_hash)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn version_pins(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS mod_pins (
+            mod_name TEXT PRIMARY KEY COLLATE NOCASE,
+            version_constraint TEXT NOT NULL,
+            reason TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn operations(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS operations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            mods TEXT NOT NULL DEFAULT '[]',
+            outcome TEXT NOT NULL,
+            detail TEXT,
+            error TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Moves the one installation path into an installation named `Default` and
+/// ties the installed mods, collections, version history, pins and the
+/// operation log to it. The tables keyed by mod or collection name are
+/// rebuilt, since the same name can now show up in several installations.
+fn installations(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS installations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            game_dir TEXT UNIQUE,
+            mods_dir TEXT,
+            active_collection TEXT
+        );
+        INSERT INTO installations (id, name, game_dir, active_collection)
+            VALUES (1, 'Default',
+                    (SELECT NULLIF(value, '') FROM settings WHERE setting = 'installation_path'),
+                    (SELECT value FROM settings WHERE setting = 'active_collection'));
+        INSERT OR REPLACE INTO settings (setting, value) VALUES ('active_installation', '1');
+        DELETE FROM settings WHERE setting IN ('installation_path', 'active_collection');
+
+        CREATE TABLE installed_mods_v7 (
+            installation_id INTEGER NOT NULL REFERENCES installations (id),
+            name TEXT NOT NULL,
+            path TEXT NOT NULL,
+            dependencies TEXT NOT NULL DEFAULT '[]',
+            current_version TEXT,
+            PRIMARY KEY (installation_id, name)
+        );
+        INSERT INTO installed_mods_v7 (installation_id, name, path, dependencies, current_version)
+            SELECT 1, name, path, dependencies, current_version FROM installed_mods;
+        DROP TABLE installed_mods;
+        ALTER TABLE installed_mods_v7 RENAME TO installed_mods;
+
+        CREATE TABLE mod_collections_v7 (
+            installation_id INTEGER NOT NULL REFERENCES installations (id),
+            name TEXT NOT NULL,
+            enabled_mods TEXT NOT NULL DEFAULT '[]',
+            PRIMARY KEY (installation_id, name)
+        );
+        INSERT INTO mod_collections_v7 (installation_id, name, enabled_mods)
+            SELECT 1, name, enabled_mods FROM mod_collections;
+        DROP TABLE mod_collections;
+        ALTER TABLE mod_collections_v7 RENAME TO mod_collections;
+
+        CREATE TABLE mod_versions_v7 (
+            installation_id INTEGER NOT NULL REFERENCES installations (id),
+            mod_name TEXT NOT NULL,
+            version TEXT NOT NULL,
+            download_url TEXT NOT NULL,
+            archive_hash TEXT NOT NULL,
+            dependencies TEXT NOT NULL DEFAULT '[]',
+            installed_at INTEGER NOT NULL,
+            PRIMARY KEY (installation_id, mod_name, archive_hash)
+        );
+        INSERT INTO mod_versions_v7
+            (installation_id, mod_name, version, download_url, archive_hash, dependencies, installed_at)
+            SELECT 1, mod_name, version, download_url, archive_hash, dependencies, installed_at
+            FROM mod_versions ORDER BY rowid;
+        DROP TABLE mod_versions;
+        ALTER TABLE mod_versions_v7 RENAME TO mod_versions;
+
+        CREATE TABLE mod_pins_v7 (
+            installation_id INTEGER NOT NULL REFERENCES installations (id),
+            mod_name TEXT NOT NULL COLLATE NOCASE,
+            version_constraint TEXT NOT NULL,
+            reason TEXT,
+            PRIMARY KEY (installation_id, mod_name)
+        );
+        INSERT INTO mod_pins_v7 (installation_id, mod_name, version_constraint, reason)
+            SELECT 1, mod_name, version_constraint, reason FROM mod_pins;
+        DROP TABLE mod_pins;
+        ALTER TABLE mod_pins_v7 RENAME TO mod_pins;
+
+        ALTER TABLE operations ADD COLUMN installation_id INTEGER NOT NULL DEFAULT 1;",
+    )
+}
+
+fn lovely_installs(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS lovely_installs (
+            path TEXT PRIMARY KEY,
+            version TEXT NOT NULL,
+            sha256 TEXT NOT NULL,
+            installed_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn game_versions(tx: &Transaction<'_>) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS game_versions (
+            installation_id INTEGER PRIMARY KEY REFERENCES installations (id),
+            version TEXT,
+            build_id TEXT,
+            branch TEXT,
+            detected_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
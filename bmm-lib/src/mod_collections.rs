@@ -1,133 +1,323 @@
-use rusqlite::{Connection, Result};
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
+use crate::conflicts;
+use crate::database::Database;
+use crate::errors::AppError;
+use crate::game_version;
+use crate::installations::InstallationManager;
+use crate::installer;
+use crate::operations::{self, AffectedMod, OperationKind};
+use rayon::prelude::*;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone)]
+/// A named set of enabled mods, a.k.a. a profile. Mods are identified by the
+/// name of their directory in the Mods dir, so manually installed mods can be
+/// part of a collection too.
+#[derive(Debug, Clone, Serialize)]
 pub struct ModCollection {
     pub name: String,
-    pub path: PathBuf,
-    pub hash: u64,
-}
-
-impl ModCollection {
-    #[must_use]
-    pub fn new(name: String, path: PathBuf) -> Self {
-        use std::hash::DefaultHasher;
-        let mut hasher = DefaultHasher::new();
-        name.hash(&mut hasher);
-        path.to_string_lossy().hash(&mut hasher);
-        let hash = hasher.finish();
-
-        Self { name, path, hash }
-    }
+    pub enabled_mods: Vec<String>,
+    pub active: bool,
 }
 
+/// The collections of the active installation, borrowed from
+/// [`Database::collections`](crate::database::Database::collections).
 #[derive(Debug, Clone)]
-pub struct ModCollectionManager {
-    collections: HashMap<u64, ModCollection>,
+pub struct ModCollectionManager<'db> {
+    conn: &'db Connection,
 }
 
-impl Default for ModCollectionManager {
-    fn default() -> Self {
-        Self::new()
+impl<'db> ModCollectionManager<'db> {
+    pub(crate) fn new(conn: &'db Connection) -> Self {
+        Self { conn }
     }
-}
 
-impl ModCollectionManager {
-    #[must_use]
-    pub fn new() -> Self {
-        Self {
-            collections: HashMap::new(),
+    fn installation_id(&self) -> Result<i64, AppError> {
+        InstallationManager::new(self.conn).active_id()
+    }
+
+    pub fn list(&self) -> Result<Vec<ModCollection>, AppError> {
+        let active = self.active()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT name, enabled_mods FROM mod_collections WHERE installation_id = ?1
+             ORDER BY name",
+        )?;
+        let mut rows = stmt.query([self.installation_id()?])?;
+
+        let mut collections = Vec::new();
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            collections.push(ModCollection {
+                active: active.as_deref() == Some(name.as_str()),
+                enabled_mods: serde_json::from_str(&row.get::<_, String>(1)?)?,
+                name,
+            });
         }
+        Ok(collections)
+    }
+
+    pub fn get(&self, name: &str) -> Result<ModCollection, AppError> {
+        let enabled_mods = self
+            .conn
+            .query_row(
+                "SELECT enabled_mods FROM mod_collections WHERE installation_id = ?1 AND name = ?2",
+                rusqlite::params![self.installation_id()?, name],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .ok_or_else(|| not_found(name))?;
+
+        Ok(ModCollection {
+            name: name.to_string(),
+            enabled_mods: serde_json::from_str(&enabled_mods)?,
+            active: self.active()?.as_deref() == Some(name),
+        })
+    }
+
+    /// The name of the collection that was switched to last, if it still exists.
+    pub fn active(&self) -> Result<Option<String>, AppError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT active_collection FROM installations WHERE id = ?1",
+                [self.installation_id()?],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten())
     }
 
-    pub fn initialize_table(conn: &Connection) -> Result<()> {
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS mod_collections (
-                hash INTEGER PRIMARY KEY,
-                name TEXT NOT NULL,
-                path TEXT NOT NULL
-            )",
-            [],
+    pub fn create(&self, name: &str, enabled_mods: &[String]) -> Result<(), AppError> {
+        let name = validate_name(name)?;
+        if self.exists(name)? {
+            return Err(AppError::InvalidState(format!(
+                "A mod collection named '{name}' already exists"
+            )));
+        }
+        self.conn.execute(
+            "INSERT INTO mod_collections (installation_id, name, enabled_mods) VALUES (?1, ?2, ?3)",
+            rusqlite::params![
+                self.installation_id()?,
+                name,
+                serde_json::to_string(enabled_mods)?
+            ],
         )?;
         Ok(())
     }
 
-    pub fn add_collection(&mut self, conn: &Connection, collection: &ModCollection) -> Result<()> {
-        self.collections.insert(collection.hash, collection.clone());
-
-        conn.execute(
-            "INSERT OR REPLACE INTO mod_collections (hash, name, path) VALUES (?1, ?2, ?3)",
-            [
-                &collection.hash.to_string(),
-                &collection.name,
-                &collection.path.to_string_lossy().to_string(),
+    pub fn set_enabled_mods(&self, name: &str, enabled_mods: &[String]) -> Result<(), AppError> {
+        let updated = self.conn.execute(
+            "UPDATE mod_collections SET enabled_mods = ?3 WHERE installation_id = ?1 AND name = ?2",
+            rusqlite::params![
+                self.installation_id()?,
+                name,
+                serde_json::to_string(enabled_mods)?
             ],
         )?;
+        if updated == 0 {
+            return Err(not_found(name));
+        }
+        Ok(())
+    }
 
+    pub fn rename(&self, name: &str, new_name: &str) -> Result<(), AppError> {
+        let new_name = validate_name(new_name)?;
+        if name != new_name && self.exists(new_name)? {
+            return Err(AppError::InvalidState(format!(
+                "A mod collection named '{new_name}' already exists"
+            )));
+        }
+
+        let installation_id = self.installation_id()?;
+        let tx = self.conn.unchecked_transaction()?;
+        let updated = tx.execute(
+            "UPDATE mod_collections SET name = ?3 WHERE installation_id = ?1 AND name = ?2",
+            rusqlite::params![installation_id, name, new_name],
+        )?;
+        if updated == 0 {
+            return Err(not_found(name));
+        }
+        tx.execute(
+            "UPDATE installations SET active_collection = ?3 WHERE id = ?1 AND active_collection = ?2",
+            rusqlite::params![installation_id, name, new_name],
+        )?;
+        tx.commit()?;
         Ok(())
     }
 
-    #[must_use]
-    pub fn get_collection(&self, hash: u64) -> Option<&ModCollection> {
-        self.collections.get(&hash)
+    pub fn clone_collection(&self, name: &str, new_name: &str) -> Result<(), AppError> {
+        let source = self.get(name)?;
+        self.create(new_name, &source.enabled_mods)
     }
 
-    pub fn remove_collection(&mut self, conn: &Connection, hash: u64) -> Result<()> {
-        self.collections.remove(&hash);
-        conn.execute(
-            "DELETE FROM mod_collections WHERE hash = ?1",
-            [hash.to_string()],
+    pub fn delete(&self, name: &str) -> Result<(), AppError> {
+        let installation_id = self.installation_id()?;
+        let tx = self.conn.unchecked_transaction()?;
+        let deleted = tx.execute(
+            "DELETE FROM mod_collections WHERE installation_id = ?1 AND name = ?2",
+            rusqlite::params![installation_id, name],
+        )?;
+        if deleted == 0 {
+            return Err(not_found(name));
+        }
+        tx.execute(
+            "UPDATE installations SET active_collection = NULL
+             WHERE id = ?1 AND active_collection = ?2",
+            rusqlite::params![installation_id, name],
         )?;
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn load_collections(&mut self, conn: &Connection) -> Result<Vec<ModCollection>> {
-        let mut stmt = conn.prepare("SELECT hash, name, path FROM mod_collections")?;
+    /// Enables exactly the mods of collection `name` in `mods_dir` and marks it
+    /// as the active one once every mod was toggled. Unless `force` is set,
+    /// it's refused like enabling a single mod is, when mods in the
+    /// collection conflict with each other or don't support the installed
+    /// game version. Returns the mods it toggled, see [`record_switch`].
+    pub fn switch(
+        &self,
+        name: &str,
+        mods_dir: &Path,
+        force: bool,
+    ) -> Result<Vec<Toggle>, AppError> {
+        let collection = self.get(name)?;
+        if !force {
+            conflicts::check_set(mods_dir, &collection.enabled_mods)?;
+            let game_dir = InstallationManager::new(self.conn).active()?.game_dir;
+            game_version::check_set(game_dir.as_ref(), mods_dir, &collection.enabled_mods)?;
+        }
+        let toggles = apply_enabled_mods(mods_dir, &collection.enabled_mods)?;
+        if toggles.iter().all(|toggle| toggle.result.is_ok()) {
+            self.conn.execute(
+                "UPDATE installations SET active_collection = ?2 WHERE id = ?1",
+                rusqlite::params![self.installation_id()?, name],
+            )?;
+        }
+        Ok(toggles)
+    }
 
-        let collections = stmt.query_map([], |row| {
-            let hash: u64 = row.get::<_, String>(0)?.parse().unwrap();
-            let name: String = row.get(1)?;
-            let path: String = row.get(2)?;
+    fn exists(&self, name: &str) -> Result<bool, AppError> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT 1 FROM mod_collections WHERE installation_id = ?1 AND name = ?2",
+                rusqlite::params![self.installation_id()?, name],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+}
 
-            Ok(ModCollection {
-                hash,
-                name,
-                path: PathBuf::from(path),
-            })
-        })?;
+fn validate_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidState(
+            "Mod collection names can't be empty".to_string(),
+        ));
+    }
+    Ok(name)
+}
 
-        let mut result = Vec::new();
-        for collection in collections {
-            let collection = collection?;
-            self.collections.insert(collection.hash, collection.clone());
-            result.push(collection);
+fn not_found(name: &str) -> AppError {
+    AppError::InvalidState(format!("No mod collection named '{name}'"))
+}
+
+/// Directories in the Mods dir that lovely would load as mods. lovely keeps its
+/// own logs and dumps in `Mods/lovely`, which isn't one.
+fn mod_dirs(mods_dir: &Path) -> Result<Vec<(String, PathBuf)>, AppError> {
+    let entries = match fs::read_dir(mods_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(AppError::FileRead {
+                path: mods_dir.to_path_buf(),
+                source: e.to_string(),
+            });
         }
+    };
 
-        Ok(result)
-    }
+    Ok(entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?.to_string();
+            (name != "lovely" && !name.starts_with('.')).then_some((name, path))
+        })
+        .collect())
+}
 
-    pub fn get_all_collections(&self, conn: &Connection) -> Result<Vec<ModCollection>> {
-        let mut stmt = conn.prepare("SELECT hash, name, path FROM mod_collections")?;
+/// The directory names of every currently enabled mod, sorted.
+pub fn enabled_mods(mods_dir: &Path) -> Result<Vec<String>, AppError> {
+    let mut enabled = mod_dirs(mods_dir)?
+        .into_iter()
+        .filter(|(_, path)| installer::is_mod_enabled(path))
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    enabled.sort();
+    Ok(enabled)
+}
 
-        let collections = stmt.query_map([], |row| {
-            let hash: u64 = row.get::<_, String>(0)?.parse().unwrap();
-            let name: String = row.get(1)?;
-            let path: String = row.get(2)?;
+/// A mod [`apply_enabled_mods`] enabled or disabled, or failed to.
+#[derive(Debug)]
+pub struct Toggle {
+    pub path: PathBuf,
+    pub enabled: bool,
+    pub result: Result<(), AppError>,
+}
 
-            Ok(ModCollection {
-                hash,
-                name,
-                path: PathBuf::from(path),
+/// Enables the mods named in `enabled_mods` and disables every other one,
+/// touching only the mods whose state actually changes. Every mod is
+/// attempted even if some fail, and each one's outcome is returned.
+pub fn apply_enabled_mods(
+    mods_dir: &Path,
+    enabled_mods: &[String],
+) -> Result<Vec<Toggle>, AppError> {
+    let toggles = mod_dirs(mods_dir)?
+        .into_par_iter()
+        .filter_map(|(name, path)| {
+            let enabled = enabled_mods.contains(&name);
+            (installer::is_mod_enabled(&path) != enabled).then(|| Toggle {
+                result: installer::set_mod_enabled(&path, enabled),
+                path,
+                enabled,
             })
-        })?;
+        })
+        .collect::<Vec<_>>();
 
-        let mut result = Vec::new();
-        for collection in collections {
-            result.push(collection?);
-        }
+    for missing in enabled_mods
+        .iter()
+        .filter(|name| !mods_dir.join(name).is_dir())
+    {
+        log::warn!("Mod {missing} from the collection isn't in the Mods directory anymore");
+    }
+
+    Ok(toggles)
+}
 
-        Ok(result)
+/// Records what switching to collection `name` enabled and disabled as one
+/// `Enable` and one `Disable` operation, with the collection's name as the
+/// detail, and returns the first mod that couldn't be toggled.
+pub fn record_switch(db: &Database, name: &str, toggles: Vec<Toggle>) -> Result<(), AppError> {
+    let (enabled, disabled): (Vec<_>, Vec<_>) = toggles.into_iter().partition(|t| t.enabled);
+    let mut first_error = None;
+    for (kind, toggles) in [
+        (OperationKind::Enable, enabled),
+        (OperationKind::Disable, disabled),
+    ] {
+        if toggles.is_empty() {
+            continue;
+        }
+        let mods = toggles
+            .iter()
+            .map(|toggle| AffectedMod::at_path(db, &toggle.path))
+            .collect::<Vec<_>>();
+        let result = toggles.into_iter().try_for_each(|toggle| toggle.result);
+        if let Err(e) = operations::record(db, kind, &mods, Some(name), result) {
+            first_error.get_or_insert(e);
+        }
     }
+    first_error.map_or(Ok(()), Err)
 }
@@ -0,0 +1,183 @@
+use crate::database::{Database, InstalledMod};
+use crate::errors::AppError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// How many operations a page holds when the caller doesn't say.
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+
+/// What was done to the installed mods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    Install,
+    Uninstall,
+    Enable,
+    Disable,
+    /// Installing a version of Steamodded or Talisman.
+    FrameworkInstall,
+    Update,
+    Rollback,
+    Repair,
+    /// Dropping tracked mods whose directory is gone.
+    Reindex,
+    /// Deleting directories in the Mods folder that no mod is tracked at.
+    Cleanup,
+}
+
+impl OperationKind {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Install => "install",
+            Self::Uninstall => "uninstall",
+            Self::Enable => "enable",
+            Self::Disable => "disable",
+            Self::FrameworkInstall => "framework_install",
+            Self::Update => "update",
+            Self::Rollback => "rollback",
+            Self::Repair => "repair",
+            Self::Reindex => "reindex",
+            Self::Cleanup => "cleanup",
+        }
+    }
+
+    pub(crate) fn parse(kind: &str) -> Result<Self, AppError> {
+        Ok(match kind {
+            "install" => Self::Install,
+            "uninstall" => Self::Uninstall,
+            "enable" => Self::Enable,
+            "disable" => Self::Disable,
+            "framework_install" => Self::FrameworkInstall,
+            "update" => Self::Update,
+            "rollback" => Self::Rollback,
+            "repair" => Self::Repair,
+            "reindex" => Self::Reindex,
+            "cleanup" => Self::Cleanup,
+            _ => {
+                return Err(AppError::InvalidState(format!(
+                    "Unknown operation kind '{kind}'"
+                )));
+            }
+        })
+    }
+}
+
+impl fmt::Display for OperationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Succeeded,
+    Failed,
+}
+
+/// A mod an operation touched, at the version it was installed at, or the
+/// one it was meant to be installed at for installs and updates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AffectedMod {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl AffectedMod {
+    #[must_use]
+    pub fn new(name: impl Into<String>, version: Option<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.filter(|v| !v.is_empty()),
+        }
+    }
+
+    /// The tracked mod installed at `path`, or the bare directory name for
+    /// mods that were installed by hand.
+    #[must_use]
+    pub fn at_path(db: &Database, path: &Path) -> Self {
+        db.get_installed_mods()
+            .ok()
+            .and_then(|mods| {
+                mods.iter()
+                    .find(|m| Path::new(&m.path) == path)
+                    .map(Self::from)
+            })
+            .unwrap_or_else(|| {
+                let name = path.file_name().map_or_else(
+                    || path.display().to_string(),
+                    |n| n.to_string_lossy().into_owned(),
+                );
+                Self::new(name, None)
+            })
+    }
+}
+
+impl From<&InstalledMod> for AffectedMod {
+    fn from(installed: &InstalledMod) -> Self {
+        Self::new(installed.name.clone(), installed.current_version.clone())
+    }
+}
+
+/// An entry in the operation log.
+#[derive(Debug, Clone, Serialize)]
+pub struct Operation {
+    pub id: i64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub kind: OperationKind,
+    pub mods: Vec<AffectedMod>,
+    pub outcome: Outcome,
+    /// Context that doesn't fit the other fields, such as the path that was
+    /// cleaned up.
+    pub detail: Option<String>,
+    /// Why the operation failed.
+    pub error: Option<String>,
+}
+
+/// One page of the operation log, newest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationsPage {
+    pub operations: Vec<Operation>,
+    /// How many operations match in total, across all pages.
+    pub total: u64,
+    /// Starts at 1.
+    pub page: u32,
+    pub per_page: u32,
+}
+
+/// Appends `kind` to the operation log with the outcome of `result`, and
+/// hands `result` back. Failing to write the entry is only logged, so the
+/// operation itself isn't reported as failed because of it.
+pub fn record<T>(
+    db: &Database,
+    kind: OperationKind,
+    mods: &[AffectedMod],
+    detail: Option<&str>,
+    result: Result<T, AppError>,
+) -> Result<T, AppError> {
+    let error = result.as_ref().err().map(ToString::to_string);
+    if let Err(e) = db.log_operation(kind, mods, detail, error.as_deref()) {
+        log::warn!("Failed to record the {kind} operation: {e}");
+    }
+    result
+}
+
+/// [`record`] for callers that only hold the database lock briefly.
+pub fn record_locked<T>(
+    db: &Mutex<Database>,
+    kind: OperationKind,
+    mods: &[AffectedMod],
+    detail: Option<&str>,
+    result: Result<T, AppError>,
+) -> Result<T, AppError> {
+    if let Ok(db) = db.lock() {
+        record(&db, kind, mods, detail, result)
+    } else {
+        log::warn!("Failed to record the {kind} operation: database lock poisoned");
+        result
+    }
+}
@@ -0,0 +1,361 @@
+use crate::errors::AppError;
+use crate::lovely_patches::{
+    CopyPatch, CopyPosition, ModPatches, Patch, PatchManifest, PatternPatch, Position, RegexPatch,
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What the enabled mods' patches do to one game file.
+#[derive(Debug, Clone, Serialize)]
+pub struct FilePreview {
+    pub target: String,
+    /// The mods whose patches changed the file, in the order they applied.
+    pub patched_by: Vec<String>,
+    /// A unified diff from the original to the patched file, empty if the
+    /// patches left it unchanged.
+    pub diff: String,
+}
+
+/// A patch that didn't apply the way its author meant it to.
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchWarning {
+    pub mod_name: String,
+    pub manifest: PathBuf,
+    pub target: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchPreview {
+    pub files: Vec<FilePreview>,
+    pub warnings: Vec<PatchWarning>,
+}
+
+/// A patch along with what's needed to apply it.
+struct QueuedPatch<'a> {
+    mods: &'a ModPatches,
+    manifest: &'a PatchManifest,
+    patch: &'a Patch,
+}
+
+/// Applies the patches of the enabled mods in `mods` to the game source in
+/// `source_dir` in memory, the way lovely applies them when the game loads
+/// each file, and diffs the result against the original. Only files in
+/// `targets` are previewed, or every patched file if it's empty. Module
+/// patches only add a module, so they don't show up in the diffs.
+pub fn preview(
+    source_dir: &Path,
+    mods: &[ModPatches],
+    targets: &[String],
+) -> Result<PatchPreview, AppError> {
+    let mut by_target: BTreeMap<&str, Vec<QueuedPatch<'_>>> = BTreeMap::new();
+    for patches in mods.iter().filter(|patches| patches.enabled) {
+        for manifest in &patches.manifests {
+            for patch in &manifest.patches {
+                if matches!(patch, Patch::Module(_))
+                    || !(targets.is_empty() || targets.iter().any(|t| t == patch.target()))
+                {
+                    continue;
+                }
+                by_target
+                    .entry(patch.target())
+                    .or_default()
+                    .push(QueuedPatch {
+                        mods: patches,
+                        manifest,
+                        patch,
+                    });
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    let mut warnings = Vec::new();
+    for (target, mut queued) in by_target {
+        let path = source_dir.join(target);
+        let Ok(original) = fs::read_to_string(&path) else {
+            for patch in &queued {
+                warnings.push(warning(
+                    patch,
+                    format!("{target} isn't a file in {}", source_dir.display()),
+                ));
+            }
+            continue;
+        };
+
+        // lovely applies copy patches first, then pattern and then regex
+        // patches, each in order of their manifest's priority
+        queued.sort_by_key(|patch| {
+            let stage = match patch.patch {
+                Patch::Copy(_) => 0,
+                Patch::Pattern(_) => 1,
+                Patch::Regex(_) | Patch::Module(_) => 2,
+            };
+            (stage, patch.manifest.priority)
+        });
+
+        let mut patched = original.clone();
+        let mut patched_by: Vec<String> = Vec::new();
+        for patch in &queued {
+            match apply(&patched, patch) {
+                Ok(Applied { text, matches }) => {
+                    if let Some(message) = match_warning(patch.patch, matches) {
+                        warnings.push(warning(patch, message));
+                    }
+                    if text != patched && !patched_by.contains(&patch.mods.name) {
+                        patched_by.push(patch.mods.name.clone());
+                    }
+                    patched = text;
+                }
+                Err(message) => warnings.push(warning(patch, message)),
+            }
+        }
+
+        let diff = if patched == original {
+            String::new()
+        } else {
+            similar::TextDiff::from_lines(&original, &patched)
+                .unified_diff()
+                .context_radius(3)
+                .header(&format!("a/{target}"), &format!("b/{target}"))
+                .to_string()
+        };
+        files.push(FilePreview {
+            target: target.to_string(),
+            patched_by,
+            diff,
+        });
+    }
+
+    Ok(PatchPreview { files, warnings })
+}
+
+fn warning(patch: &QueuedPatch<'_>, message: String) -> PatchWarning {
+    PatchWarning {
+        mod_name: patch.mods.name.clone(),
+        manifest: patch.manifest.path.clone(),
+        target: patch.patch.target().to_string(),
+        message,
+    }
+}
+
+/// Warns about a pattern or regex patch that matched nothing, or fewer
+/// times than its `times` asks for.
+fn match_warning(patch: &Patch, matches: usize) -> Option<String> {
+    let (kind, pattern, times) = match patch {
+        Patch::Pattern(p) => ("Pattern", &p.pattern, p.times),
+        Patch::Regex(p) => ("Regex", &p.pattern, p.times),
+        Patch::Copy(_) | Patch::Module(_) => return None,
+    };
+    if matches == 0 {
+        Some(format!("{kind} '{}' matched nothing", pattern.trim()))
+    } else if let Some(times) = times
+        && matches < times
+    {
+        Some(format!(
+            "{kind} '{}' matched {matches} times, expected {times}",
+            pattern.trim()
+        ))
+    } else {
+        None
+    }
+}
+
+struct Applied {
+    text: String,
+    /// How many times the pattern matched, before `times` was applied.
+    matches: usize,
+}
+
+fn apply(text: &str, queued: &QueuedPatch<'_>) -> Result<Applied, String> {
+    let vars = &queued.manifest.vars;
+    match queued.patch {
+        Patch::Pattern(patch) => Ok(apply_pattern(text, patch, vars)),
+        Patch::Regex(patch) => apply_regex(text, patch, vars),
+        Patch::Copy(patch) => apply_copy(text, patch, vars, &queued.mods.path),
+        Patch::Module(_) => Ok(Applied {
+            text: text.to_string(),
+            matches: 1,
+        }),
+    }
+}
+
+/// Replaces `{{lovely:name}}` with the manifest's `[vars]`.
+fn interpolate_vars(payload: &str, vars: &HashMap<String, String>) -> String {
+    let mut payload = payload.to_string();
+    for (name, value) in vars {
+        payload = payload.replace(&format!("{{{{lovely:{name}}}}}"), value);
+    }
+    payload
+}
+
+fn apply_pattern(text: &str, patch: &PatternPatch, vars: &HashMap<String, String>) -> Applied {
+    let pattern = patch.pattern.trim();
+    let mut payload = interpolate_vars(&patch.payload, vars);
+    if !payload.ends_with('\n') {
+        payload.push('\n');
+    }
+
+    let lines = text.split_inclusive('\n').collect::<Vec<_>>();
+    let matched = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| wildcard_match(pattern, line.trim()))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let applied = &matched[..patch.times.map_or(matched.len(), |t| t.min(matched.len()))];
+
+    let mut out = String::with_capacity(text.len() + payload.len() * applied.len());
+    for (i, line) in lines.iter().enumerate() {
+        if !applied.contains(&i) {
+            out.push_str(line);
+            continue;
+        }
+        let indent = if patch.match_indent {
+            &line[..line.len() - line.trim_start().len()]
+        } else {
+            ""
+        };
+        let indented = prefix_lines(indent, &payload);
+        // A last line without a newline still needs one between it and an
+        // inserted payload
+        let line_ends = if line.ends_with('\n') { "" } else { "\n" };
+        match patch.position {
+            Position::Before => {
+                out.push_str(&indented);
+                out.push_str(line);
+            }
+            Position::After => {
+                out.push_str(line);
+                out.push_str(line_ends);
+                out.push_str(&indented);
+            }
+            Position::At => out.push_str(&indented),
+        }
+    }
+    Applied {
+        text: out,
+        matches: matched.len(),
+    }
+}
+
+fn prefix_lines(prefix: &str, text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        out.push_str(prefix);
+        out.push_str(line);
+    }
+    out
+}
+
+/// Whole-string match where `*` matches any run of characters and `?` any
+/// one character, like the wildmatch lovely uses.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => {
+                let Some((star, matched)) = backtrack else {
+                    return false;
+                };
+                p = star + 1;
+                t = matched + 1;
+                backtrack = Some((star, matched + 1));
+            }
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn apply_regex(
+    text: &str,
+    patch: &RegexPatch,
+    vars: &HashMap<String, String>,
+) -> Result<Applied, String> {
+    let regex = regex::Regex::new(&patch.pattern)
+        .map_err(|e| format!("Regex '{}' is invalid: {e}", patch.pattern))?;
+    let payload = interpolate_vars(&patch.payload, vars);
+
+    let captures = regex.captures_iter(text).collect::<Vec<_>>();
+    let applied = &captures[..patch
+        .times
+        .map_or(captures.len(), |t| t.min(captures.len()))];
+
+    // Edits go from the end of the file backwards so earlier offsets stay
+    // valid
+    let mut out = text.to_string();
+    for captures in applied.iter().rev() {
+        let base = match &patch.root_capture {
+            Some(name) => {
+                let group = name.trim_start_matches('$');
+                captures
+                    .name(group)
+                    .or_else(|| group.parse().ok().and_then(|i| captures.get(i)))
+                    .ok_or_else(|| format!("Regex '{}' has no capture '{name}'", patch.pattern))?
+            }
+            // Group 0, the whole match, is always there
+            None => match captures.get(0) {
+                Some(whole) => whole,
+                None => continue,
+            },
+        };
+
+        let mut line_prepend = String::new();
+        captures.expand(&patch.line_prepend, &mut line_prepend);
+        let mut expanded = String::new();
+        captures.expand(&payload, &mut expanded);
+        let insert = prefix_lines(&line_prepend, &expanded);
+
+        match patch.position {
+            Position::Before => out.insert_str(base.start(), &insert),
+            Position::After => out.insert_str(base.end(), &insert),
+            Position::At => out.replace_range(base.range(), &insert),
+        }
+    }
+    Ok(Applied {
+        text: out,
+        matches: captures.len(),
+    })
+}
+
+fn apply_copy(
+    text: &str,
+    patch: &CopyPatch,
+    vars: &HashMap<String, String>,
+    mod_dir: &Path,
+) -> Result<Applied, String> {
+    let mut copied = Vec::new();
+    for source in &patch.sources {
+        let source = mod_dir.join(source);
+        copied.push(
+            fs::read_to_string(&source)
+                .map_err(|e| format!("Can't read {}: {e}", source.display()))?,
+        );
+    }
+    copied.extend(
+        patch
+            .payload
+            .as_deref()
+            .map(|payload| interpolate_vars(payload, vars)),
+    );
+    let copied = copied.join("\n");
+
+    let text = match patch.position {
+        CopyPosition::Prepend => format!("{copied}\n{text}"),
+        CopyPosition::Append => format!("{text}\n{copied}"),
+    };
+    Ok(Applied { text, matches: 1 })
+}
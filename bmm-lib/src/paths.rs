@@ -0,0 +1,144 @@
+use crate::errors::AppError;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Points every directory at `$BMM_HOME/{config,cache,data,backups}`.
+pub const HOME_ENV: &str = "BMM_HOME";
+/// A file with this name next to the executable makes the directory it's in
+/// the home, so an install on a USB stick keeps everything on the stick.
+pub const PORTABLE_MARKER: &str = "portable";
+
+static PATHS: OnceLock<Paths> = OnceLock::new();
+
+/// Where the directories came from, in order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathsOrigin {
+    /// Set by the embedding program, e.g. `bmm --home`.
+    Override,
+    Environment,
+    Portable,
+    Platform,
+}
+
+/// The directories bromomethane keeps its own files in. The Mods directory
+/// and the game itself aren't among them, those belong to the game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Paths {
+    /// The database, logs, the mod index checkout and lovely on macOS.
+    pub config_dir: PathBuf,
+    /// Catalog and release caches, the archive cache and the extracted game
+    /// sources.
+    pub cache_dir: PathBuf,
+    /// The archives kept for rollbacks.
+    pub data_dir: PathBuf,
+    /// Backups of manually installed mods.
+    pub backup_dir: PathBuf,
+    pub origin: PathsOrigin,
+}
+
+impl Paths {
+    /// The paths used by this process: whatever [`Paths::init`] was given,
+    /// or else [`Paths::resolve`] the first time they're needed.
+    pub fn get() -> Result<&'static Self, AppError> {
+        if let Some(paths) = PATHS.get() {
+            return Ok(paths);
+        }
+        let paths = Self::resolve()?;
+        Ok(PATHS.get_or_init(|| paths))
+    }
+
+    /// Makes `paths` the ones [`Paths::get`] returns. Has to happen before
+    /// anything looks a path up.
+    pub fn init(paths: Self) -> Result<(), AppError> {
+        PATHS.set(paths).map_err(|_| {
+            AppError::InvalidState("Paths were already in use when overridden".to_string())
+        })
+    }
+
+    /// `$BMM_HOME` if it's set, then a portable marker next to the
+    /// executable, then the platform's usual directories.
+    pub fn resolve() -> Result<Self, AppError> {
+        if let Some(home) = std::env::var_os(HOME_ENV).filter(|home| !home.is_empty()) {
+            return Ok(Self::rooted(Path::new(&home), PathsOrigin::Environment));
+        }
+        if let Some(exe_dir) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+            && exe_dir.join(PORTABLE_MARKER).is_file()
+        {
+            return Ok(Self::rooted(&exe_dir, PathsOrigin::Portable));
+        }
+        Self::platform()
+    }
+
+    /// Everything under one directory, as in portable mode.
+    #[must_use]
+    pub fn rooted(root: &Path, origin: PathsOrigin) -> Self {
+        Self {
+            config_dir: root.join("config"),
+            cache_dir: root.join("cache"),
+            data_dir: root.join("data"),
+            backup_dir: root.join("backups"),
+            origin,
+        }
+    }
+
+    pub fn platform() -> Result<Self, AppError> {
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| AppError::DirNotFound(PathBuf::from("config directory")))?;
+        let cache_dir = dirs::cache_dir()
+            .ok_or_else(|| AppError::DirNotFound(PathBuf::from("cache directory")))?;
+        let data_dir = dirs::data_dir()
+            .ok_or_else(|| AppError::DirNotFound(PathBuf::from("data directory")))?;
+        Ok(Self {
+            config_dir: config_dir.join("Balatro"),
+            cache_dir: cache_dir.join("bromomethane"),
+            data_dir: data_dir.join("bromomethane"),
+            backup_dir: std::env::temp_dir().join("bromomethane_backups"),
+            origin: PathsOrigin::Platform,
+        })
+    }
+
+    #[must_use]
+    pub fn database(&self) -> PathBuf {
+        self.config_dir.join("bmm_storage.db")
+    }
+
+    #[must_use]
+    pub fn log_dir(&self) -> PathBuf {
+        self.config_dir.join("logs")
+    }
+
+    #[must_use]
+    pub fn mod_index_dir(&self) -> PathBuf {
+        self.config_dir.join("mod_index")
+    }
+
+    #[must_use]
+    pub fn archive_cache_dir(&self) -> PathBuf {
+        self.cache_dir.join("archives")
+    }
+
+    /// The game's Lua sources, extracted per release.
+    #[must_use]
+    pub fn game_source_dir(&self) -> PathBuf {
+        self.cache_dir.join("game_source")
+    }
+
+    #[must_use]
+    pub fn version_store_dir(&self) -> PathBuf {
+        self.data_dir.join("versions")
+    }
+}
+
+/// Creates `dir` if it doesn't exist yet and hands it back.
+pub fn ensure_dir(dir: PathBuf) -> Result<PathBuf, AppError> {
+    fs::create_dir_all(&dir).map_err(|e| AppError::DirCreate {
+        path: dir.clone(),
+        source: e.to_string(),
+    })?;
+    Ok(dir)
+}
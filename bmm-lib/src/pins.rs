@@ -0,0 +1,163 @@
+use crate::database::Database;
+use crate::dependencies::VersionConstraint;
+use crate::errors::AppError;
+use crate::smods_installer::ModType;
+use crate::version::Version;
+use serde::{Deserialize, Serialize};
+
+/// Holds a tracked mod, Steamodded or Talisman at the versions matching
+/// `constraint`, such as `1.2.0` for exactly that version or `>=1.0, <<2.0`
+/// for a range. Updates outside of it are held back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModPin {
+    pub name: String,
+    pub constraint: String,
+    /// Why the mod is pinned, shown when an update is held back.
+    pub reason: Option<String>,
+}
+
+/// An update that a pin held back.
+#[derive(Debug, Clone, Serialize)]
+pub struct HeldUpdate {
+    pub name: String,
+    pub installed: String,
+    pub available: String,
+    pub pin: ModPin,
+    pub explanation: String,
+}
+
+impl ModPin {
+    /// Checks that `constraint` parses before the pin is stored.
+    pub fn new(name: &str, constraint: &str, reason: Option<String>) -> Result<Self, AppError> {
+        let pin = Self {
+            name: name.to_string(),
+            constraint: constraint.trim().to_string(),
+            reason: reason.filter(|r| !r.trim().is_empty()),
+        };
+        pin.constraints()?;
+        Ok(pin)
+    }
+
+    /// The comma separated constraints, with or without the parentheses
+    /// dependencies put around them.
+    pub fn constraints(&self) -> Result<Vec<VersionConstraint>, AppError> {
+        let constraint = self.constraint.trim();
+        let constraint = constraint
+            .strip_prefix('(')
+            .and_then(|c| c.strip_suffix(')'))
+            .unwrap_or(constraint);
+        let constraints = constraint
+            .split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if constraints.is_empty() {
+            return Err(AppError::InvalidVersion(self.constraint.clone()));
+        }
+        Ok(constraints)
+    }
+
+    /// Whether `version` satisfies the pin. A version that can't be parsed
+    /// only does if the pin names it exactly.
+    #[must_use]
+    pub fn allows(&self, version: &str) -> bool {
+        match (version.parse::<Version>(), self.constraints()) {
+            (Ok(version), Ok(constraints)) => constraints.iter().all(|c| c.matches(&version)),
+            _ => self.constraint.trim() == version.trim(),
+        }
+    }
+
+    /// Why `available` isn't installed over `installed`, which is empty when
+    /// the mod isn't installed at all.
+    #[must_use]
+    pub fn explain(&self, installed: &str, available: &str) -> String {
+        let mut explanation = format!(
+            "{} {available} is available, but {} is pinned to {}",
+            self.name, self.name, self.constraint
+        );
+        if !installed.is_empty() {
+            explanation.push_str(" and stays at ");
+            explanation.push_str(installed);
+        }
+        if let Some(reason) = &self.reason {
+            explanation.push_str(": ");
+            explanation.push_str(reason);
+        }
+        explanation
+    }
+
+    #[must_use]
+    pub fn hold(&self, installed: &str, available: &str) -> HeldUpdate {
+        HeldUpdate {
+            name: self.name.clone(),
+            installed: installed.to_string(),
+            available: available.to_string(),
+            pin: self.clone(),
+            explanation: self.explain(installed, available),
+        }
+    }
+}
+
+/// The versions of a mod that can be installed, and the ones its pin holds
+/// back.
+#[derive(Debug, Clone, Serialize)]
+pub struct AllowedVersions {
+    pub versions: Vec<String>,
+    pub held: Vec<HeldUpdate>,
+}
+
+/// Splits `versions` into the ones `name`'s pin allows and the ones it
+/// holds back, both in the same order. Without a pin that's all of them.
+pub fn allowed_versions(
+    db: &Database,
+    name: &str,
+    versions: Vec<String>,
+) -> Result<AllowedVersions, AppError> {
+    let Some(pin) = db.get_mod_pin(name)? else {
+        return Ok(AllowedVersions {
+            versions,
+            held: Vec::new(),
+        });
+    };
+    let installed = db
+        .get_mod_details(&pin.name)
+        .ok()
+        .and_then(|installed| installed.current_version)
+        .unwrap_or_default();
+    let (versions, held): (Vec<_>, Vec<_>) = versions.into_iter().partition(|v| pin.allows(v));
+    Ok(AllowedVersions {
+        versions,
+        held: held.iter().map(|v| pin.hold(&installed, v)).collect(),
+    })
+}
+
+/// Pins a tracked mod to `constraint`, or to the version installed right now
+/// when there's no constraint. Steamodded and Talisman can be pinned before
+/// they're installed.
+pub fn pin(
+    db: &Database,
+    name: &str,
+    constraint: Option<&str>,
+    reason: Option<String>,
+) -> Result<ModPin, AppError> {
+    let (name, installed_version) = match db.get_mod_details(name) {
+        Ok(installed) => (installed.name, installed.current_version),
+        Err(e) => match ModType::from_mod_name(name) {
+            Some(mod_type) => (mod_type.to_string(), None),
+            None => return Err(e),
+        },
+    };
+    let constraint = match constraint {
+        Some(constraint) => constraint.to_string(),
+        None => installed_version.filter(|v| !v.is_empty()).ok_or_else(|| {
+            AppError::InvalidState(format!(
+                "The installed version of {name} isn't known, so a version to pin to is needed"
+            ))
+        })?,
+    };
+
+    let pin = ModPin::new(&name, &constraint, reason)?;
+    db.set_mod_pin(&pin)?;
+    Ok(pin)
+}
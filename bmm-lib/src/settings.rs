@@ -0,0 +1,165 @@
+use crate::database::Database;
+use crate::errors::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Bumped whenever an exported settings file couldn't be read by an older
+/// version.
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// The user's preferences, stored as rows of the `settings` table and loaded
+/// and saved as a whole. Missing or unreadable rows fall back to the
+/// defaults. The installation path belongs to the active installation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// The game directory of the active installation.
+    pub installation_path: Option<String>,
+    /// Whether the animated background is shown.
+    pub background_enabled: bool,
+    /// Whether the warning about running third-party mods was dismissed.
+    pub security_warning_acknowledged: bool,
+}
+
+/// The on-disk format of exported settings.
+#[derive(Debug, Serialize)]
+struct SettingsFile<'a> {
+    settings_version: u32,
+    #[serde(flatten)]
+    settings: &'a Settings,
+}
+
+impl Settings {
+    /// Reads the settings back from their rows. The values are stored the
+    /// way earlier releases stored them, so those can still read them.
+    pub(crate) fn from_rows(
+        rows: &HashMap<String, String>,
+        installation_path: Option<String>,
+    ) -> Self {
+        let row = |key: &str| rows.get(key).map(String::as_str);
+        Self {
+            installation_path,
+            background_enabled: row("background_enabled") == Some("enabled"),
+            security_warning_acknowledged: row("security_warning_acknowledged") == Some("yes"),
+        }
+    }
+
+    /// The rows to store, everything but the installation path.
+    pub(crate) fn to_rows(&self) -> [(&'static str, &'static str); 2] {
+        [
+            (
+                "background_enabled",
+                if self.background_enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                },
+            ),
+            (
+                "security_warning_acknowledged",
+                if self.security_warning_acknowledged {
+                    "yes"
+                } else {
+                    "no"
+                },
+            ),
+        ]
+    }
+
+    /// Rejects values that can't be used, before they're saved. Values that
+    /// are the same as in `current` aren't checked again, so a game that was
+    /// moved away doesn't stop anything else from being changed.
+    pub fn validate(&self, current: &Self) -> Result<(), AppError> {
+        if let Some(path) = &self.installation_path
+            && current.installation_path.as_ref() != Some(path)
+        {
+            if path.trim().is_empty() {
+                return Err(AppError::InvalidState(
+                    "The installation path can't be empty".to_string(),
+                ));
+            }
+            if !Path::new(path).is_dir() {
+                return Err(AppError::invalid_path(
+                    path,
+                    "Installation path isn't a directory",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the keys present in `json` on top of these settings, leaving
+    /// the others as they are. An installation path that doesn't exist here,
+    /// such as one exported on another machine, is ignored.
+    pub fn merge_json(&self, json: &str) -> Result<Self, AppError> {
+        let serde_json::Value::Object(mut changes) = serde_json::from_str(json)? else {
+            return Err(AppError::InvalidState(
+                "Settings have to be a JSON object".to_string(),
+            ));
+        };
+        if let Some(version) = changes.remove("settings_version")
+            && version
+                .as_u64()
+                .is_none_or(|v| v > u64::from(SETTINGS_VERSION))
+        {
+            return Err(AppError::InvalidState(format!(
+                "The settings were exported by a newer version of bromomethane (settings version {version})"
+            )));
+        }
+        if let Some(path) = changes
+            .get("installation_path")
+            .and_then(|p| p.as_str())
+            .filter(|path| !Path::new(path).is_dir())
+            .map(str::to_string)
+        {
+            log::warn!("Keeping the current installation path, {path} doesn't exist here");
+            changes.remove("installation_path");
+        }
+
+        let mut merged = serde_json::to_value(self)?
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        merged.extend(changes);
+        Ok(serde_json::from_value(serde_json::Value::Object(merged))?)
+    }
+
+    /// Serializes the settings for [`Settings::merge_json`] on another
+    /// machine.
+    pub fn to_json(&self) -> Result<String, AppError> {
+        Ok(serde_json::to_string_pretty(&SettingsFile {
+            settings_version: SETTINGS_VERSION,
+            settings: self,
+        })?)
+    }
+}
+
+/// Writes the stored settings to `path` as JSON.
+pub fn export(db: &Database, path: &Path) -> Result<(), AppError> {
+    std::fs::write(path, db.get_settings()?.to_json()?).map_err(|e| AppError::FileWrite {
+        path: path.to_path_buf(),
+        source: e.to_string(),
+    })
+}
+
+/// Applies the settings exported to `path` on top of the stored ones, see
+/// [`Settings::merge_json`], and returns the result.
+pub fn import(db: &Database, path: &Path) -> Result<Settings, AppError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| AppError::FileRead {
+        path: path.to_path_buf(),
+        source: e.to_string(),
+    })?;
+    let settings = db
+        .get_settings()?
+        .merge_json(&contents)
+        .map_err(|e| match e {
+            AppError::Serialization { source, .. } => AppError::JsonParse {
+                path: path.to_path_buf(),
+                source,
+            },
+            e => e,
+        })?;
+    db.set_settings(&settings)?;
+    Ok(settings)
+}
@@ -1,10 +1,17 @@
-use crate::finder::get_lovely_mods_dir;
+use crate::archive_cache;
+use crate::checksum;
+use crate::download::DownloadJob;
+use crate::errors::AppError;
+use crate::installer::ModSource;
+use crate::integrity;
+use crate::staging::Staging;
+use crate::version;
 use anyhow::{Context, Result, anyhow};
 use log::info;
 use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::io::Cursor;
+use std::path::{Path, PathBuf};
 use tokio::fs as tokio_fs;
 use zip::ZipArchive;
 
@@ -35,12 +42,33 @@ impl std::fmt::Display for ModType {
 }
 
 impl ModType {
+    /// The framework a tracked mod name refers to, if it's one of them.
+    #[must_use]
+    pub fn from_mod_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "steamodded" => Some(ModType::Steamodded),
+            "talisman" => Some(ModType::Talisman),
+            _ => None,
+        }
+    }
+
     fn get_repo_url(&self) -> &str {
         match self {
             ModType::Steamodded => "Steamodded/smods",
             ModType::Talisman => "MathIsFun0/Talisman",
         }
     }
+
+    /// The release tag a download URL built by
+    /// [`ModInstaller::install_version`] points at, if it points at one.
+    #[must_use]
+    pub fn release_tag_from_url<'a>(&self, url: &'a str) -> Option<&'a str> {
+        url.strip_prefix(&format!(
+            "https://github.com/{}/archive/refs/tags/",
+            self.get_repo_url()
+        ))?
+        .strip_suffix(".zip")
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -52,22 +80,32 @@ struct ReleaseAsset {
 pub struct ModInstaller {
     client: reqwest::Client,
     pub mod_type: ModType,
-    pub installation_path: Option<String>,
+    /// The Mods directory to install into. Only looking up releases works
+    /// without one.
+    pub mods_dir: Option<PathBuf>,
 }
 
 impl ModInstaller {
     #[must_use]
-    pub fn new(installation_path: Option<&String>, mod_type: ModType) -> Self {
+    pub fn new(mods_dir: Option<&Path>, mod_type: ModType) -> Self {
         Self {
             client: reqwest::Client::new(),
             mod_type,
-            installation_path: installation_path.cloned(),
+            mods_dir: mods_dir.map(Path::to_path_buf),
         }
     }
 
+    fn mods_dir(&self) -> Result<&Path> {
+        self.mods_dir
+            .as_deref()
+            .ok_or_else(|| anyhow!("No Mods directory to install {:?} into", self.mod_type))
+    }
+
     #[must_use]
     pub fn is_installed(&self) -> bool {
-        let mods_dir = get_lovely_mods_dir(self.installation_path.as_ref());
+        let Some(mods_dir) = &self.mods_dir else {
+            return false;
+        };
 
         fs::read_dir(mods_dir)
             .map(|mut entries| {
@@ -135,7 +173,7 @@ impl ModInstaller {
             .map(|r| r.tag_name)
             .collect();
 
-        versions.sort_by(|a, b| b.cmp(a));
+        version::sort_newest_first(&mut versions);
 
         Ok(versions)
     }
@@ -172,7 +210,19 @@ impl ModInstaller {
     }
 
     pub async fn install_version(&self, version: &str) -> Result<String> {
-        let mods_dir = get_lovely_mods_dir(self.installation_path.as_ref());
+        self.install_version_with_source(version, &DownloadJob::default())
+            .await
+            .map(|(path, _)| path)
+    }
+
+    /// Like [`install_version`](Self::install_version), but also returns
+    /// where the release was downloaded from, and reports the download's
+    /// progress to `job`.
+    pub async fn install_version_with_source(
+        &self,
+        version: &str,
+        job: &DownloadJob,
+    ) -> Result<(String, ModSource)> {
         let url = match version {
             "newest" => self.get_default_branch_download_url().await?,
             _ => format!(
@@ -182,70 +232,75 @@ impl ModInstaller {
             ),
         };
 
-        info!(
-            "Installing {:?} version {} to {:?}",
-            self.mod_type, version, mods_dir
-        );
+        info!("Installing {:?} version {}", self.mod_type, version);
+        self.install_archive_with_source(url, None, job).await
+    }
 
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            USER_AGENT,
-            HeaderValue::from_static("Balatro-Mod-Manager/1.0"),
+    /// Installs the release archive at `url`. When `expected_hash` is given,
+    /// an archive that doesn't match it is rejected before anything in the
+    /// Mods directory is touched.
+    pub async fn install_archive_with_source(
+        &self,
+        url: String,
+        expected_hash: Option<&str>,
+        job: &DownloadJob,
+    ) -> Result<(String, ModSource)> {
+        let mods_dir = self.mods_dir()?;
+        info!(
+            "Installing {:?} from {} to {:?}",
+            self.mod_type, url, mods_dir
         );
 
-        // Download the zip file
-        let response = self.client.get(&url).headers(headers).send().await?;
-        let bytes = response.bytes().await?;
-
-        // Create temp directory
-        let temp_dir = mods_dir.join(format!(
-            "temp_{}",
-            match self.mod_type {
-                ModType::Steamodded => "smods",
-                ModType::Talisman => "talisman",
+        let archive = archive_cache::fetch(&url, expected_hash, job).await?;
+        if let Some(expected) = expected_hash
+            && !checksum::matches(expected, &archive.sha256)
+        {
+            return Err(AppError::ChecksumMismatch {
+                subject: url,
+                expected: expected.to_string(),
+                actual: archive.sha256.clone(),
             }
-        ));
-        if temp_dir.exists() {
-            fs::remove_dir_all(&temp_dir)?;
+            .into());
         }
-        fs::create_dir_all(&temp_dir)?;
 
-        // Extract to temp directory
-        let cursor = Cursor::new(bytes);
-        let mut archive = ZipArchive::new(cursor)?;
-        archive.extract(&temp_dir)?;
+        // Extract next to the Mods dir, and only replace the installed copy
+        // once the new one is complete
+        let staging = Staging::new(mods_dir, &self.mod_type.to_string())?;
+        let mut zip = ZipArchive::new(archive.open()?)?;
+        zip.extract(staging.path())?;
 
         // Find the root directory name (GitHub format: Steamodded-smods-commitHash)
-        let root_dir = fs::read_dir(&temp_dir)?
+        let root_dir = fs::read_dir(staging.path())?
             .next()
             .ok_or(anyhow!("Empty archive"))??
             .file_name()
             .into_string()
             .map_err(|_| anyhow!("Invalid directory name"))?;
 
-        // Move to final location
         let final_dir = match self.mod_type {
             ModType::Steamodded => mods_dir.join(&root_dir),
             ModType::Talisman => mods_dir.join("Talisman"),
         };
-        if final_dir.exists() {
-            fs::remove_dir_all(&final_dir)?;
-        }
-        fs::rename(temp_dir.join(&root_dir), &final_dir)?;
-
-        // Cleanup
-        fs::remove_dir_all(temp_dir)?;
+        staging.commit(&staging.path().join(&root_dir), &final_dir)?;
+        let files = integrity::manifest(&final_dir)?;
 
         info!(
-            "Successfully installed {:?} version {} to {:?}",
-            self.mod_type, version, final_dir
+            "Successfully installed {:?} to {:?}",
+            self.mod_type, final_dir
         );
 
-        Ok(final_dir.to_string_lossy().to_string())
+        Ok((
+            final_dir.to_string_lossy().to_string(),
+            ModSource {
+                download_url: url,
+                archive_hash: archive.sha256,
+                files,
+            },
+        ))
     }
 
     pub async fn uninstall(&self) -> Result<()> {
-        let mods_dir = get_lovely_mods_dir(self.installation_path.as_ref());
+        let mods_dir = self.mods_dir()?;
         if !mods_dir.exists() {
             info!("Mods directory not found");
             return Ok(());
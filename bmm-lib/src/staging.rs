@@ -0,0 +1,118 @@
+use crate::errors::AppError;
+use crate::local_mod_detection;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A scratch directory next to the Mods dir that installs are extracted into
+/// before they replace anything in the Mods dir. It lives on the same
+/// filesystem as the Mods dir so moving a mod into place is a rename, and
+/// outside of it so lovely never loads a half-extracted mod. The directory is
+/// removed when the `Staging` is dropped.
+#[derive(Debug)]
+pub struct Staging {
+    dir: PathBuf,
+}
+
+impl Staging {
+    /// Creates an empty staging directory for installing `name` into
+    /// `mods_dir`.
+    pub fn new(mods_dir: &Path, name: &str) -> Result<Self, AppError> {
+        let root = mods_dir.with_file_name(".bmm-staging");
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let dir = root.join(format!("{name}-{timestamp}"));
+
+        fs::create_dir_all(&dir).map_err(|e| AppError::DirCreate {
+            path: dir.clone(),
+            source: e.to_string(),
+        })?;
+        Ok(Self { dir })
+    }
+
+    /// Where archives should be extracted to.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Checks that `staged` (a directory inside the staging directory) holds
+    /// a mod, then swaps it in at `target`. If `target` already exists, it's
+    /// set aside first and put back if anything goes wrong, so a failed
+    /// install or upgrade leaves the previous copy in place.
+    pub fn commit(&self, staged: &Path, target: &Path) -> Result<(), AppError> {
+        validate(staged)?;
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| AppError::DirCreate {
+                path: parent.to_path_buf(),
+                source: e.to_string(),
+            })?;
+        }
+
+        let previous = self.dir.join(".previous");
+        let had_previous = target.exists();
+        if had_previous {
+            fs::rename(target, &previous).map_err(|e| AppError::FileWrite {
+                path: target.to_path_buf(),
+                source: format!("Failed to move the installed copy aside: {e}"),
+            })?;
+        }
+
+        if let Err(e) = fs::rename(staged, target) {
+            if had_previous && let Err(restore) = fs::rename(&previous, target) {
+                log::error!(
+                    "Failed to restore {} after a failed install: {restore}",
+                    target.display()
+                );
+            }
+            return Err(AppError::FileWrite {
+                path: target.to_path_buf(),
+                source: format!("Failed to move the new copy into place: {e}"),
+            });
+        }
+
+        log::info!("Installed {} from staging", target.display());
+        Ok(())
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            log::warn!(
+                "Failed to clean up staging directory {}: {e}",
+                self.dir.display()
+            );
+        }
+        // Only succeeds once no other install is staging.
+        if let Some(root) = self.dir.parent() {
+            let _ = fs::remove_dir(root);
+        }
+    }
+}
+
+/// A staged directory is installable if `local_mod_detection` recognises it
+/// as a mod, or if it carries lovely patches. Lovely-only mods have no
+/// Steamodded metadata, so detection alone would turn them away.
+fn validate(staged: &Path) -> Result<(), AppError> {
+    if !staged.is_dir() {
+        return Err(AppError::InvalidState(format!(
+            "Nothing was extracted to {}",
+            staged.display()
+        )));
+    }
+
+    let has_lovely_patches = staged.join("lovely.toml").is_file() || staged.join("lovely").is_dir();
+    if has_lovely_patches || local_mod_detection::detect_mod(staged).is_some() {
+        Ok(())
+    } else {
+        Err(AppError::InvalidState(format!(
+            "The downloaded archive doesn't contain a recognisable mod ({})",
+            staged
+                .file_name()
+                .map_or_else(String::new, |n| n.to_string_lossy().into_owned())
+        )))
+    }
+}
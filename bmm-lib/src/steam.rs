@@ -0,0 +1,283 @@
+use crate::errors::AppError;
+use crate::vdf;
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+
+/// Balatro's Steam app id.
+pub const BALATRO_APP_ID: u32 = 2_379_780;
+
+/// What an `appmanifest_<id>.acf` says about an installed app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppManifest {
+    pub app_id: u32,
+    pub name: String,
+    /// The app's directory under `steamapps/common`.
+    pub install_dir: String,
+    pub build_id: Option<String>,
+    /// The beta branch that's installed, `None` for the default branch.
+    pub branch: Option<String>,
+}
+
+impl AppManifest {
+    pub fn read(path: &Path) -> Result<Self, AppError> {
+        let manifest = vdf::read(path)?;
+        let state = manifest
+            .get_table("AppState")
+            .ok_or_else(|| AppError::invalid_path(path, "App manifest has no AppState section"))?;
+        let app_id = state
+            .get_str("appid")
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| AppError::invalid_path(path, "App manifest has no app id"))?;
+        let branch = ["MountedConfig", "UserConfig"]
+            .iter()
+            .filter_map(|section| state.get_table(section)?.get_str("BetaKey"))
+            .find(|branch| !branch.is_empty() && *branch != "public")
+            .map(str::to_string);
+
+        Ok(Self {
+            app_id,
+            name: state.get_str("name").unwrap_or_default().to_string(),
+            install_dir: state.get_str("installdir").unwrap_or_default().to_string(),
+            build_id: state
+                .get_str("buildid")
+                .filter(|id| !id.is_empty() && *id != "0")
+                .map(str::to_string),
+            branch,
+        })
+    }
+}
+
+/// An app found in one of the Steam libraries.
+#[derive(Debug, Clone)]
+pub struct SteamApp {
+    /// The library folder, the directory that holds `steamapps`.
+    pub library: PathBuf,
+    pub manifest: AppManifest,
+    pub game_dir: PathBuf,
+}
+
+#[cfg(target_os = "windows")]
+fn read_path_from_registry() -> Result<String, std::io::Error> {
+    use winreg::enums::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+
+    let hkcu = winreg::RegKey::predef(HKEY_CURRENT_USER);
+    if let Ok(path) = hkcu
+        .open_subkey("Software\\Valve\\Steam")
+        .and_then(|key| key.get_value::<String, _>("SteamPath"))
+    {
+        return Ok(path);
+    }
+
+    let hklm = winreg::RegKey::predef(HKEY_LOCAL_MACHINE);
+    let steam_path = hklm.open_subkey("SOFTWARE\\WOW6432Node\\Valve\\Steam")?;
+
+    steam_path.get_value("InstallPath")
+}
+
+/// Places Steam may be installed in, whether or not it is.
+fn candidate_roots() -> Vec<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        let path = read_path_from_registry().unwrap_or_else(|_| {
+            warn!("Could not read steam install path from Registry! Trying standard installation path in C:\\");
+            String::from("C:\\Program Files (x86)\\Steam")
+        });
+        vec![PathBuf::from(path)]
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        dirs::home_dir()
+            .map(|home| vec![home.join("Library/Application Support/Steam")])
+            .unwrap_or_default()
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let Some(home) = dirs::home_dir() else {
+            warn!("Impossible to get your home dir!");
+            return Vec::new();
+        };
+        let mut roots = vec![home.join(".steam/steam"), home.join(".steam/root")];
+        roots.extend(dirs::data_dir().map(|data| data.join("Steam")));
+        roots.extend([
+            home.join(".local/share/Steam"),
+            // Flatpak
+            home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+            home.join(".var/app/com.valvesoftware.Steam/.steam/steam"),
+            home.join(".var/app/com.valvesoftware.Steam/data/Steam"),
+            // Snap
+            home.join("snap/steam/common/.local/share/Steam"),
+            home.join("snap/steam/common/.steam/steam"),
+        ]);
+        roots
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        Vec::new()
+    }
+}
+
+/// Adds `path` to `paths` unless it, or the directory a symlink to it points
+/// to, is already there.
+fn push_unique(paths: &mut Vec<PathBuf>, path: PathBuf) {
+    let path = path.canonicalize().unwrap_or(path);
+    if !paths.contains(&path) {
+        paths.push(path);
+    }
+}
+
+/// The Steam installations on this machine, the directories holding a
+/// `steamapps` directory. On Linux that includes Flatpak and Snap installs.
+#[must_use]
+pub fn steam_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    for root in candidate_roots() {
+        if root.join("steamapps").is_dir() {
+            push_unique(&mut roots, root);
+        }
+    }
+    roots
+}
+
+/// The library folders of the Steam installation at `steam_root`, starting
+/// with the installation itself. Both the current `libraryfolders.vdf`
+/// layout and the older one that listed bare paths are understood.
+#[must_use]
+pub fn library_folders(steam_root: &Path) -> Vec<PathBuf> {
+    let mut libraries = Vec::new();
+    push_unique(&mut libraries, steam_root.to_path_buf());
+
+    let vdf_path = steam_root.join("steamapps/libraryfolders.vdf");
+    if !vdf_path.is_file() {
+        return libraries;
+    }
+    let folders = match vdf::read(&vdf_path) {
+        Ok(folders) => folders,
+        Err(e) => {
+            warn!("Ignoring {}: {e}", vdf_path.display());
+            return libraries;
+        }
+    };
+    let Some(folders) = folders.get_table("libraryfolders") else {
+        warn!("{} has no libraryfolders section", vdf_path.display());
+        return libraries;
+    };
+
+    for (key, folder) in folders.iter() {
+        if key.parse::<u32>().is_err() {
+            continue;
+        }
+        let path = match folder {
+            vdf::Vdf::Value(path) => Some(path.as_str()),
+            vdf::Vdf::Table(folder) => folder.get_str("path"),
+        };
+        if let Some(path) = path.filter(|path| !path.is_empty()) {
+            push_unique(&mut libraries, PathBuf::from(path));
+        }
+    }
+    libraries
+}
+
+/// Every installed copy of `app_id`, found by the `appmanifest_<id>.acf` in
+/// each library folder of each Steam installation.
+#[must_use]
+pub fn find_app(app_id: u32) -> Vec<SteamApp> {
+    let mut apps: Vec<SteamApp> = Vec::new();
+    for root in steam_roots() {
+        for library in library_folders(&root) {
+            let manifest_path = library
+                .join("steamapps")
+                .join(format!("appmanifest_{app_id}.acf"));
+            if !manifest_path.is_file() {
+                continue;
+            }
+            let manifest = match AppManifest::read(&manifest_path) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    warn!("Ignoring {}: {e}", manifest_path.display());
+                    continue;
+                }
+            };
+            let game_dir = library.join("steamapps/common").join(&manifest.install_dir);
+            if apps.iter().any(|app| app.game_dir == game_dir) {
+                continue;
+            }
+            info!(
+                "Found app {app_id} in the Steam library {}",
+                library.display()
+            );
+            apps.push(SteamApp {
+                library,
+                manifest,
+                game_dir,
+            });
+        }
+    }
+    apps
+}
+
+/// The `steamapps` directory of the library `game_dir` is installed in, if
+/// it's a Steam install at `<library>/steamapps/common/<game>`.
+#[must_use]
+pub fn steamapps_of(game_dir: &Path) -> Option<&Path> {
+    let common = game_dir.parent()?;
+    let steamapps = common.parent()?;
+    (common.file_name()? == "common" && steamapps.file_name()? == "steamapps").then_some(steamapps)
+}
+
+/// The Wine prefix Proton runs `app_id` in. `STEAM_COMPAT_DATA_PATH` wins if
+/// it's set; otherwise the prefix is looked for in the library the game is
+/// installed in, then in every other library, since Steam keeps it next to
+/// the game.
+#[cfg(target_os = "linux")]
+pub fn proton_prefix(app_id: u32, game_dir: Option<&Path>) -> Result<PathBuf, AppError> {
+    if let Some(compat_data) =
+        std::env::var_os("STEAM_COMPAT_DATA_PATH").filter(|path| !path.is_empty())
+    {
+        let prefix = Path::new(&compat_data).join("pfx");
+        if prefix.is_dir() {
+            return Ok(prefix);
+        }
+        return Err(AppError::SystemDetection(format!(
+            "STEAM_COMPAT_DATA_PATH is set to {}, but there's no Proton prefix at {}",
+            Path::new(&compat_data).display(),
+            prefix.display()
+        )));
+    }
+
+    let mut steamapps_dirs: Vec<PathBuf> = Vec::new();
+    steamapps_dirs.extend(game_dir.and_then(steamapps_of).map(Path::to_path_buf));
+    for app in find_app(app_id) {
+        push_unique(&mut steamapps_dirs, app.library.join("steamapps"));
+    }
+    for root in steam_roots() {
+        for library in library_folders(&root) {
+            push_unique(&mut steamapps_dirs, library.join("steamapps"));
+        }
+    }
+
+    let compat_data = format!("compatdata/{app_id}/pfx");
+    steamapps_dirs
+        .iter()
+        .map(|steamapps| steamapps.join(&compat_data))
+        .find(|prefix| prefix.is_dir())
+        .ok_or_else(|| {
+            let searched = steamapps_dirs
+                .iter()
+                .map(|dir| dir.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            AppError::SystemDetection(format!(
+                "No Proton prefix for app {app_id} found in {}. Launch the game through Steam \
+                 once so Proton creates it, or set STEAM_COMPAT_DATA_PATH to its compatdata \
+                 directory",
+                if searched.is_empty() {
+                    "any Steam library"
+                } else {
+                    &searched
+                }
+            ))
+        })
+}
@@ -0,0 +1,444 @@
+use crate::cache;
+use crate::database::{Database, InstalledMod};
+use crate::dependencies::{self, InstalledSet, UnmetDependency, find_in_catalog};
+use crate::download::DownloadJob;
+use crate::errors::AppError;
+use crate::installer::{self, ModSource};
+use crate::local_mod_detection;
+use crate::operations::{self, AffectedMod, OperationKind};
+use crate::pins::{HeldUpdate, ModPin};
+use crate::smods_installer::{ModInstaller, ModType};
+use crate::version;
+use crate::version_history;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where an update comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateSource {
+    Catalog,
+    Steamodded,
+    Talisman,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedUpdate {
+    /// The name the mod is tracked under.
+    pub name: String,
+    pub path: String,
+    pub from_version: String,
+    pub to_version: String,
+    pub source: UpdateSource,
+    /// Dependencies the new version declares that the installed one didn't.
+    pub new_dependencies: Vec<String>,
+}
+
+/// Everything needed to bring the tracked mods up to date.
+#[derive(Debug, Default, Serialize)]
+pub struct UpdatePlan {
+    pub updates: Vec<PlannedUpdate>,
+    /// Catalog titles that the updated mods newly depend on, in install order.
+    pub install: Vec<String>,
+    /// New dependencies that can't be satisfied. The updates are still
+    /// applied.
+    pub unmet: Vec<UnmetDependency>,
+    /// Newer versions that pinned mods are kept from.
+    pub held: Vec<HeldUpdate>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateFailure {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct UpdateReport {
+    pub updated: Vec<String>,
+    /// Dependencies that were installed for the updates.
+    pub installed: Vec<String>,
+    pub failed: Vec<UpdateFailure>,
+}
+
+/// The Steamodded and Talisman releases, newest first, when they're known.
+#[derive(Debug, Clone, Default)]
+pub struct LatestReleases {
+    pub steamodded: Vec<String>,
+    pub talisman: Vec<String>,
+}
+
+impl LatestReleases {
+    /// Looks up the releases of each framework in `installed_mods`. When
+    /// GitHub can't be reached, the cached release lists are used.
+    pub async fn fetch(installed_mods: &[InstalledMod]) -> Self {
+        let mut releases = Self::default();
+        for mod_type in installed_mods
+            .iter()
+            .filter_map(|m| ModType::from_mod_name(&m.name))
+        {
+            let versions = releases_of(mod_type.clone()).await;
+            match mod_type {
+                ModType::Steamodded => releases.steamodded = versions,
+                ModType::Talisman => releases.talisman = versions,
+            }
+        }
+        releases
+    }
+
+    fn get(&self, mod_type: &ModType) -> &[String] {
+        match mod_type {
+            ModType::Steamodded => &self.steamodded,
+            ModType::Talisman => &self.talisman,
+        }
+    }
+}
+
+async fn releases_of(mod_type: ModType) -> Vec<String> {
+    let cache_key = mod_type.to_string().to_lowercase();
+    let mut versions = match ModInstaller::new(None, mod_type)
+        .get_available_versions()
+        .await
+    {
+        Ok(versions) => versions,
+        Err(e) => {
+            log::warn!("Failed to fetch {cache_key} releases, using the cached list: {e}");
+            cache::load_versions_cache(&cache_key)
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+        }
+    };
+    version::sort_newest_first(&mut versions);
+    versions
+}
+
+/// What checking a single mod for an update turned up.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum UpdateStatus {
+    UpToDate,
+    Available { version: String },
+    Held(HeldUpdate),
+}
+
+impl UpdateStatus {
+    /// Picks the newest of `candidates` (newest first) that `pin` allows.
+    /// When a newer one is disallowed, the update is held back, unless an
+    /// allowed one is still newer than `installed`.
+    fn check<'a>(
+        installed: &str,
+        candidates: impl IntoIterator<Item = &'a String>,
+        pin: Option<&ModPin>,
+    ) -> Self {
+        let mut held = None;
+        for candidate in candidates {
+            if !version::is_update(installed, candidate) {
+                break;
+            }
+            match pin {
+                Some(pin) if !pin.allows(candidate) => {
+                    held.get_or_insert_with(|| pin.hold(installed, candidate));
+                }
+                _ => {
+                    return Self::Available {
+                        version: candidate.clone(),
+                    };
+                }
+            }
+        }
+        held.map_or(Self::UpToDate, Self::Held)
+    }
+}
+
+/// Whether the catalog has a newer version of the tracked mod `name` that
+/// its pin allows.
+pub fn catalog_update_status(
+    db: &Database,
+    catalog: &[cache::Mod],
+    name: &str,
+) -> Result<UpdateStatus, AppError> {
+    let installed = db.get_last_installed_version(name)?;
+    let Some(available) = find_in_catalog(catalog, name).and_then(|m| m.version.as_ref()) else {
+        return Ok(UpdateStatus::UpToDate);
+    };
+    if installed.is_empty() {
+        return Ok(UpdateStatus::UpToDate);
+    }
+    Ok(UpdateStatus::check(
+        &installed,
+        [available],
+        db.get_mod_pin(name)?.as_ref(),
+    ))
+}
+
+/// Compares the tracked mods among `names` (every tracked mod when `None`)
+/// against `catalog` and `releases`. Mods whose installed version isn't known
+/// are skipped, since there's nothing to compare.
+pub fn plan(
+    db: &Database,
+    catalog: &[cache::Mod],
+    releases: &LatestReleases,
+    names: Option<&[String]>,
+) -> Result<UpdatePlan, AppError> {
+    let mut plan = UpdatePlan::default();
+
+    for installed_mod in db
+        .get_installed_mods()?
+        .into_iter()
+        .filter(|m| names.is_none_or(|names| names.contains(&m.name)))
+    {
+        let Some(from_version) = installed_mod
+            .current_version
+            .clone()
+            .filter(|v| !v.is_empty())
+        else {
+            continue;
+        };
+        let pin = db.get_mod_pin(&installed_mod.name)?;
+
+        let (source, status, declared) = match ModType::from_mod_name(&installed_mod.name) {
+            Some(mod_type) => {
+                let source = match mod_type {
+                    ModType::Steamodded => UpdateSource::Steamodded,
+                    ModType::Talisman => UpdateSource::Talisman,
+                };
+                let status =
+                    UpdateStatus::check(&from_version, releases.get(&mod_type), pin.as_ref());
+                (source, status, Vec::new())
+            }
+            None => match find_in_catalog(catalog, &installed_mod.name) {
+                Some(catalog_mod) => (
+                    UpdateSource::Catalog,
+                    UpdateStatus::check(&from_version, &catalog_mod.version, pin.as_ref()),
+                    dependencies::catalog_dependencies(catalog_mod),
+                ),
+                None => continue,
+            },
+        };
+        let to_version = match status {
+            UpdateStatus::UpToDate => continue,
+            UpdateStatus::Held(held) => {
+                plan.held.push(held);
+                continue;
+            }
+            UpdateStatus::Available { version } => version,
+        };
+
+        plan.updates.push(PlannedUpdate {
+            new_dependencies: declared
+                .into_iter()
+                .filter(|dep| {
+                    !installed_mod
+                        .dependencies
+                        .iter()
+                        .any(|spec| dependencies::spec_mentions(spec, dep))
+                })
+                .collect(),
+            name: installed_mod.name,
+            path: installed_mod.path,
+            from_version,
+            to_version,
+            source,
+        });
+    }
+
+    // New dependencies may be satisfied by what's installed once the other
+    // updates are through
+    let mut after = InstalledSet::from_database(db)?;
+    for update in &plan.updates {
+        after.add_version(&update.name, &update.to_version);
+    }
+    for update in &plan.updates {
+        let resolution =
+            dependencies::resolve_specs(&update.name, &update.new_dependencies, &after, catalog);
+        for title in resolution.install {
+            if !plan.install.contains(&title) {
+                plan.install.push(title);
+            }
+        }
+        plan.unmet.extend(resolution.unmet);
+    }
+
+    Ok(plan)
+}
+
+/// Applies `plan`: installs the new dependencies first, then updates each
+/// mod, keeping its enabled state. Every item is attempted even if others
+/// fail, unless `job` is cancelled.
+pub async fn apply(
+    db: &Mutex<Database>,
+    catalog: &[cache::Mod],
+    plan: &UpdatePlan,
+    job: &DownloadJob,
+) -> Result<UpdateReport, AppError> {
+    let mods_dir = db.lock()?.get_mods_dir()?;
+    let mut report = UpdateReport::default();
+
+    for title in &plan.install {
+        if report.installed.contains(title) {
+            continue;
+        }
+        match dependencies::install_with_dependencies(db, catalog, title, false, job).await {
+            Ok(installed) => report.installed.extend(installed),
+            Err(e) if job.is_cancelled() => return Err(e),
+            Err(e) => report.failed.push(UpdateFailure {
+                name: title.clone(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    for update in &plan.updates {
+        let result = match update.source {
+            UpdateSource::Catalog => update_from_catalog(db, &mods_dir, catalog, update, job).await,
+            UpdateSource::Steamodded => {
+                update_framework(db, &mods_dir, ModType::Steamodded, update, job).await
+            }
+            UpdateSource::Talisman => {
+                update_framework(db, &mods_dir, ModType::Talisman, update, job).await
+            }
+        };
+        match operations::record_locked(
+            db,
+            OperationKind::Update,
+            &[AffectedMod::new(
+                &update.name,
+                Some(update.to_version.clone()),
+            )],
+            (!update.from_version.is_empty())
+                .then(|| format!("From {}", update.from_version))
+                .as_deref(),
+            result,
+        ) {
+            Ok(()) => report.updated.push(update.name.clone()),
+            Err(e) if job.is_cancelled() => return Err(e),
+            Err(e) => report.failed.push(UpdateFailure {
+                name: update.name.clone(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    Ok(report)
+}
+
+async fn update_from_catalog(
+    db: &Mutex<Database>,
+    mods_dir: &Path,
+    catalog: &[cache::Mod],
+    update: &PlannedUpdate,
+    job: &DownloadJob,
+) -> Result<(), AppError> {
+    let catalog_mod =
+        find_in_catalog(catalog, &update.name).ok_or_else(|| AppError::ModNotFound {
+            mod_name: update.name.clone(),
+            version: update.to_version.clone(),
+        })?;
+
+    log::info!(
+        "Updating {} from {} to {}",
+        update.name,
+        update.from_version,
+        update.to_version
+    );
+    let expected_hash = version_history::recorded_archive(
+        &*db.lock()?,
+        &catalog_mod.download_url,
+        catalog_mod.version.as_deref(),
+    )?;
+    let (path, source) = reinstall_in_place(
+        mods_dir,
+        Path::new(&update.path),
+        catalog_mod.download_url.clone(),
+        expected_hash.as_deref(),
+        job,
+    )
+    .await?;
+
+    let detected = local_mod_detection::detect_mod(&path);
+    let path = path.to_string_lossy();
+    let db = db.lock()?;
+    db.record_install(
+        &update.name,
+        &path,
+        &dependencies::tracked_dependencies(catalog_mod, detected.as_ref()),
+        catalog_mod.version.clone(),
+        &source,
+    )
+}
+
+async fn update_framework(
+    db: &Mutex<Database>,
+    mods_dir: &Path,
+    mod_type: ModType,
+    update: &PlannedUpdate,
+    job: &DownloadJob,
+) -> Result<(), AppError> {
+    log::info!(
+        "Updating {mod_type} from {} to {}",
+        update.from_version,
+        update.to_version
+    );
+    let (path, source) = reinstall_framework(
+        mods_dir,
+        mod_type,
+        Path::new(&update.path),
+        &update.to_version,
+        job,
+    )
+    .await?;
+
+    db.lock()?.replace_install(
+        &update.name,
+        &update.name,
+        &path,
+        &[],
+        Some(update.to_version.clone()),
+        &source,
+    )
+}
+
+/// Replaces the mod at `old_path` with the archive at `url`, extracted into
+/// the same folder and left enabled or disabled as before.
+pub(crate) async fn reinstall_in_place(
+    mods_dir: &Path,
+    old_path: &Path,
+    url: String,
+    expected_hash: Option<&str>,
+    job: &DownloadJob,
+) -> Result<(PathBuf, ModSource), AppError> {
+    let folder_name = old_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned());
+    let enabled = installer::is_mod_enabled(old_path);
+
+    let (path, source) =
+        installer::install_mod_with_source(mods_dir, url, folder_name, expected_hash, None, job)
+            .await?;
+    installer::set_mod_enabled(&path, enabled)?;
+    Ok((path, source))
+}
+
+/// Replaces the Steamodded or Talisman install at `old_path` with `version`,
+/// left enabled or disabled as before.
+pub(crate) async fn reinstall_framework(
+    mods_dir: &Path,
+    mod_type: ModType,
+    old_path: &Path,
+    version: &str,
+    job: &DownloadJob,
+) -> Result<(String, ModSource), AppError> {
+    let enabled = installer::is_mod_enabled(old_path);
+
+    let (path, source) = ModInstaller::new(Some(mods_dir), mod_type)
+        .install_version_with_source(version, job)
+        .await?;
+    // Steamodded releases extract to a directory named after the release
+    if Path::new(&path) != old_path && old_path.exists() {
+        installer::uninstall_mod(mods_dir, old_path.to_path_buf())?;
+    }
+    installer::set_mod_enabled(Path::new(&path), enabled)?;
+    Ok((path, source))
+}
@@ -0,0 +1,214 @@
+use crate::errors::AppError;
+use std::path::Path;
+
+/// A value in a Valve `KeyValues` (VDF) document, the format of Steam's
+/// `libraryfolders.vdf` and `appmanifest_*.acf` files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Vdf {
+    Value(String),
+    Table(Table),
+}
+
+impl Vdf {
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Value(value) => Some(value),
+            Self::Table(_) => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_table(&self) -> Option<&Table> {
+        match self {
+            Self::Value(_) => None,
+            Self::Table(table) => Some(table),
+        }
+    }
+}
+
+/// The entries between a pair of braces, in file order. Keys aren't unique
+/// in VDF, and Steam compares them case-insensitively.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table(Vec<(String, Vdf)>);
+
+impl Table {
+    /// The first entry named `key`, ignoring case.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Vdf> {
+        self.0
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+    }
+
+    #[must_use]
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(Vdf::as_str)
+    }
+
+    #[must_use]
+    pub fn get_table(&self, key: &str) -> Option<&Table> {
+        self.get(key).and_then(Vdf::as_table)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Vdf)> {
+        self.0.iter().map(|(key, value)| (key.as_str(), value))
+    }
+}
+
+/// Parses a VDF document into its top-level entries. Quoted and bare
+/// tokens, escape sequences, `//` comments and `[$WIN32]` style conditions
+/// are understood; conditions are ignored, so every entry is kept.
+pub fn parse(text: &str) -> Result<Table, AppError> {
+    Parser {
+        chars: text.chars().peekable(),
+        line: 1,
+    }
+    .entries(false)
+}
+
+/// Reads and parses the VDF file at `path`.
+pub fn read(path: &Path) -> Result<Table, AppError> {
+    let text = std::fs::read_to_string(path).map_err(|e| AppError::FileRead {
+        path: path.to_path_buf(),
+        source: e.to_string(),
+    })?;
+    parse(&text).map_err(|e| match e {
+        AppError::Serialization { format, source } => AppError::Serialization {
+            format,
+            source: format!("{}: {source}", path.display()),
+        },
+        e => e,
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Text(String),
+    Condition,
+}
+
+#[derive(Debug)]
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> AppError {
+        AppError::Serialization {
+            format: "VDF".to_string(),
+            source: format!("line {}: {message}", self.line),
+        }
+    }
+
+    /// Reads entries up to the closing brace of the current table, or to the
+    /// end of the text at the top level.
+    fn entries(&mut self, nested: bool) -> Result<Table, AppError> {
+        let mut entries = Vec::new();
+        loop {
+            let key = match self.token()? {
+                Some(Token::Text(key)) => key,
+                Some(Token::Close) if nested => return Ok(Table(entries)),
+                None if !nested => return Ok(Table(entries)),
+                None => return Err(self.error("missing closing brace")),
+                Some(Token::Condition) => continue,
+                Some(Token::Open | Token::Close) => {
+                    return Err(self.error("expected a key"));
+                }
+            };
+            let value = match self.token()? {
+                Some(Token::Text(value)) => Vdf::Value(value),
+                Some(Token::Open) => Vdf::Table(self.entries(true)?),
+                _ => return Err(self.error(&format!("expected a value for '{key}'"))),
+            };
+            entries.push((key, value));
+        }
+    }
+
+    fn token(&mut self) -> Result<Option<Token>, AppError> {
+        self.skip_whitespace_and_comments();
+        let Some(c) = self.chars.next() else {
+            return Ok(None);
+        };
+        Ok(Some(match c {
+            '{' => Token::Open,
+            '}' => Token::Close,
+            '"' => Token::Text(self.quoted()?),
+            '[' => {
+                for c in self.chars.by_ref() {
+                    match c {
+                        ']' => return Ok(Some(Token::Condition)),
+                        '\n' => break,
+                        _ => {}
+                    }
+                }
+                return Err(self.error("unterminated condition"));
+            }
+            c => {
+                let mut text = c.to_string();
+                while let Some(&c) = self.chars.peek() {
+                    if c.is_whitespace() || matches!(c, '{' | '}' | '"') {
+                        break;
+                    }
+                    text.push(c);
+                    self.chars.next();
+                }
+                Token::Text(text)
+            }
+        }))
+    }
+
+    fn quoted(&mut self) -> Result<String, AppError> {
+        let mut text = String::new();
+        while let Some(c) = self.chars.next() {
+            match c {
+                '"' => return Ok(text),
+                '\\' => match self.chars.next() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some(c @ ('\\' | '"')) => text.push(c),
+                    Some(c) => {
+                        text.push('\\');
+                        text.push(c);
+                    }
+                    None => break,
+                },
+                '\n' => {
+                    self.line += 1;
+                    text.push(c);
+                }
+                c => text.push(c),
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c == '\n' {
+                self.line += 1;
+                self.chars.next();
+            } else if c.is_whitespace() {
+                self.chars.next();
+            } else if c == '/' {
+                let mut ahead = self.chars.clone();
+                ahead.next();
+                if ahead.peek() != Some(&'/') {
+                    return;
+                }
+                for c in self.chars.by_ref() {
+                    if c == '\n' {
+                        self.line += 1;
+                        break;
+                    }
+                }
+            } else {
+                return;
+            }
+        }
+    }
+}
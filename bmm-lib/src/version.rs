@@ -0,0 +1,179 @@
+use crate::errors::AppError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// A Steamodded-style version, `major.minor.patch~rev`.
+///
+/// Missing components default to zero, so `1.0` == `1.0.0`. Whatever follows
+/// the numbers is a revision marking a pre-release: `1.0.0~BETA-0827c` sorts
+/// before `1.0.0`, and revisions of the same release are compared as strings,
+/// ignoring case since release tags and mod headers spell them differently.
+/// Release tags are accepted too, so `v2.3`, `1.0.0-beta-0827c` and date tags
+/// such as `2024-03-27` all parse.
+#[derive(Debug, Clone)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub rev: Option<String>,
+}
+
+impl Version {
+    #[must_use]
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            rev: None,
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let body = s.strip_prefix(['v', 'V']).unwrap_or(s);
+        let invalid = || AppError::InvalidVersion(s.to_string());
+
+        if let Some(date) = parse_date(body) {
+            return Ok(date);
+        }
+
+        let mut components = [0u64; 3];
+        let mut rest = body;
+        for (i, component) in components.iter_mut().enumerate() {
+            if i > 0 {
+                match rest.strip_prefix('.') {
+                    Some(next) if next.starts_with(|c: char| c.is_ascii_digit()) => rest = next,
+                    _ => break,
+                }
+            }
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            if digits == 0 {
+                return Err(invalid());
+            }
+            *component = rest[..digits].parse().map_err(|_| invalid())?;
+            rest = &rest[digits..];
+        }
+
+        let rev = rest.trim_start_matches(['~', '-', '+', '.']);
+        if rev.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+
+        let [major, minor, patch] = components;
+        Ok(Self {
+            major,
+            minor,
+            patch,
+            rev: (!rev.is_empty()).then(|| rev.to_string()),
+        })
+    }
+}
+
+/// Date tags, `YYYY-MM-DD` optionally followed by a revision, are read as
+/// `YYYY.MM.DD` so they order chronologically.
+fn parse_date(s: &str) -> Option<Version> {
+    let mut parts = s.splitn(4, '-');
+    let mut date = [0u64; 3];
+    for (component, len) in date.iter_mut().zip([4, 2, 2]) {
+        let part = parts.next()?;
+        if part.len() != len || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        *component = part.parse().ok()?;
+    }
+
+    let [major, minor, patch] = date;
+    Some(Version {
+        major,
+        minor,
+        patch,
+        rev: parts.next().filter(|r| !r.is_empty()).map(str::to_string),
+    })
+}
+
+/// Whether `candidate` is newer than `current`. When either of them can't be
+/// parsed there's no telling, so any difference counts as an update.
+#[must_use]
+pub fn is_update(current: &str, candidate: &str) -> bool {
+    match (current.parse::<Version>(), candidate.parse::<Version>()) {
+        (Ok(current), Ok(candidate)) => candidate > current,
+        _ => current.trim() != candidate.trim(),
+    }
+}
+
+/// Sorts version strings newest first. Strings that don't parse keep their
+/// relative order after all of the ones that do.
+pub fn sort_newest_first(versions: &mut [String]) {
+    versions.sort_by_cached_key(|v| std::cmp::Reverse(v.parse::<Version>().ok()));
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (&self.rev, &other.rev) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.to_ascii_lowercase().cmp(&b.to_ascii_lowercase()),
+            })
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Version {}
+
+impl Hash for Version {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.major, self.minor, self.patch).hash(state);
+        self.rev
+            .as_ref()
+            .map(|rev| rev.to_ascii_lowercase())
+            .hash(state);
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(rev) = &self.rev {
+            write!(f, "~{rev}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
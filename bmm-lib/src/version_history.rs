@@ -0,0 +1,270 @@
+use crate::archive_cache::{self, ArchiveCache};
+use crate::database::Database;
+use crate::download::DownloadJob;
+use crate::errors::AppError;
+use crate::operations::{self, AffectedMod, OperationKind};
+use crate::paths::Paths;
+use crate::smods_installer::ModType;
+use crate::updates;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// How many installed versions of each mod are remembered per installation,
+/// including the current one.
+pub const KEPT_VERSIONS: usize = 3;
+
+/// A version of a mod that was installed at some point.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModVersion {
+    pub name: String,
+    /// Empty when the version wasn't known at install time.
+    pub version: String,
+    pub download_url: String,
+    pub archive_hash: String,
+    pub dependencies: Vec<String>,
+    /// Seconds since the Unix epoch.
+    pub installed_at: u64,
+    /// Whether this is the version installed right now.
+    pub current: bool,
+    /// Whether the archive is kept locally, so rolling back to this version
+    /// doesn't need the network.
+    pub archived: bool,
+}
+
+/// The archives of the versions in the history, stored under their SHA-256 in
+/// the data directory. Unlike the [`ArchiveCache`], nothing is evicted from
+/// here until the version drops out of the history.
+#[derive(Debug, Clone)]
+pub struct VersionStore {
+    dir: PathBuf,
+}
+
+impl VersionStore {
+    pub fn open() -> Result<Self, AppError> {
+        Self::at(Paths::get()?.version_store_dir())
+    }
+
+    pub fn at(dir: PathBuf) -> Result<Self, AppError> {
+        fs::create_dir_all(&dir).map_err(|e| AppError::DirCreate {
+            path: dir.clone(),
+            source: e.to_string(),
+        })?;
+        Ok(Self { dir })
+    }
+
+    /// The stored archive with this hash, if there is one.
+    #[must_use]
+    pub fn get(&self, sha256: &str) -> Option<PathBuf> {
+        let sha256 = sha256.trim().to_lowercase();
+        let path = self.dir.join(&sha256);
+        (archive_cache::is_archive_name(&sha256) && path.is_file()).then_some(path)
+    }
+
+    /// Copies the archive with this hash out of the archive cache, unless it's
+    /// already stored.
+    fn keep(&self, sha256: &str) -> Result<(), AppError> {
+        if self.get(sha256).is_some() {
+            return Ok(());
+        }
+        let Some(cached) = ArchiveCache::open()?.get(sha256) else {
+            return Err(AppError::InvalidState(format!(
+                "Archive {sha256} is no longer in the archive cache"
+            )));
+        };
+
+        let path = self.dir.join(sha256);
+        let temp = path.with_extension("tmp");
+        fs::copy(&cached, &temp).map_err(|e| AppError::FileCopy {
+            source: cached.display().to_string(),
+            dest: temp.display().to_string(),
+            source_error: e.to_string(),
+        })?;
+        fs::rename(&temp, &path).map_err(|e| AppError::FileWrite {
+            path,
+            source: e.to_string(),
+        })
+    }
+
+    fn remove(&self, sha256: &str) -> Result<(), AppError> {
+        let Some(path) = self.get(sha256) else {
+            return Ok(());
+        };
+        fs::remove_file(&path).map_err(|e| AppError::FileWrite {
+            path,
+            source: e.to_string(),
+        })
+    }
+}
+
+/// Stores the archive of a version that was just recorded, and drops the
+/// archives of versions that fell out of the history. Failures are only
+/// logged; the history itself is already recorded.
+pub(crate) fn retain_archives(kept: &str, dropped: &[String]) {
+    let store = match VersionStore::open() {
+        Ok(store) => store,
+        Err(e) => {
+            log::warn!("Version store unavailable: {e}");
+            return;
+        }
+    };
+    if let Err(e) = store.keep(kept) {
+        log::warn!("Failed to keep the archive {kept} for rollbacks: {e}");
+    }
+    drop_archives(&store, dropped);
+}
+
+/// Drops the archives of versions that no history refers to anymore, e.g.
+/// after an installation was removed. Failures are only logged.
+pub(crate) fn forget_archives(dropped: &[String]) {
+    match VersionStore::open() {
+        Ok(store) => drop_archives(&store, dropped),
+        Err(e) => log::warn!("Version store unavailable: {e}"),
+    }
+}
+
+fn drop_archives(store: &VersionStore, dropped: &[String]) {
+    for sha256 in dropped {
+        if let Err(e) = store.remove(sha256) {
+            log::warn!("Failed to remove the stored archive {sha256}: {e}");
+        }
+    }
+}
+
+/// The remembered versions of `name`, newest first.
+pub fn history(db: &Database, name: &str) -> Result<Vec<ModVersion>, AppError> {
+    let mut versions = db.get_mod_versions(name)?;
+    if let Ok(store) = VersionStore::open() {
+        for version in &mut versions {
+            version.archived = store.get(&version.archive_hash).is_some();
+        }
+    }
+    Ok(versions)
+}
+
+/// Reinstalls the version of `name` whose archive hashes to `archive_hash`,
+/// keeping the mod enabled or disabled, and makes it the current version. The
+/// stored archive is used when there is one, otherwise it's downloaded again
+/// and has to hash the same as it did back then.
+pub async fn rollback(
+    db: &Mutex<Database>,
+    name: &str,
+    archive_hash: &str,
+    job: &DownloadJob,
+) -> Result<ModVersion, AppError> {
+    let (mods_dir, installed, target) = {
+        let db = db.lock()?;
+        let target = history(&db, name)?
+            .into_iter()
+            .find(|v| v.archive_hash.eq_ignore_ascii_case(archive_hash.trim()))
+            .ok_or_else(|| AppError::ModNotFound {
+                mod_name: name.to_string(),
+                version: archive_hash.to_string(),
+            })?;
+        (db.get_mods_dir()?, db.get_mod_details(name)?, target)
+    };
+    if target.current {
+        return Ok(target);
+    }
+
+    if target.archived
+        && let Err(e) = restore_to_cache(&target.download_url, &target.archive_hash)
+    {
+        log::warn!("Failed to restore the stored archive for {name}, downloading it instead: {e}");
+    }
+
+    log::info!(
+        "Rolling back {name} to {}",
+        if target.version.is_empty() {
+            &target.archive_hash
+        } else {
+            &target.version
+        }
+    );
+    let result = restore(
+        db,
+        &mods_dir,
+        name,
+        Path::new(&installed.path),
+        &target,
+        job,
+    )
+    .await;
+    operations::record_locked(
+        db,
+        OperationKind::Rollback,
+        &[AffectedMod::new(name, Some(target.version.clone()))],
+        installed
+            .current_version
+            .filter(|v| !v.is_empty())
+            .map(|v| format!("From {v}"))
+            .as_deref(),
+        result,
+    )?;
+
+    Ok(ModVersion {
+        current: true,
+        ..target
+    })
+}
+
+async fn restore(
+    db: &Mutex<Database>,
+    mods_dir: &Path,
+    name: &str,
+    old_path: &Path,
+    target: &ModVersion,
+    job: &DownloadJob,
+) -> Result<(), AppError> {
+    let version = Some(target.version.clone()).filter(|v| !v.is_empty());
+    if let Some(mod_type) = ModType::from_mod_name(name) {
+        let (path, source) =
+            updates::reinstall_framework(mods_dir, mod_type, old_path, &target.version, job)
+                .await?;
+        db.lock()?
+            .replace_install(name, name, &path, &target.dependencies, version, &source)
+    } else {
+        let (path, source) = updates::reinstall_in_place(
+            mods_dir,
+            old_path,
+            target.download_url.clone(),
+            Some(&target.archive_hash),
+            job,
+        )
+        .await?;
+        let path = path.to_string_lossy();
+        let db = db.lock()?;
+        db.record_install(name, &path, &target.dependencies, version, &source)
+    }
+}
+
+/// Puts a stored archive back into the archive cache, where installs look
+/// for it.
+fn restore_to_cache(download_url: &str, archive_hash: &str) -> Result<(), AppError> {
+    let path = VersionStore::open()?.get(archive_hash).ok_or_else(|| {
+        AppError::InvalidState(format!("Archive {archive_hash} is no longer stored"))
+    })?;
+    ArchiveCache::open()?.add(download_url, &path)
+}
+
+/// The archive recorded for `version` of the mod downloaded from `url`, for
+/// installs from the catalog, which has no hashes. It's only returned while
+/// the archive is cached or stored, so installing a version again doesn't
+/// download it, but a URL that serves another archive for the same version
+/// by now is downloaded instead of refused.
+pub fn recorded_archive(
+    db: &Database,
+    url: &str,
+    version: Option<&str>,
+) -> Result<Option<String>, AppError> {
+    let Some(version) = version.filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    let Some(sha256) = db.recorded_archive_hash(url, version)? else {
+        return Ok(None);
+    };
+    let available = ArchiveCache::open().is_ok_and(|cache| cache.get(&sha256).is_some())
+        || restore_to_cache(url, &sha256).is_ok();
+    Ok(available.then_some(sha256))
+}
@@ -0,0 +1,409 @@
+//! Parses dependency specs and resolves them against installed mods and a
+//! small catalog, and installs from a catalog whose archives are cached.
+
+use bmm_lib::archive_cache::ArchiveCache;
+use bmm_lib::cache::{ColorPair, Mod};
+use bmm_lib::checksum;
+use bmm_lib::database::Database;
+use bmm_lib::dependencies::{
+    self, Comparison, DependencySpec, InstalledSet, parse_provided, resolve_specs, spec_mentions,
+};
+use bmm_lib::download::DownloadJob;
+use bmm_lib::errors::AppError;
+use bmm_lib::installer::ModSource;
+use bmm_lib::local_mod_detection::DetectedMod;
+use bmm_lib::paths::{Paths, PathsOrigin};
+use bmm_lib::version::Version;
+use serde_json::json;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use zip::write::SimpleFileOptions;
+
+fn catalog_mod(title: &str, version: Option<&str>, steamodded: bool, talisman: bool) -> Mod {
+    Mod {
+        title: title.to_string(),
+        description: String::new(),
+        image: None,
+        categories: HashSet::new(),
+        colors: ColorPair {
+            color1: String::new(),
+            color2: String::new(),
+        },
+        installed: false,
+        requires_steamodded: steamodded,
+        requires_talisman: talisman,
+        publisher: String::new(),
+        repo: String::new(),
+        download_url: format!("https://example.com/{title}.zip"),
+        folder_name: None,
+        version: version.map(str::to_string),
+        last_updated: 0,
+    }
+}
+
+fn detected(id: &str, version: Option<&str>, provides: &[&str]) -> DetectedMod {
+    DetectedMod {
+        name: id.to_string(),
+        id: id.to_string(),
+        author: Vec::new(),
+        description: String::new(),
+        prefix: String::new(),
+        version: version.map(str::to_string),
+        path: format!("/game/Mods/{id}"),
+        dependencies: Vec::new(),
+        conflicts: Vec::new(),
+        provides: provides.iter().map(ToString::to_string).collect(),
+        catalog_match: None,
+        is_duplicate: false,
+    }
+}
+
+/// The ids `detected` answers to, as if it were the only mod installed.
+fn only_installed(detected: &DetectedMod) -> InstalledSet {
+    let mut set = InstalledSet::default();
+    set.add_detected(detected);
+    set
+}
+
+fn specs(specs: &[&str]) -> Vec<String> {
+    specs.iter().map(ToString::to_string).collect()
+}
+
+fn version(version: &str) -> Version {
+    version.parse().unwrap()
+}
+
+#[test]
+fn parses_alternatives_and_constraints() {
+    let spec: DependencySpec = "Talisman (>=2.0, <<3) | Cryptid (>>0.5)(<=1.0)"
+        .parse()
+        .unwrap();
+    assert_eq!(spec.alternatives.len(), 2);
+
+    let talisman = &spec.alternatives[0];
+    assert_eq!(talisman.id, "Talisman");
+    assert_eq!(
+        talisman
+            .constraints
+            .iter()
+            .map(|c| c.comparison)
+            .collect::<Vec<_>>(),
+        [Comparison::GreaterOrEqual, Comparison::Less]
+    );
+    assert_eq!(talisman.constraints[1].version, Version::new(3, 0, 0));
+
+    let cryptid = &spec.alternatives[1];
+    assert_eq!(cryptid.id, "Cryptid");
+    assert_eq!(cryptid.constraints.len(), 2);
+    assert_eq!(cryptid.constraints[1].comparison, Comparison::LessOrEqual);
+
+    let bare: DependencySpec = "Steamodded".parse().unwrap();
+    assert!(bare.alternatives[0].constraints.is_empty());
+}
+
+#[test]
+fn rejects_malformed_specs() {
+    for spec in [
+        "",
+        "Talisman | ",
+        "(>=1.0)",
+        "Jen's Almanac",
+        "Talisman >=2.0",
+        "Talisman (>=2.0",
+        "Talisman (>=two)",
+    ] {
+        assert!(
+            matches!(
+                spec.parse::<DependencySpec>(),
+                Err(AppError::InvalidDependency { .. } | AppError::InvalidVersion(_))
+            ),
+            "{spec:?} parsed"
+        );
+    }
+}
+
+#[test]
+fn matches_each_comparison() {
+    let matches = |constraint: &str, candidate: &str| {
+        format!("Talisman ({constraint})")
+            .parse::<DependencySpec>()
+            .unwrap()
+            .alternatives[0]
+            .matches_version(Some(&version(candidate)))
+    };
+
+    assert!(matches("<<2.0", "1.9.9"));
+    assert!(!matches("<<2.0", "2.0.0"));
+    assert!(matches("<2.0", "1.0"));
+    assert!(matches("<=2.0", "2.0.0"));
+    assert!(matches("==2.0", "2.0.0"));
+    assert!(matches("=2.0", "2.0.0"));
+    assert!(matches("2.0", "2.0.0"));
+    assert!(!matches("2.0", "2.0.1"));
+    assert!(matches(">=1.0.0~BETA-0827c", "1.0.0"));
+    assert!(!matches(">=1.0.0", "1.0.0~BETA-0827c"));
+    assert!(matches(">>1.0.9", "1.0.10"));
+    assert!(!matches(">1.0.0", "1.0.0"));
+}
+
+#[test]
+fn gives_unknown_versions_the_benefit_of_the_doubt() {
+    let spec: DependencySpec = "Talisman (>=2.0)".parse().unwrap();
+    let requirement = &spec.alternatives[0];
+    assert!(requirement.matches_version(None));
+    assert!(requirement.matches_version(Some(&version("2.1"))));
+    assert!(!requirement.matches_version(Some(&version("1.9"))));
+}
+
+#[test]
+fn compares_ids_without_case_or_punctuation() {
+    assert!(spec_mentions(
+        "Jen's_Almanac (>=1.0) | Cryptid",
+        "jensalmanac"
+    ));
+    assert!(spec_mentions("Talisman | Cryptid", "CRYPTID"));
+    assert!(!spec_mentions("Talisman", "Cryptid"));
+    // unparseable specs fall back to comparing the whole string
+    assert!(spec_mentions("Jen's Almanac", "JensAlmanac"));
+}
+
+#[test]
+fn parses_provided_mods() {
+    assert_eq!(
+        parse_provided("Talisman").unwrap(),
+        ("Talisman".into(), None)
+    );
+    assert_eq!(
+        parse_provided("Talisman (2.1)").unwrap(),
+        ("Talisman".into(), Some(version("2.1")))
+    );
+    assert!(parse_provided("Talisman (>=2.1)").is_err());
+    assert!(parse_provided("Talisman (2.1, 2.2)").is_err());
+}
+
+#[test]
+fn plans_dependencies_before_the_mod_that_needs_them() {
+    let catalog = [
+        catalog_mod("Cryptid", Some("0.5.0"), true, true),
+        catalog_mod("Talisman", Some("2.1.0"), true, false),
+        catalog_mod("Steamodded", Some("1.0.0"), false, false),
+    ];
+    let plan = dependencies::resolve("cryptid", &InstalledSet::default(), &catalog).unwrap();
+    assert_eq!(plan.install, ["Steamodded", "Talisman", "Cryptid"]);
+    assert!(plan.unmet.is_empty());
+}
+
+#[test]
+fn skips_what_is_already_installed() {
+    let catalog = [
+        catalog_mod("Cryptid", Some("0.5.0"), true, true),
+        catalog_mod("Talisman", Some("2.1.0"), false, false),
+        catalog_mod("Steamodded", Some("1.0.0"), false, false),
+    ];
+    let mut installed = only_installed(&detected("Steamodded", Some("1.0.0"), &[]));
+    installed.add_detected(&detected("Talisman", Some("2.0.0"), &[]));
+    let plan = dependencies::resolve("Cryptid", &installed, &catalog).unwrap();
+    assert_eq!(plan.install, ["Cryptid"]);
+}
+
+#[test]
+fn counts_provided_ids_as_installed() {
+    let installed = only_installed(&detected(
+        "Cartomancer",
+        Some("4.0"),
+        &["Talisman (2.1)", "Perkeo"],
+    ));
+    let plan = resolve_specs(
+        "Jokers",
+        &specs(&["Talisman (>=2.0)", "Perkeo (>=4)", "Cartomancer"]),
+        &installed,
+        &[],
+    );
+    assert!(plan.install.is_empty());
+    assert!(plan.unmet.is_empty(), "{:?}", plan.unmet);
+
+    let plan = resolve_specs("Jokers", &specs(&["Talisman (>=3.0)"]), &installed, &[]);
+    assert_eq!(plan.unmet.len(), 1);
+}
+
+#[test]
+fn assumes_the_game_and_lovely_are_present() {
+    let plan = resolve_specs(
+        "Jokers",
+        &specs(&["Balatro (>=1.0.1)", "Lovely"]),
+        &InstalledSet::default(),
+        &[],
+    );
+    assert!(plan.install.is_empty());
+    assert!(plan.unmet.is_empty());
+}
+
+#[test]
+fn picks_the_first_alternative_the_catalog_satisfies() {
+    let catalog = [
+        catalog_mod("Talisman", Some("1.0.0"), false, false),
+        catalog_mod("Cryptid", Some("0.5.0"), false, false),
+        catalog_mod("Perkeo", Some("1.0.0"), false, false),
+    ];
+    let plan = resolve_specs(
+        "Jokers",
+        &specs(&["Talisman (>=2.0) | Cryptid | Perkeo"]),
+        &InstalledSet::default(),
+        &catalog,
+    );
+    assert_eq!(plan.install, ["Cryptid"]);
+
+    // an installed alternative wins over any the catalog has
+    let installed = only_installed(&detected("Perkeo", None, &[]));
+    let plan = resolve_specs(
+        "Jokers",
+        &specs(&["Talisman (>=2.0) | Cryptid | Perkeo"]),
+        &installed,
+        &catalog,
+    );
+    assert!(plan.install.is_empty());
+}
+
+#[test]
+fn reports_unmet_dependencies() {
+    let catalog = [catalog_mod("Talisman", Some("1.0.0"), false, false)];
+    let plan = resolve_specs(
+        "Jokers",
+        &specs(&["Talisman (>=2.0) | Missing", "Talisman (", "Perkeo"]),
+        &InstalledSet::default(),
+        &catalog,
+    );
+    assert!(plan.install.is_empty());
+    assert_eq!(plan.unmet.len(), 3);
+    assert!(plan.unmet.iter().all(|unmet| unmet.dependent == "Jokers"));
+    assert_eq!(
+        plan.unmet[0].reason,
+        "the catalog only has Talisman 1.0.0; Missing is not in the catalog"
+    );
+    assert_eq!(plan.unmet[1].spec, "Talisman (");
+    assert_eq!(plan.unmet[2].reason, "Perkeo is not in the catalog");
+}
+
+#[test]
+fn resolves_dependency_cycles_once() {
+    let catalog = [
+        catalog_mod("Steamodded", Some("1.0.0"), false, true),
+        catalog_mod("Talisman", Some("2.1.0"), true, false),
+    ];
+    let plan = dependencies::resolve("Talisman", &InstalledSet::default(), &catalog).unwrap();
+    assert_eq!(plan.install, ["Steamodded", "Talisman"]);
+    assert!(plan.unmet.is_empty());
+}
+
+#[test]
+fn fails_for_a_mod_that_is_not_in_the_catalog() {
+    assert!(matches!(
+        dependencies::resolve("Missing", &InstalledSet::default(), &[]),
+        Err(AppError::ModNotFound { .. })
+    ));
+}
+
+/// Puts an archive of `catalog_mod` whose metadata depends on
+/// `dependencies` into the archive cache, and into the version history as if
+/// it had been installed before, so installing it never downloads anything.
+fn cache_archive(db: &Database, dir: &Path, catalog_mod: &Mod, dependencies: &[&str]) {
+    let title = &catalog_mod.title;
+    let archive = dir.join(format!("{title}.zip"));
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+    let metadata = json!({
+        "id": title,
+        "name": title,
+        "author": ["Jimbo"],
+        "description": "",
+        "prefix": title.to_lowercase(),
+        "main_file": "main.lua",
+        "version": catalog_mod.version,
+        "dependencies": dependencies,
+    });
+    zip.start_file(
+        format!("{title}/{title}.json"),
+        SimpleFileOptions::default(),
+    )
+    .unwrap();
+    write!(zip, "{metadata}").unwrap();
+    zip.start_file(format!("{title}/main.lua"), SimpleFileOptions::default())
+        .unwrap();
+    writeln!(zip, "-- {title}").unwrap();
+    zip.finish().unwrap();
+
+    ArchiveCache::open()
+        .unwrap()
+        .add(&catalog_mod.download_url, &archive)
+        .unwrap();
+    let source = ModSource {
+        download_url: catalog_mod.download_url.clone(),
+        archive_hash: checksum::sha256_hex(&fs::read(&archive).unwrap()),
+        files: Vec::new(),
+    };
+    db.record_install(title, "", &[], catalog_mod.version.clone(), &source)
+        .unwrap();
+    db.remove_installed_mod(title).unwrap();
+}
+
+#[tokio::test]
+async fn installs_the_dependencies_a_mod_declares_before_it() {
+    let home = tempfile::tempdir().unwrap();
+    Paths::init(Paths::rooted(home.path(), PathsOrigin::Override)).unwrap();
+    let mods = home.path().join("Mods");
+    let game = home.path().join("game");
+    let resources = game.join("Balatro.app/Contents/Resources");
+    fs::create_dir_all(&resources).unwrap();
+    for file in [
+        game.join("Balatro.exe"),
+        game.join("love.dll"),
+        resources.join("Balatro.love"),
+    ] {
+        fs::write(file, "").unwrap();
+    }
+    let db = Database::open(&home.path().join("bmm_storage.db")).unwrap();
+    db.installations()
+        .add("Modded", &game, Some(&mods))
+        .unwrap();
+    db.installations().switch("Modded").unwrap();
+
+    let catalog = [
+        catalog_mod("Jokers", Some("1.0.0"), false, false),
+        catalog_mod("Perkeo", Some("1.0.0"), false, false),
+        catalog_mod("Cryptid", Some("0.5.0"), false, false),
+    ];
+    cache_archive(&db, home.path(), &catalog[0], &["Missing"]);
+    cache_archive(&db, home.path(), &catalog[1], &["Cryptid (>=0.5)"]);
+    cache_archive(&db, home.path(), &catalog[2], &[]);
+    let db = Mutex::new(db);
+    let job = DownloadJob::default();
+
+    // the catalog doesn't know about Missing, which only shows once Jokers
+    // is extracted
+    match dependencies::install_with_dependencies(&db, &catalog, "Jokers", false, &job).await {
+        Err(AppError::ModInstall { mod_name, source }) => {
+            assert_eq!(mod_name, "Jokers");
+            assert_eq!(
+                source,
+                "unmet dependencies: Jokers needs `Missing` (Missing is not in the catalog)"
+            );
+        }
+        other => panic!("expected unmet dependencies, got {other:?}"),
+    }
+    assert!(!mods.join("Jokers").exists());
+    assert!(!home.path().join(".bmm-staging").exists());
+
+    let installed = dependencies::install_with_dependencies(&db, &catalog, "Perkeo", false, &job)
+        .await
+        .unwrap();
+    assert_eq!(installed, ["Cryptid", "Perkeo"]);
+    assert!(mods.join("Cryptid/main.lua").is_file());
+    assert!(mods.join("Perkeo/main.lua").is_file());
+    let db = db.lock().unwrap();
+    assert_eq!(
+        db.get_mod_details("Perkeo").unwrap().dependencies,
+        ["Cryptid (>=0.5)"]
+    );
+}
@@ -0,0 +1 @@
+-- from extra.lua
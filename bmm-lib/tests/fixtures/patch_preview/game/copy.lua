@@ -0,0 +1 @@
+local original = true
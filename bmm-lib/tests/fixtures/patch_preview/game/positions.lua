@@ -0,0 +1,6 @@
+function Game:update(dt)
+    if self.paused then
+        return
+    end
+    self.timer = self.timer + dt
+end
@@ -0,0 +1,4 @@
+function Card:set_cost()
+    self.cost = self.base_cost + self.extra_cost
+    self.sell_cost = math.max(1, math.floor(self.cost / 2))
+end
@@ -0,0 +1,9 @@
+function add_dollars(mod)
+    G.GAME.dollars = G.GAME.dollars + mod
+end
+function set_dollars(x)
+    G.GAME.dollars = x
+end
+function reset_dollars()
+    G.GAME.dollars = 0
+end
@@ -0,0 +1,10 @@
+-- A database from before settings were stored, whose installed_mods table
+-- hasn't gained current_version yet.
+CREATE TABLE installed_mods (
+    name TEXT PRIMARY KEY,
+    path TEXT NOT NULL,
+    dependencies TEXT NOT NULL DEFAULT '[]'
+);
+INSERT INTO installed_mods (name, path, dependencies) VALUES
+    ('Steamodded', '/game/Mods/smods-1.0.0-beta-0827c', '[]'),
+    ('Cryptid', '/game/Mods/Cryptid', '["Steamodded (>=1.0.0~BETA-0827c)","Talisman"]');
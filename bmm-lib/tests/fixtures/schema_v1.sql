@@ -0,0 +1,22 @@
+-- The last schema before numbered migrations, db_version 1.1.
+CREATE TABLE settings (
+    setting TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+INSERT INTO settings (setting, value) VALUES
+    ('db_version', '1.1'),
+    ('security_warning_acknowledged', 'yes'),
+    ('installation_path', '/game'),
+    ('last_fetched', '1720000000');
+CREATE TABLE installed_mods (
+    name TEXT PRIMARY KEY,
+    path TEXT NOT NULL,
+    dependencies TEXT NOT NULL DEFAULT '[]',
+    current_version TEXT
+);
+INSERT INTO installed_mods (name, path, dependencies, current_version) VALUES
+    ('Steamodded', '/game/Mods/smods-1.0.0-beta-0827c', '[]', '1.0.0~BETA-0827c'),
+    ('Cryptid', '/game/Mods/Cryptid', '["Steamodded (>=1.0.0~BETA-0827c)","Talisman"]', '0.5.0');
+-- Not created by bromomethane, and has to survive upgrades all the same.
+CREATE TABLE notes (body TEXT NOT NULL);
+INSERT INTO notes (body) VALUES ('keep me');
@@ -0,0 +1,61 @@
+-- Version pins.
+CREATE TABLE settings (
+    setting TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+INSERT INTO settings (setting, value) VALUES
+    ('db_version', '1.1'),
+    ('security_warning_acknowledged', 'yes'),
+    ('installation_path', '/game'),
+    ('last_fetched', '1720000000');
+CREATE TABLE installed_mods (
+    name TEXT PRIMARY KEY,
+    path TEXT NOT NULL,
+    dependencies TEXT NOT NULL DEFAULT '[]',
+    current_version TEXT
+);
+INSERT INTO installed_mods (name, path, dependencies, current_version) VALUES
+    ('Steamodded', '/game/Mods/smods-1.0.0-beta-0827c', '[]', '1.0.0~BETA-0827c'),
+    ('Cryptid', '/game/Mods/Cryptid', '["Steamodded (>=1.0.0~BETA-0827c)","Talisman"]', '0.5.0');
+-- Not created by bromomethane, and has to survive upgrades all the same.
+CREATE TABLE notes (body TEXT NOT NULL);
+INSERT INTO notes (body) VALUES ('keep me');
+INSERT INTO settings (setting, value) VALUES ('schema_version', '5');
+CREATE TABLE mod_collections (
+    name TEXT PRIMARY KEY,
+    enabled_mods TEXT NOT NULL DEFAULT '[]'
+);
+INSERT INTO mod_collections (name, enabled_mods) VALUES ('Cryptid run', '["Cryptid","smods-1.0.0-beta-0827c"]');
+INSERT INTO settings (setting, value) VALUES ('active_collection', 'Cryptid run');
+CREATE TABLE mod_sources (
+    path TEXT PRIMARY KEY,
+    download_url TEXT NOT NULL,
+    archive_hash TEXT NOT NULL
+);
+INSERT INTO mod_sources (path, download_url, archive_hash) VALUES
+    ('/game/Mods/Cryptid', 'https://example.com/Cryptid.zip', 'a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66');
+CREATE TABLE mod_files (
+    mod_path TEXT NOT NULL,
+    path TEXT NOT NULL,
+    sha256 TEXT NOT NULL,
+    PRIMARY KEY (mod_path, path)
+);
+INSERT INTO mod_files (mod_path, path, sha256) VALUES
+    ('/game/Mods/Cryptid', 'Cryptid.lua', '780a315a780a315a780a315a780a315a780a315a780a315a780a315a780a315a');
+CREATE TABLE mod_versions (
+    mod_name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    download_url TEXT NOT NULL,
+    archive_hash TEXT NOT NULL,
+    dependencies TEXT NOT NULL DEFAULT '[]',
+    installed_at INTEGER NOT NULL,
+    PRIMARY KEY (mod_name, archive_hash)
+);
+INSERT INTO mod_versions (mod_name, version, download_url, archive_hash, dependencies, installed_at) VALUES
+    ('Cryptid', '0.5.0', 'https://example.com/Cryptid.zip', 'a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66', '[]', 1720000000);
+CREATE TABLE mod_pins (
+    mod_name TEXT PRIMARY KEY COLLATE NOCASE,
+    version_constraint TEXT NOT NULL,
+    reason TEXT
+);
+INSERT INTO mod_pins (mod_name, version_constraint, reason) VALUES ('Cryptid', '0.5.0', 'Run in progress');
@@ -0,0 +1,92 @@
+-- Installations.
+CREATE TABLE settings (
+    setting TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+INSERT INTO settings (setting, value) VALUES
+    ('db_version', '1.1'),
+    ('security_warning_acknowledged', 'yes'),
+    ('active_installation', '1'),
+    ('last_fetched', '1720000000');
+CREATE TABLE installations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    game_dir TEXT UNIQUE,
+    mods_dir TEXT,
+    active_collection TEXT
+);
+INSERT INTO installations (id, name, game_dir, mods_dir, active_collection) VALUES
+    (1, 'Default', '/game', NULL, 'Cryptid run'),
+    (2, 'Beta', '/beta', '/beta/Mods', NULL);
+CREATE TABLE installed_mods (
+    installation_id INTEGER NOT NULL REFERENCES installations (id),
+    name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    dependencies TEXT NOT NULL DEFAULT '[]',
+    current_version TEXT,
+    PRIMARY KEY (installation_id, name)
+);
+INSERT INTO installed_mods (installation_id, name, path, dependencies, current_version) VALUES
+    (1, 'Steamodded', '/game/Mods/smods-1.0.0-beta-0827c', '[]', '1.0.0~BETA-0827c'),
+    (1, 'Cryptid', '/game/Mods/Cryptid', '["Steamodded (>=1.0.0~BETA-0827c)","Talisman"]', '0.5.0'),
+    (2, 'Cryptid', '/beta/Mods/Cryptid', '[]', '0.6.0');
+-- Not created by bromomethane, and has to survive upgrades all the same.
+CREATE TABLE notes (body TEXT NOT NULL);
+INSERT INTO notes (body) VALUES ('keep me');
+INSERT INTO settings (setting, value) VALUES ('schema_version', '7');
+CREATE TABLE mod_collections (
+    installation_id INTEGER NOT NULL REFERENCES installations (id),
+    name TEXT NOT NULL,
+    enabled_mods TEXT NOT NULL DEFAULT '[]',
+    PRIMARY KEY (installation_id, name)
+);
+INSERT INTO mod_collections (installation_id, name, enabled_mods) VALUES
+    (1, 'Cryptid run', '["Cryptid","smods-1.0.0-beta-0827c"]'),
+    (2, 'Beta run', '["Cryptid"]');
+CREATE TABLE mod_sources (
+    path TEXT PRIMARY KEY,
+    download_url TEXT NOT NULL,
+    archive_hash TEXT NOT NULL
+);
+INSERT INTO mod_sources (path, download_url, archive_hash) VALUES
+    ('/game/Mods/Cryptid', 'https://example.com/Cryptid.zip', 'a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66');
+CREATE TABLE mod_files (
+    mod_path TEXT NOT NULL,
+    path TEXT NOT NULL,
+    sha256 TEXT NOT NULL,
+    PRIMARY KEY (mod_path, path)
+);
+INSERT INTO mod_files (mod_path, path, sha256) VALUES
+    ('/game/Mods/Cryptid', 'Cryptid.lua', '780a315a780a315a780a315a780a315a780a315a780a315a780a315a780a315a');
+CREATE TABLE mod_versions (
+    installation_id INTEGER NOT NULL REFERENCES installations (id),
+    mod_name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    download_url TEXT NOT NULL,
+    archive_hash TEXT NOT NULL,
+    dependencies TEXT NOT NULL DEFAULT '[]',
+    installed_at INTEGER NOT NULL,
+    PRIMARY KEY (installation_id, mod_name, archive_hash)
+);
+INSERT INTO mod_versions (installation_id, mod_name, version, download_url, archive_hash, dependencies, installed_at) VALUES
+    (1, 'Cryptid', '0.5.0', 'https://example.com/Cryptid.zip', 'a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66a1ce7c66', '[]', 1720000000);
+CREATE TABLE mod_pins (
+    installation_id INTEGER NOT NULL REFERENCES installations (id),
+    mod_name TEXT NOT NULL COLLATE NOCASE,
+    version_constraint TEXT NOT NULL,
+    reason TEXT,
+    PRIMARY KEY (installation_id, mod_name)
+);
+INSERT INTO mod_pins (installation_id, mod_name, version_constraint, reason) VALUES (1, 'Cryptid', '0.5.0', 'Run in progress');
+CREATE TABLE operations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    mods TEXT NOT NULL DEFAULT '[]',
+    outcome TEXT NOT NULL,
+    detail TEXT,
+    error TEXT,
+    installation_id INTEGER NOT NULL DEFAULT 1
+);
+INSERT INTO operations (timestamp, kind, mods, outcome, installation_id) VALUES
+    (1720000000, 'install', '[{"name":"Cryptid","version":"0.5.0"}]', 'succeeded', 1);
@@ -0,0 +1,86 @@
+//! Extracts the sources of fake game installs into the cache and lists them.
+
+use bmm_lib::game_source;
+use bmm_lib::paths::{Paths, PathsOrigin};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+
+/// A game directory with a bare `Balatro.love` whose `globals.lua` sets
+/// `VERSION` to `version`, or doesn't set it at all.
+fn game_dir(root: &Path, name: &str, version: Option<&str>) -> PathBuf {
+    let dir = root.join(name);
+    fs::create_dir_all(&dir).unwrap();
+    let mut zip = zip::ZipWriter::new(fs::File::create(dir.join("Balatro.love")).unwrap());
+    zip.start_file("globals.lua", SimpleFileOptions::default())
+        .unwrap();
+    if let Some(version) = version {
+        writeln!(zip, "VERSION = '{version}'").unwrap();
+    }
+    zip.start_file("main.lua", SimpleFileOptions::default())
+        .unwrap();
+    writeln!(zip, "-- {name}").unwrap();
+    zip.finish().unwrap();
+    dir
+}
+
+#[test]
+fn extracts_each_version_into_the_cache_and_lists_newest_first() {
+    let home = tempfile::tempdir().unwrap();
+    Paths::init(Paths::rooted(home.path(), PathsOrigin::Override)).unwrap();
+    let cache = game_source::cache_dir().unwrap();
+
+    let games = home.path().join("games");
+    let mut extracted = Vec::new();
+    for (name, version) in [
+        ("o", Some("1.0.1o-FULL")),
+        ("ten", Some("1.0.10-FULL")),
+        ("dots", Some("..")),
+        ("none", None),
+        ("n", Some("1.0.1n-FULL")),
+    ] {
+        let source = game_source::extract(&game_dir(&games, name, version)).unwrap();
+        assert_eq!(source.dir.parent(), Some(cache.as_path()), "{name}");
+        assert_eq!(source.files, ["globals.lua", "main.lua"]);
+        assert_eq!(
+            fs::read_to_string(source.dir.join("main.lua")).unwrap(),
+            format!("-- {name}\n")
+        );
+        extracted.push(source);
+    }
+    // a version that's nothing but dots gets the hash name too
+    assert_eq!(extracted[2].version.as_deref(), Some(".."));
+    assert!(
+        extracted[2]
+            .dir
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("unknown-")
+    );
+    assert_ne!(extracted[2].dir, extracted[3].dir);
+
+    let listed = game_source::list().unwrap();
+    let versions = listed
+        .iter()
+        .map(|source| source.version.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        versions[..3],
+        [
+            Some("1.0.10-FULL"),
+            Some("1.0.1o-FULL"),
+            Some("1.0.1n-FULL")
+        ]
+    );
+    // neither `..` nor a missing version parses, so they come last
+    let mut rest = versions[3..].to_vec();
+    rest.sort_unstable();
+    assert_eq!(rest, [None, Some("..")]);
+
+    assert_eq!(
+        game_source::get("1.0.1o-FULL").unwrap().unwrap().dir,
+        extracted[0].dir
+    );
+}
@@ -0,0 +1,332 @@
+//! Upgrades fixture databases from each earlier schema version and checks
+//! that nothing they held gets lost on the way.
+
+use bmm_lib::database::Database;
+use bmm_lib::game_version::GameVersion;
+use bmm_lib::lovely::LovelyInstall;
+use bmm_lib::migrations::{self, LATEST_VERSION};
+use bmm_lib::pins::ModPin;
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Builds `tests/fixtures/schema_v{version}.sql` into a fresh database file.
+fn fixture(version: u32) -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bmm_storage.db");
+    let sql = fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(format!("schema_v{version}.sql")),
+    )
+    .unwrap();
+    Connection::open(&path)
+        .unwrap()
+        .execute_batch(&sql)
+        .unwrap();
+    (dir, path)
+}
+
+fn schema_version(path: &Path) -> u32 {
+    migrations::schema_version(&Connection::open(path).unwrap()).unwrap()
+}
+
+fn count(path: &Path, table: &str) -> i64 {
+    Connection::open(path)
+        .unwrap()
+        .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+}
+
+/// Upgrades the fixture for `version` and checks what every fixture shares.
+fn upgrade(version: u32) -> (TempDir, PathBuf, Database) {
+    let (dir, path) = fixture(version);
+    let original = fs::read(&path).unwrap();
+    assert_eq!(schema_version(&path), version);
+
+    let db = Database::open(&path).unwrap();
+    assert_eq!(schema_version(&path), LATEST_VERSION);
+    assert_eq!(
+        fs::read(migrations::backup_path(&path, version)).unwrap(),
+        original
+    );
+
+    let mods = db.get_installed_mods().unwrap();
+    assert_eq!(mods.len(), 2);
+    let cryptid = db.get_mod_details("Cryptid").unwrap();
+    assert_eq!(cryptid.path, "/game/Mods/Cryptid");
+    assert_eq!(
+        cryptid.dependencies,
+        ["Steamodded (>=1.0.0~BETA-0827c)", "Talisman"]
+    );
+    (dir, path, db)
+}
+
+#[test]
+fn upgrades_a_database_from_before_settings() {
+    let (_dir, _path, db) = upgrade(0);
+    assert_eq!(db.get_mod_details("Cryptid").unwrap().current_version, None);
+    assert!(!db.get_settings().unwrap().security_warning_acknowledged);
+    assert!(db.collections().list().unwrap().is_empty());
+}
+
+#[test]
+fn upgrades_the_last_legacy_schema() {
+    let (_dir, path, db) = upgrade(1);
+    assert_eq!(
+        db.get_mod_details("Cryptid").unwrap().current_version,
+        Some("0.5.0".to_string())
+    );
+    assert_eq!(
+        db.get_installation_path().unwrap().as_deref(),
+        Some("/game")
+    );
+    assert_eq!(db.get_last_fetched().unwrap(), 1_720_000_000);
+    assert!(db.get_settings().unwrap().security_warning_acknowledged);
+    assert_eq!(count(&path, "notes"), 1);
+}
+
+#[test]
+fn upgrades_a_database_with_mod_collections() {
+    let (_dir, path, db) = upgrade(2);
+    let collections = db.collections().list().unwrap();
+    assert_eq!(collections.len(), 1);
+    assert!(collections[0].active);
+    assert_eq!(
+        collections[0].enabled_mods,
+        ["Cryptid", "smods-1.0.0-beta-0827c"]
+    );
+    assert_eq!(count(&path, "mod_sources"), 0);
+    assert_eq!(count(&path, "notes"), 1);
+}
+
+#[test]
+fn upgrades_a_database_with_mod_sources() {
+    let (_dir, path, db) = upgrade(3);
+    let source = db.get_mod_source("/game/Mods/Cryptid").unwrap().unwrap();
+    assert_eq!(source.download_url, "https://example.com/Cryptid.zip");
+    assert_eq!(source.files.len(), 1);
+    assert!(db.get_mod_versions("Cryptid").unwrap().is_empty());
+    assert_eq!(count(&path, "notes"), 1);
+}
+
+#[test]
+fn upgrades_a_database_with_version_history() {
+    let (_dir, path, db) = upgrade(4);
+    let versions = db.get_mod_versions("Cryptid").unwrap();
+    assert_eq!(versions.len(), 1);
+    assert!(versions[0].current);
+    assert_eq!(db.get_mod_pins().unwrap().len(), 0);
+    assert_eq!(count(&path, "operations"), 0);
+    assert_eq!(count(&path, "notes"), 1);
+}
+
+#[test]
+fn upgrades_a_database_with_version_pins() {
+    let (_dir, path, db) = upgrade(5);
+    let pin = db.get_mod_pin("cryptid").unwrap().unwrap();
+    assert_eq!(pin.constraint, "0.5.0");
+    assert_eq!(pin.reason.as_deref(), Some("Run in progress"));
+    assert_eq!(db.get_operations(1, 10, None).unwrap().total, 0);
+    assert_eq!(count(&path, "notes"), 1);
+}
+
+#[test]
+fn upgrades_a_database_with_an_operation_log() {
+    let (_dir, path, db) = upgrade(6);
+    let operations = db.get_operations(1, 10, Some("Cryptid")).unwrap();
+    assert_eq!(operations.total, 1);
+    assert_eq!(operations.operations[0].mods[0].name, "Cryptid");
+
+    let installations = db.installations().list().unwrap();
+    assert_eq!(installations.len(), 1);
+    assert_eq!(installations[0].name, "Default");
+    assert_eq!(installations[0].game_dir.as_deref(), Some("/game"));
+    assert!(installations[0].active);
+    assert_eq!(count(&path, "notes"), 1);
+}
+
+#[test]
+fn upgrades_a_database_with_installations() {
+    let (_dir, path, db) = upgrade(7);
+    let installations = db.installations().list().unwrap();
+    assert_eq!(installations.len(), 2);
+    assert_eq!(installations[1].mods_dir.as_deref(), Some("/beta/Mods"));
+    assert_eq!(count(&path, "installed_mods"), 3);
+    let collections = db.collections().list().unwrap();
+    assert_eq!(collections.len(), 1);
+    assert!(collections[0].active);
+    db.installations().switch("Beta").unwrap();
+    assert_eq!(db.get_mods_dir().unwrap(), Path::new("/beta/Mods"));
+    assert_eq!(db.collections().list().unwrap()[0].name, "Beta run");
+    assert_eq!(db.collections().active().unwrap(), None);
+    assert!(db.get_mod_pins().unwrap().is_empty());
+    db.installations().switch("Default").unwrap();
+
+    let dll = Path::new("/game/version.dll");
+    assert_eq!(db.get_lovely_install(dll).unwrap(), None);
+    let install = LovelyInstall {
+        path: dll.to_string_lossy().into_owned(),
+        version: "v0.7.1".to_string(),
+        sha256: "d11d11d1".repeat(8),
+        installed_at: 1_720_000_000,
+    };
+    db.set_lovely_install(&install).unwrap();
+    assert_eq!(db.get_lovely_install(dll).unwrap(), Some(install));
+    assert_eq!(count(&path, "notes"), 1);
+}
+
+#[test]
+fn upgrades_a_database_with_lovely_installs() {
+    let (_dir, path, db) = upgrade(8);
+    assert!(
+        db.get_lovely_install(Path::new("/game/version.dll"))
+            .unwrap()
+            .is_some()
+    );
+
+    assert_eq!(db.get_game_version().unwrap(), None);
+    let version = GameVersion {
+        version: Some("1.0.1o-FULL".to_string()),
+        build_id: Some("16829351".to_string()),
+        branch: None,
+        detected_at: 1_720_000_000,
+    };
+    db.set_game_version(&version).unwrap();
+    assert_eq!(db.get_game_version().unwrap(), Some(version));
+    db.installations().switch("Beta").unwrap();
+    assert_eq!(db.get_game_version().unwrap(), None);
+    assert_eq!(count(&path, "notes"), 1);
+}
+
+#[test]
+fn scopes_installed_mods_to_the_active_installation() {
+    let (_dir, path, db) = upgrade(6);
+    let game = tempfile::tempdir().unwrap();
+    // What `is_valid_game_dir` looks for on each platform
+    let resources = game.path().join("Balatro.app/Contents/Resources");
+    fs::create_dir_all(&resources).unwrap();
+    for file in [
+        game.path().join("Balatro.exe"),
+        game.path().join("love.dll"),
+        resources.join("Balatro.love"),
+    ] {
+        fs::write(file, "").unwrap();
+    }
+    let mods = game.path().join("Mods");
+
+    let installations = db.installations();
+    installations
+        .add("Beta", game.path(), Some(&mods))
+        .unwrap_or_else(|e| panic!("{e}"));
+    installations.switch("beta").unwrap();
+    assert_eq!(
+        db.get_installation_path()
+            .unwrap()
+            .map(PathBuf::from)
+            .as_deref(),
+        Some(game.path())
+    );
+    assert_eq!(db.get_mods_dir().unwrap(), mods);
+    assert!(db.get_installed_mods().unwrap().is_empty());
+    assert_eq!(db.get_operations(1, 10, None).unwrap().total, 0);
+    assert!(db.collections().list().unwrap().is_empty());
+    assert!(db.get_mod_pins().unwrap().is_empty());
+    assert!(db.get_mod_versions("Cryptid").unwrap().is_empty());
+
+    db.add_installed_mod(
+        "Cryptid",
+        &mods.join("Cryptid").to_string_lossy(),
+        &[],
+        None,
+    )
+    .unwrap();
+    db.collections().create("Cryptid run", &[]).unwrap();
+    db.collections()
+        .switch("Cryptid run", &mods, false)
+        .unwrap();
+    db.set_mod_pin(&ModPin {
+        name: "Cryptid".to_string(),
+        constraint: "0.6.0".to_string(),
+        reason: None,
+    })
+    .unwrap();
+    assert_eq!(db.get_installed_mods().unwrap().len(), 1);
+    assert!(installations.remove("Beta").is_err());
+
+    installations.switch("Default").unwrap();
+    assert_eq!(db.get_installed_mods().unwrap().len(), 2);
+    assert_eq!(
+        db.get_mod_details("Cryptid").unwrap().path,
+        "/game/Mods/Cryptid"
+    );
+    let collection = db.collections().get("Cryptid run").unwrap();
+    assert_eq!(
+        collection.enabled_mods,
+        ["Cryptid", "smods-1.0.0-beta-0827c"]
+    );
+    db.collections().delete("Cryptid run").unwrap();
+    assert_eq!(
+        db.get_mod_pin("Cryptid").unwrap().unwrap().constraint,
+        "0.5.0"
+    );
+    assert_eq!(db.get_mod_versions("Cryptid").unwrap().len(), 1);
+
+    installations.switch("Beta").unwrap();
+    assert_eq!(
+        db.collections().active().unwrap().as_deref(),
+        Some("Cryptid run")
+    );
+    assert_eq!(
+        db.get_mod_pin("Cryptid").unwrap().unwrap().constraint,
+        "0.6.0"
+    );
+
+    installations.switch("Default").unwrap();
+    installations.remove("Beta").unwrap();
+    assert_eq!(installations.list().unwrap().len(), 1);
+    assert_eq!(count(&path, "mod_collections"), 0);
+    assert_eq!(count(&path, "mod_pins"), 1);
+}
+
+#[test]
+fn creates_a_fresh_database_without_a_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bmm_storage.db");
+
+    let db = Database::open(&path).unwrap();
+    assert!(db.get_installed_mods().unwrap().is_empty());
+    assert_eq!(schema_version(&path), LATEST_VERSION);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn leaves_an_up_to_date_database_alone() {
+    let (dir, path, db) = upgrade(1);
+    drop(db);
+    let files = fs::read_dir(dir.path()).unwrap().count();
+
+    Database::open(&path).unwrap();
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), files);
+}
+
+#[test]
+fn refuses_a_database_from_a_newer_version() {
+    let (dir, path) = fixture(8);
+    Connection::open(&path)
+        .unwrap()
+        .execute(
+            "UPDATE settings SET value = ?1 WHERE setting = 'schema_version'",
+            [(LATEST_VERSION + 1).to_string()],
+        )
+        .unwrap();
+    let original = fs::read(&path).unwrap();
+
+    let err = Database::open(&path).unwrap_err();
+    assert!(err.to_string().contains("newer version"), "{err}");
+    assert_eq!(fs::read(&path).unwrap(), original);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
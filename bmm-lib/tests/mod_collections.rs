@@ -0,0 +1,139 @@
+//! Switches between profiles of mods that declare conflicts with each other,
+//! and checks what the switches leave in the operation log.
+
+use bmm_lib::database::Database;
+use bmm_lib::errors::AppError;
+use bmm_lib::installer;
+use bmm_lib::mod_collections;
+use bmm_lib::operations::{AffectedMod, Operation, OperationKind};
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A database whose active installation has a Mods directory of its own
+/// with Alpha, which conflicts with Beta, Beta and Gamma, all enabled. The
+/// game's files are empty, so its version is unknown and never refuses
+/// anything.
+fn setup() -> (TempDir, Database, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let mods = dir.path().join("Mods");
+    for (name, conflicts) in [("Alpha", vec!["Beta"]), ("Beta", vec![]), ("Gamma", vec![])] {
+        let mod_dir = mods.join(name);
+        fs::create_dir_all(&mod_dir).unwrap();
+        let metadata = json!({
+            "id": name,
+            "name": name,
+            "author": ["Jimbo"],
+            "description": "",
+            "prefix": name.to_lowercase(),
+            "main_file": "main.lua",
+            "version": "1.0.0",
+            "conflicts": conflicts,
+        });
+        fs::write(mod_dir.join(format!("{name}.json")), metadata.to_string()).unwrap();
+    }
+
+    let game = dir.path().join("game");
+    let resources = game.join("Balatro.app/Contents/Resources");
+    fs::create_dir_all(&resources).unwrap();
+    for file in [
+        game.join("Balatro.exe"),
+        game.join("love.dll"),
+        resources.join("Balatro.love"),
+    ] {
+        fs::write(file, "").unwrap();
+    }
+
+    let db = Database::open(&dir.path().join("bmm_storage.db")).unwrap();
+    db.installations()
+        .add("Modded", &game, Some(&mods))
+        .unwrap();
+    db.installations().switch("Modded").unwrap();
+    (dir, db, mods)
+}
+
+fn enabled(mods: &Path) -> Vec<String> {
+    mod_collections::enabled_mods(mods).unwrap()
+}
+
+fn operations(db: &Database) -> Vec<Operation> {
+    db.get_operations(1, 10, None).unwrap().operations
+}
+
+fn touched(operation: &Operation) -> (OperationKind, Vec<AffectedMod>, Option<&str>) {
+    (
+        operation.kind,
+        operation.mods.clone(),
+        operation.detail.as_deref(),
+    )
+}
+
+#[test]
+fn refuses_a_profile_whose_mods_conflict_unless_forced() {
+    let (_dir, db, mods) = setup();
+    db.collections()
+        .create("Clash", &["Alpha".to_string(), "Beta".to_string()])
+        .unwrap();
+
+    match db.collections().switch("Clash", &mods, false) {
+        Err(AppError::ModConflict {
+            mod_name,
+            conflicts,
+        }) => {
+            assert_eq!(mod_name, "Alpha");
+            assert_eq!(conflicts, ["Beta 1.0.0"]);
+        }
+        other => panic!("expected a conflict, got {other:?}"),
+    }
+    assert_eq!(enabled(&mods), ["Alpha", "Beta", "Gamma"]);
+    assert_eq!(db.collections().active().unwrap(), None);
+    assert!(operations(&db).is_empty());
+
+    let toggles = db.collections().switch("Clash", &mods, true).unwrap();
+    mod_collections::record_switch(&db, "Clash", toggles).unwrap();
+    assert_eq!(enabled(&mods), ["Alpha", "Beta"]);
+    assert_eq!(db.collections().active().unwrap().as_deref(), Some("Clash"));
+    assert_eq!(
+        operations(&db).iter().map(touched).collect::<Vec<_>>(),
+        [(
+            OperationKind::Disable,
+            vec![AffectedMod::new("Gamma", None)],
+            Some("Clash")
+        )]
+    );
+}
+
+#[test]
+fn records_what_a_switch_enabled_and_disabled() {
+    let (_dir, db, mods) = setup();
+    installer::set_mod_enabled(&mods.join("Beta"), false).unwrap();
+    // Alpha conflicts with Beta, but it's not part of the profile, so
+    // having it enabled now doesn't matter
+    db.collections()
+        .create("Calm", &["Beta".to_string(), "Gamma".to_string()])
+        .unwrap();
+
+    let toggles = db.collections().switch("Calm", &mods, false).unwrap();
+    mod_collections::record_switch(&db, "Calm", toggles).unwrap();
+    assert_eq!(enabled(&mods), ["Beta", "Gamma"]);
+    assert_eq!(
+        operations(&db).iter().map(touched).collect::<Vec<_>>(),
+        [
+            (
+                OperationKind::Disable,
+                vec![AffectedMod::new("Alpha", None)],
+                Some("Calm")
+            ),
+            (
+                OperationKind::Enable,
+                vec![AffectedMod::new("Beta", None)],
+                Some("Calm")
+            ),
+        ]
+    );
+
+    // nothing changes when switching to the profile that's already applied
+    let toggles = db.collections().switch("Calm", &mods, false).unwrap();
+    assert!(toggles.is_empty());
+}
@@ -0,0 +1,196 @@
+//! Previews the lovely patches of the mods in `tests/fixtures/patch_preview`
+//! against the game files next to them. Each mod there patches its own file,
+//! so every test previews just the files it's about.
+
+use bmm_lib::lovely_patches;
+use bmm_lib::patch_preview::{self, FilePreview, PatchPreview};
+use std::path::{Path, PathBuf};
+
+fn fixture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/patch_preview")
+}
+
+fn preview(targets: &[&str]) -> PatchPreview {
+    let report = lovely_patches::analyse(&fixture().join("Mods")).unwrap();
+    let targets = targets.iter().map(ToString::to_string).collect::<Vec<_>>();
+    patch_preview::preview(&fixture().join("game"), &report.mods, &targets).unwrap()
+}
+
+fn only_file(preview: &PatchPreview) -> &FilePreview {
+    assert_eq!(preview.files.len(), 1, "{:?}", preview.files);
+    &preview.files[0]
+}
+
+fn warnings(preview: &PatchPreview) -> Vec<(&str, &str)> {
+    preview
+        .warnings
+        .iter()
+        .map(|w| (w.mod_name.as_str(), w.message.as_str()))
+        .collect()
+}
+
+#[test]
+fn wildcards_match_whole_trimmed_lines() {
+    let preview = preview(&["wildcards.lua"]);
+    let file = only_file(&preview);
+    assert_eq!(file.patched_by, ["Wildcards"]);
+    assert_eq!(
+        file.diff,
+        "\
+--- a/wildcards.lua
++++ b/wildcards.lua
+@@ -1,9 +1,14 @@
++-- dollars
+ function add_dollars(mod)
+     G.GAME.dollars = G.GAME.dollars + mod
+ end
++-- dollars
+ function set_dollars(x)
+     G.GAME.dollars = x
++    check_dollars()
+ end
++-- dollars
+ function reset_dollars()
+     G.GAME.dollars = 0
++    check_dollars()
+ end
+"
+    );
+    assert_eq!(
+        warnings(&preview),
+        [("Wildcards", "Pattern 'G.GAME.dollars' matched nothing")]
+    );
+}
+
+#[test]
+fn patterns_apply_at_their_position_with_indent_and_times() {
+    let preview = preview(&["positions.lua"]);
+    assert_eq!(
+        only_file(&preview).diff,
+        "\
+--- a/positions.lua
++++ b/positions.lua
+@@ -1,6 +1,9 @@
+ function Game:update(dt)
+     if self.paused then
++        log('paused')
++        log('still paused')
+         return
+     end
+-    self.timer = self.timer + dt
++-- after the first end
++    self.timer = self.timer + dt * G.SPEED
+ end
+"
+    );
+    // `times` only warns when there are fewer matches than it asks for
+    assert!(preview.warnings.is_empty(), "{:?}", preview.warnings);
+}
+
+#[test]
+fn regex_payloads_go_at_the_root_capture_with_line_prepend() {
+    let preview = preview(&["regex.lua"]);
+    assert_eq!(
+        only_file(&preview).diff,
+        "\
+--- a/regex.lua
++++ b/regex.lua
+@@ -1,4 +1,6 @@
+ function Card:set_cost()
+-    self.cost = self.base_cost + self.extra_cost
++    self.cost = (self.base_cost * 2) + self.extra_cost
+     self.sell_cost = math.max(1, math.floor(self.cost / 2))
++    self.sell_cost = self.sell_cost + 1
++    self.sell_cost = math.min(self.sell_cost, 10)
+ end
+"
+    );
+    assert_eq!(
+        warnings(&preview),
+        [(
+            "Regexes",
+            r"Regex 'function Card:set_cost\(\)' has no capture 'missing'"
+        )]
+    );
+}
+
+#[test]
+fn copy_patches_apply_first_in_priority_order() {
+    let preview = preview(&["copy.lua"]);
+    let file = only_file(&preview);
+    assert_eq!(file.patched_by, ["Copier", "Aardvark"]);
+    assert_eq!(
+        file.diff,
+        "\
+--- a/copy.lua
++++ b/copy.lua
+@@ -1 +1,6 @@
++-- aardvark
++-- copier
+ local original = true
++-- pattern ran
++
++-- from extra.lua
+"
+    );
+    assert!(preview.warnings.is_empty(), "{:?}", preview.warnings);
+}
+
+#[test]
+fn warns_about_patches_that_match_nothing_or_too_little() {
+    let preview = preview(&["warnings.lua", "missing.lua"]);
+    let file = only_file(&preview);
+    assert_eq!(file.patched_by, ["Warnings"]);
+    assert_eq!(
+        file.diff,
+        "\
+--- a/warnings.lua
++++ b/warnings.lua
+@@ -1,2 +1,4 @@
+ print('hi')
++print('there')
+ print('hi')
++print('there')
+"
+    );
+    let missing = format!(
+        "missing.lua isn't a file in {}",
+        fixture().join("game").display()
+    );
+    assert_eq!(
+        warnings(&preview),
+        [
+            ("Warnings", missing.as_str()),
+            ("Warnings", "Pattern 'does not exist' matched nothing"),
+            (
+                "Warnings",
+                "Pattern 'print('hi')' matched 2 times, expected 3"
+            ),
+            // regex patches apply after every pattern patch
+            ("Warnings", r"Regex 'nope\(\)' matched nothing"),
+        ]
+    );
+}
+
+#[test]
+fn previews_every_file_the_enabled_mods_patch() {
+    let preview = preview(&[]);
+    let files = preview
+        .files
+        .iter()
+        .map(|file| (file.target.as_str(), file.patched_by.len()))
+        .collect::<Vec<_>>();
+    // the module patch before main.lua adds a module and changes no file
+    assert_eq!(
+        files,
+        [
+            ("copy.lua", 2),
+            ("positions.lua", 1),
+            ("regex.lua", 1),
+            ("warnings.lua", 1),
+            ("wildcards.lua", 1),
+        ]
+    );
+    assert!(preview.warnings.iter().all(|w| w.mod_name != "Disabled"));
+    assert_eq!(preview.warnings.len(), 6);
+}
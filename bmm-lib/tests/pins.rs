@@ -0,0 +1,38 @@
+//! Lists the framework versions a pin lets through, and explains the rest.
+
+use bmm_lib::database::Database;
+use bmm_lib::pins;
+
+fn versions(versions: &[&str]) -> Vec<String> {
+    versions.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn holds_back_the_versions_a_pin_excludes() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::open(&dir.path().join("bmm_storage.db")).unwrap();
+    let available = versions(&["1.0.0", "0.9.8", "0.9.7"]);
+
+    let unpinned = pins::allowed_versions(&db, "Steamodded", available.clone()).unwrap();
+    assert_eq!(unpinned.versions, available);
+    assert!(unpinned.held.is_empty());
+
+    // Steamodded can be pinned before it's installed
+    pins::pin(
+        &db,
+        "Steamodded",
+        Some("<<1.0"),
+        Some("Cryptid needs it".to_string()),
+    )
+    .unwrap();
+    let pinned = pins::allowed_versions(&db, "Steamodded", available).unwrap();
+    assert_eq!(pinned.versions, ["0.9.8", "0.9.7"]);
+    assert_eq!(
+        pinned
+            .held
+            .iter()
+            .map(|held| held.explanation.as_str())
+            .collect::<Vec<_>>(),
+        ["Steamodded 1.0.0 is available, but Steamodded is pinned to <<1.0: Cryptid needs it"]
+    );
+}
@@ -0,0 +1,212 @@
+//! Parses `KeyValues` documents the way Steam writes them, and reads library
+//! folders and app manifests out of a fake Steam installation.
+
+use bmm_lib::errors::AppError;
+use bmm_lib::steam::{self, AppManifest};
+use bmm_lib::vdf::{self, Vdf};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn steam_root(libraryfolders: &str) -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+    fs::create_dir_all(root.join("steamapps")).unwrap();
+    fs::write(root.join("steamapps/libraryfolders.vdf"), libraryfolders).unwrap();
+    (dir, root)
+}
+
+fn assert_unparseable(text: &str, line: usize) {
+    match vdf::parse(text) {
+        Err(AppError::Serialization { format, source }) => {
+            assert_eq!(format, "VDF");
+            assert!(
+                source.starts_with(&format!("line {line}:")),
+                "{text:?}: {source}"
+            );
+        }
+        result => panic!("{text:?} parsed: {result:?}"),
+    }
+}
+
+#[test]
+fn parses_quoted_and_bare_tokens() {
+    let table = vdf::parse(
+        r#"
+        "AppState"
+        {
+            "appid"     "2379780"
+            name        Balatro
+            "UserConfig" { "BetaKey" "" }
+        }
+        "#,
+    )
+    .unwrap();
+    let state = table.get_table("appstate").unwrap();
+    assert_eq!(state.get_str("AppID"), Some("2379780"));
+    assert_eq!(state.get_str("name"), Some("Balatro"));
+    assert_eq!(
+        state.get_table("UserConfig").unwrap().get_str("BetaKey"),
+        Some("")
+    );
+    assert!(state.get_table("name").is_none());
+    assert!(state.get_str("UserConfig").is_none());
+}
+
+#[test]
+fn keeps_duplicate_keys_in_order() {
+    let table = vdf::parse(r#""key" "first" "KEY" "second""#).unwrap();
+    assert_eq!(table.get_str("key"), Some("first"));
+    assert_eq!(
+        table
+            .iter()
+            .map(|(k, v)| (k, v.as_str().unwrap()))
+            .collect::<Vec<_>>(),
+        [("key", "first"), ("KEY", "second")]
+    );
+}
+
+#[test]
+fn unescapes_quoted_strings() {
+    let table = vdf::parse(
+        r#""path" "C:\\Program Files (x86)\\Steam" "quote" "say \"hi\"" "tab" "a\tb\nc" "other" "\d""#,
+    )
+    .unwrap();
+    assert_eq!(table.get_str("path"), Some(r"C:\Program Files (x86)\Steam"));
+    assert_eq!(table.get_str("quote"), Some(r#"say "hi""#));
+    assert_eq!(table.get_str("tab"), Some("a\tb\nc"));
+    // unknown escapes are kept as written
+    assert_eq!(table.get_str("other"), Some(r"\d"));
+}
+
+#[test]
+fn skips_comments_and_conditions() {
+    let table = vdf::parse(
+        r#"
+        // written by Steam
+        "root"
+        {
+            "windows"   "1"     [$WIN32]
+            "linux"     "1"     [!$WIN32] // not on Windows
+            "url"       "https://example.com/a/b"
+        }
+        "#,
+    )
+    .unwrap();
+    let root = table.get_table("root").unwrap();
+    assert_eq!(root.get_str("windows"), Some("1"));
+    assert_eq!(root.get_str("linux"), Some("1"));
+    assert_eq!(root.get_str("url"), Some("https://example.com/a/b"));
+    assert_eq!(root.iter().count(), 3);
+}
+
+#[test]
+fn reports_unterminated_input_by_line() {
+    assert_unparseable("\"root\"\n{\n\"key\" \"value\"\n", 4);
+    assert_unparseable("\"root\" {\n\"key\" \"value", 2);
+    assert_unparseable("\"key\" \"value\" [$WIN32\n", 1);
+    assert_unparseable("\"key\"", 1);
+    assert_unparseable("\"key\" \"value\"\n}", 2);
+    assert_unparseable("{ \"key\" \"value\" }", 1);
+}
+
+#[test]
+fn reads_the_current_libraryfolders_layout() {
+    let (_dir, root) = steam_root(
+        r#"
+        "libraryfolders"
+        {
+            "0"
+            {
+                "path"      "ROOT"
+                "label"     ""
+                "apps" { "2379780" "123456" }
+            }
+            "1"
+            {
+                "path"      "/mnt/games/SteamLibrary"
+            }
+            "2" { "label" "no path" }
+        }
+        "#,
+    );
+    // the installation itself is listed as well, and only counted once
+    let vdf = fs::read_to_string(root.join("steamapps/libraryfolders.vdf")).unwrap();
+    fs::write(
+        root.join("steamapps/libraryfolders.vdf"),
+        vdf.replace("ROOT", &root.display().to_string().replace('\\', "\\\\")),
+    )
+    .unwrap();
+
+    assert_eq!(
+        steam::library_folders(&root),
+        [root.clone(), PathBuf::from("/mnt/games/SteamLibrary")]
+    );
+}
+
+#[test]
+fn reads_the_old_libraryfolders_layout() {
+    let (_dir, root) = steam_root(
+        r#"
+        "LibraryFolders"
+        {
+            "TimeNextStatsReport"   "1561832478"
+            "ContentStatsID"        "-158337411"
+            "1"                     "/mnt/games/SteamLibrary"
+            "2"                     "/mnt/other/SteamLibrary"
+        }
+        "#,
+    );
+    assert_eq!(
+        steam::library_folders(&root),
+        [
+            root.clone(),
+            PathBuf::from("/mnt/games/SteamLibrary"),
+            PathBuf::from("/mnt/other/SteamLibrary")
+        ]
+    );
+}
+
+#[test]
+fn falls_back_to_the_installation_when_libraryfolders_is_broken() {
+    let (_dir, root) = steam_root("\"libraryfolders\" {\n\"1\" { \"path\" \"/mnt/games\"\n");
+    assert_eq!(steam::library_folders(&root), vec![root]);
+}
+
+#[test]
+fn reads_app_manifests() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("appmanifest_2379780.acf");
+    fs::write(
+        &path,
+        r#"
+        "AppState"
+        {
+            "appid"         "2379780"
+            "name"          "Balatro"
+            "installdir"    "Balatro"
+            "buildid"       "16317543"
+            "MountedConfig" { "BetaKey" "public" }
+            "UserConfig"    { "BetaKey" "beta" }
+        }
+        "#,
+    )
+    .unwrap();
+
+    let manifest = AppManifest::read(&path).unwrap();
+    assert_eq!(manifest.app_id, steam::BALATRO_APP_ID);
+    assert_eq!(manifest.name, "Balatro");
+    assert_eq!(manifest.install_dir, "Balatro");
+    assert_eq!(manifest.build_id.as_deref(), Some("16317543"));
+    assert_eq!(manifest.branch.as_deref(), Some("beta"));
+
+    fs::write(&path, r#""AppState" { "name" "Balatro" }"#).unwrap();
+    assert!(AppManifest::read(&path).is_err());
+    assert!(matches!(
+        vdf::read(Path::new("/nonexistent/appmanifest.acf")),
+        Err(AppError::FileRead { .. })
+    ));
+    assert!(matches!(
+        vdf::parse("\"key\" { }").unwrap().get("key"),
+        Some(Vdf::Table(_))
+    ));
+}
@@ -0,0 +1,77 @@
+//! Parses and orders versions the way mod headers, release tags and the
+//! catalog spell them.
+
+use bmm_lib::version::{Version, is_update, sort_newest_first};
+use std::collections::HashSet;
+
+fn version(version: &str) -> Version {
+    version.parse().unwrap()
+}
+
+#[test]
+fn fills_in_missing_components() {
+    assert_eq!(version("1.0"), Version::new(1, 0, 0));
+    assert_eq!(version("v2.3"), version("2.3.0"));
+    assert_eq!(version("V2"), Version::new(2, 0, 0));
+    assert_eq!(version(" 1.2.3 ").to_string(), "1.2.3");
+}
+
+#[test]
+fn compares_components_as_numbers() {
+    assert!(version("1.0.9") < version("1.0.10"));
+    assert!(version("1.9") < version("1.10"));
+    assert!(is_update("1.0.9", "1.0.10"));
+    assert!(!is_update("1.0.10", "1.0.9"));
+}
+
+#[test]
+fn sorts_revisions_before_their_release() {
+    let beta = version("1.0.0~BETA-0827c");
+    assert_eq!(beta.rev.as_deref(), Some("BETA-0827c"));
+    assert!(beta < version("1.0.0"));
+    assert!(beta > version("0.9.8"));
+    assert!(version("1.0.0~ALPHA-0812d") < beta);
+    assert!(is_update("1.0.0~BETA-0827c", "1.0.0"));
+}
+
+#[test]
+fn treats_revisions_that_differ_in_case_as_the_same_version() {
+    let header = version("1.0.0~BETA-0827c");
+    let tag = version("1.0.0-beta-0827c");
+    assert_eq!(header, tag);
+    assert_eq!(header.cmp(&tag), std::cmp::Ordering::Equal);
+    assert!(!is_update("1.0.0~BETA-0827c", "1.0.0-beta-0827c"));
+    assert!(!is_update("1.0.0-beta-0827c", "1.0.0~BETA-0827c"));
+    assert_eq!(HashSet::from([header, tag]).len(), 1);
+}
+
+#[test]
+fn orders_date_tags_chronologically() {
+    let date = version("2024-03-27");
+    assert_eq!(date, Version::new(2024, 3, 27));
+    assert!(date < version("2024-11-02"));
+    assert!(version("2024-03-27-b") < date);
+    assert!(is_update("2023-12-31", "2024-01-01"));
+}
+
+#[test]
+fn rejects_what_is_not_a_version() {
+    for invalid in ["", "latest", "v", "1.0 beta", ".1"] {
+        assert!(invalid.parse::<Version>().is_err(), "{invalid:?} parsed");
+    }
+    // without versions to compare, any change counts as an update
+    assert!(is_update("latest", "nightly"));
+    assert!(!is_update("latest", " latest "));
+}
+
+#[test]
+fn sorts_newest_first_with_unparseable_versions_last() {
+    let mut versions = ["1.0.9", "nightly", "1.0.10", "v1.1", "1.0.0~BETA", "latest"]
+        .map(String::from)
+        .to_vec();
+    sort_newest_first(&mut versions);
+    assert_eq!(
+        versions,
+        ["v1.1", "1.0.10", "1.0.9", "1.0.0~BETA", "nightly", "latest"]
+    );
+}
@@ -0,0 +1,150 @@
+//! Records installs into the version history the way updates and rollbacks
+//! do, replacing the mod in place.
+
+use bmm_lib::archive_cache::ArchiveCache;
+use bmm_lib::checksum;
+use bmm_lib::database::Database;
+use bmm_lib::installer::ModSource;
+use bmm_lib::paths::{Paths, PathsOrigin};
+use bmm_lib::version_history::{self, KEPT_VERSIONS};
+use std::fs;
+use std::sync::OnceLock;
+use tempfile::TempDir;
+
+const PATH: &str = "/game/Mods/Cryptid";
+
+/// A fresh database, with the paths pointed at a home shared by every test
+/// since they can only be set once.
+fn open() -> (TempDir, Database) {
+    static HOME: OnceLock<TempDir> = OnceLock::new();
+    HOME.get_or_init(|| {
+        let home = tempfile::tempdir().unwrap();
+        Paths::init(Paths::rooted(home.path(), PathsOrigin::Override)).unwrap();
+        home
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::open(&dir.path().join("bmm_storage.db")).unwrap();
+    (dir, db)
+}
+
+fn source(version: &str) -> ModSource {
+    ModSource {
+        download_url: format!("https://example.com/Cryptid-{version}.zip"),
+        archive_hash: format!("{:0>64}", version.replace('.', "")),
+        files: Vec::new(),
+    }
+}
+
+fn install(db: &Database, version: &str) {
+    db.record_install(
+        "Cryptid",
+        PATH,
+        &["Talisman".to_string()],
+        Some(version.to_string()),
+        &source(version),
+    )
+    .unwrap();
+}
+
+fn history(db: &Database) -> Vec<(String, String, bool)> {
+    db.get_mod_versions("Cryptid")
+        .unwrap()
+        .into_iter()
+        .map(|v| (v.version, v.archive_hash, v.current))
+        .collect()
+}
+
+fn entry(version: &str, current: bool) -> (String, String, bool) {
+    (version.to_string(), source(version).archive_hash, current)
+}
+
+#[test]
+fn labels_each_version_with_its_own_archive() {
+    let (_dir, db) = open();
+
+    install(&db, "0.5.0");
+    // updated in place, at the same path
+    install(&db, "0.5.1");
+    assert_eq!(history(&db), [entry("0.5.1", true), entry("0.5.0", false)]);
+    let source = db.get_mod_source(PATH).unwrap().unwrap();
+    assert_eq!(source.archive_hash, self::source("0.5.1").archive_hash);
+    assert_eq!(
+        db.get_mod_details("Cryptid")
+            .unwrap()
+            .current_version
+            .as_deref(),
+        Some("0.5.1")
+    );
+
+    // rolled back
+    install(&db, "0.5.0");
+    assert_eq!(history(&db), [entry("0.5.0", true), entry("0.5.1", false)]);
+
+    install(&db, "0.6.0");
+    install(&db, "0.7.0");
+    let versions = history(&db);
+    assert_eq!(versions.len(), KEPT_VERSIONS);
+    assert_eq!(
+        versions,
+        [
+            entry("0.7.0", true),
+            entry("0.6.0", false),
+            entry("0.5.0", false)
+        ]
+    );
+}
+
+#[test]
+fn replacing_a_mod_forgets_where_the_old_copy_was() {
+    let (_dir, db) = open();
+    install(&db, "0.5.0");
+
+    // a framework update can land in a folder of another name
+    let moved = "/game/Mods/Cryptid-0.5.1";
+    db.replace_install(
+        "Cryptid",
+        "Cryptid",
+        moved,
+        &[],
+        Some("0.5.1".to_string()),
+        &source("0.5.1"),
+    )
+    .unwrap();
+    assert!(db.get_mod_source(PATH).unwrap().is_none());
+    assert_eq!(
+        db.get_mod_source(moved).unwrap().unwrap().archive_hash,
+        source("0.5.1").archive_hash
+    );
+    let installed = db.get_installed_mods().unwrap();
+    assert_eq!(installed.len(), 1);
+    assert_eq!(installed[0].path, moved);
+    assert_eq!(history(&db), [entry("0.5.1", true), entry("0.5.0", false)]);
+}
+
+#[test]
+fn finds_the_archive_of_a_version_installed_before() {
+    let (dir, db) = open();
+    let url = "https://example.com/Cryptid.zip";
+    let archive = dir.path().join("Cryptid.zip");
+    fs::write(&archive, "Cryptid 0.5.0").unwrap();
+    ArchiveCache::open().unwrap().add(url, &archive).unwrap();
+    let archive_hash = checksum::sha256_hex(b"Cryptid 0.5.0");
+    db.record_install(
+        "Cryptid",
+        PATH,
+        &[],
+        Some("0.5.0".to_string()),
+        &ModSource {
+            download_url: url.to_string(),
+            archive_hash: archive_hash.clone(),
+            files: Vec::new(),
+        },
+    )
+    .unwrap();
+
+    let recorded = |version| version_history::recorded_archive(&db, url, version).unwrap();
+    assert_eq!(recorded(Some("0.5.0")), Some(archive_hash));
+    // the same URL may serve something else for another version
+    assert_eq!(recorded(Some("0.5.1")), None);
+    assert_eq!(recorded(None), None);
+}
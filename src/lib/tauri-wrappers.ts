@@ -1,5 +1,6 @@
 import {invoke} from "@tauri-apps/api/core";
 import type {Mod} from "../stores/modStore";
+import {addMessage} from "./stores";
 
 export const get_mod_list = async () => {
   // eslint-disable-next-line @typescript-eslint/no-explicit-any
@@ -9,3 +10,70 @@ export const get_mod_list = async () => {
   });
   return response as Array<Mod>;
 };
+
+// Commands that can be retried with `force` fail with the check that refused them, if `force`
+// gets past it.
+type ForceableError = {
+  kind: "mod_conflict" | "incompatible_game_version" | null;
+  message: string;
+};
+
+const isForceableError = (error: unknown): error is ForceableError =>
+  typeof error === "object" && error !== null && "kind" in error && "message" in error;
+
+// Installing or enabling a mod is refused when it conflicts with an enabled mod, and enabling a
+// mod or launching the game when a mod doesn't support the installed game version. This asks the
+// user whether to go ahead anyway, and retries with `force` if they do. Other errors are thrown
+// as their message.
+export const invokeWithConflictOverride = async <T>(
+  command: string,
+  args: Record<string, unknown>,
+): Promise<T> => {
+  try {
+    return await invoke<T>(command, args);
+  } catch (error) {
+    const {kind, message} = isForceableError(error) ? error : {kind: null, message: String(error)};
+    if (kind === null) {
+      throw message;
+    }
+    const conflict = kind === "mod_conflict";
+    const {ask} = await import("@tauri-apps/plugin-dialog");
+    const proceed = await ask(`${message}\n\nDo you want to continue anyway?`, {
+      title: conflict ? "Mod conflict" : "Unsupported game version",
+      kind: "warning",
+    });
+    if (!proceed) {
+      throw message;
+    }
+    return await invoke<T>(command, {...args, force: true});
+  }
+};
+
+type GameVersion = {
+  version: string | null;
+  build_id: string | null;
+  branch: string | null;
+  detected_at: number;
+};
+
+type VersionCheck = {current: GameVersion; previous: GameVersion | null; changed: boolean};
+
+const describeGameVersion = ({version, build_id, branch}: GameVersion) => {
+  const details = [build_id && `build ${build_id}`, branch && `branch ${branch}`].filter(Boolean);
+  return `${version ?? "unknown version"}${details.length ? ` (${details.join(", ")})` : ""}`;
+};
+
+// Launches the game through `invokeWithConflictOverride`, and warns if the game changed since the
+// last launch.
+export const launchBalatro = async (args: {vanilla: boolean}) => {
+  const check = await invokeWithConflictOverride<VersionCheck>("launch_balatro", args);
+  if (check.changed && check.previous) {
+    const from = describeGameVersion(check.previous);
+    const to = describeGameVersion(check.current);
+    addMessage(
+      `Balatro changed from ${from} to ${to} since the last launch, mods may need updating`,
+      "warning",
+    );
+  }
+  return check;
+};
@@ -89,6 +89,18 @@ export interface InstalledMod {
   // collection_hash: string | null;
 }
 
+export interface HeldUpdate {
+  name: string;
+  installed: string;
+  available: string;
+  explanation: string;
+}
+
+export interface AllowedVersions {
+  versions: string[];
+  held: HeldUpdate[];
+}
+
 interface InstallationStatus {
   [key: string]: boolean;
 }
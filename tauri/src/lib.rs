@@ -10,42 +10,121 @@
 mod github_repo;
 
 use base64::{Engine as _, engine::general_purpose::STANDARD as base64};
-use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::{
-    collections::HashSet,
     fs,
     io::Cursor,
     path::{Path, PathBuf},
-    process::Command,
     sync::{LazyLock, Mutex, RwLock},
     time::{SystemTime, UNIX_EPOCH},
 };
 use tauri::{Emitter, Manager};
 use tauri_plugin_window_state::StateFlags;
-use walkdir::WalkDir;
 use zip::{ZipArchive, read::root_dir_common_filter};
 
 use balatro_mod_index::{forge, lfs, mods::ModIndex};
 use lovely_mod_manager::{Game, ModManager};
 
 use bmm_lib::{
-    cache,
+    cache, conflicts,
     database::{Database, InstalledMod},
+    dependencies::{self, InstalledSet, ResolutionPlan},
+    download::{ActiveDownload, DownloadJob, DownloadJobs},
     errors::AppError,
-    finder::{get_lovely_mods_dir, is_steam_running},
-    local_mod_detection, lovely,
+    finder::{is_steam_running, is_valid_game_dir},
+    game_source::{self, GameSource},
+    game_version::{self, Incompatibility, VersionCheck},
+    installations::Installation,
+    installer,
+    integrity::{self, ModIntegrity, RepairReport},
+    launcher, local_mod_detection,
+    lockfile::{self, ImportReport, Lockfile},
+    lovely::{self, LovelyInstall, LovelyRelease, LovelyStatus},
+    lovely_patches::{self, PatchReport},
+    mod_collections::{self, ModCollection},
+    operations::{self, AffectedMod, OperationKind, OperationsPage},
+    patch_preview::{self, PatchPreview},
+    paths::{self, Paths},
+    pins::{self, AllowedVersions, ModPin},
+    settings::{self, Settings},
     smods_installer::{ModInstaller, ModType},
+    staging::Staging,
+    updates::{self, LatestReleases, UpdatePlan, UpdateReport, UpdateStatus},
+    version,
+    version_history::{self, ModVersion},
 };
 
 fn map_error<T>(result: Result<T, AppError>) -> Result<T, String> {
     result.map_err(|e| e.to_string())
 }
 
+/// The error of a command the frontend can retry with `force`, along with
+/// which check refused it, if `force` gets past it.
+#[derive(Debug, Serialize)]
+struct ForceableError {
+    kind: Option<Refusal>,
+    message: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Refusal {
+    ModConflict,
+    IncompatibleGameVersion,
+}
+
+impl From<AppError> for ForceableError {
+    fn from(error: AppError) -> Self {
+        let kind = match &error {
+            AppError::ModConflict { .. } => Some(Refusal::ModConflict),
+            AppError::IncompatibleGameVersion { .. } => Some(Refusal::IncompatibleGameVersion),
+            _ => None,
+        };
+        Self {
+            kind,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<String> for ForceableError {
+    fn from(message: String) -> Self {
+        Self {
+            kind: None,
+            message,
+        }
+    }
+}
+
+/// Registers a download job that reports its progress as `download-progress`
+/// events, and that the frontend can cancel with `cancel_download`.
+fn start_download<'a>(
+    app: &tauri::AppHandle,
+    state: &'a AppState<'_, '_>,
+    job_id: Option<String>,
+) -> Result<ActiveDownload<'a>, AppError> {
+    let app = app.clone();
+    let job = DownloadJob::new(job_id.unwrap_or_default()).on_progress(move |progress| {
+        if let Err(e) = app.emit("download-progress", progress) {
+            log::warn!("Failed to emit download progress: {e}");
+        }
+    });
+    state.downloads.start(job)
+}
+
+#[tauri::command]
+async fn cancel_download(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    job_id: String,
+) -> Result<bool, String> {
+    map_error(state.downloads.cancel(&job_id))
+}
+
 // Create a state structure to hold the database
 struct AppState<'index, 'game> {
     db: Mutex<Database>,
+    downloads: DownloadJobs,
     manager: RwLock<ModManager<'index, 'game>>,
     reqwest: reqwest::Client,
 }
@@ -71,41 +150,64 @@ async fn mod_update_available(
     mod_name: String,
     state: tauri::State<'_, AppState<'_, '_>>,
 ) -> Result<bool, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let last_installed_version = db
-        .get_last_installed_version(&mod_name)
-        .map_err(|e| e.to_string())?;
-
-    // If no version is installed, we can't determine if an update is available
-    if last_installed_version.is_empty() {
-        return Ok(false);
-    }
+    Ok(matches!(
+        get_mod_update_status(mod_name, state).await?,
+        UpdateStatus::Available { .. }
+    ))
+}
 
+/// Like `mod_update_available`, but also explains when a pin held an update
+/// back.
+#[tauri::command]
+async fn get_mod_update_status(
+    mod_name: String,
+    state: tauri::State<'_, AppState<'_, '_>>,
+) -> Result<UpdateStatus, String> {
+    let db = state.db.lock().map_err(|e| e.to_string())?;
     let Some((cached_mods, _)) = crate::cache::load_cache().map_err(|e| e.to_string())? else {
-        return Ok(false);
+        return Ok(UpdateStatus::UpToDate);
     };
+    map_error(updates::catalog_update_status(&db, &cached_mods, &mod_name))
+}
 
-    // Look for the mod in the cache by matching either title or folderName
-    for cached_mod in cached_mods {
-        if cached_mod.title == mod_name || (cached_mod.folder_name.as_ref() == Some(&mod_name)) {
-            // If we found a match and it has a version, compare versions
-            if let Some(remote_version) = cached_mod.version {
-                // If versions are different, consider an update available
-                return Ok(remote_version != last_installed_version);
-            }
-            break; // Found the mod but it has no version info
-        }
-    }
+#[tauri::command]
+async fn get_mod_pins(state: tauri::State<'_, AppState<'_, '_>>) -> Result<Vec<ModPin>, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.get_mod_pins())
+}
 
-    // No update found or couldn't determine
-    Ok(false)
+#[tauri::command]
+async fn pin_mod(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+    constraint: Option<String>,
+    reason: Option<String>,
+) -> Result<ModPin, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(pins::pin(&db, &name, constraint.as_deref(), reason))
+}
+
+#[tauri::command]
+async fn unpin_mod(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+) -> Result<bool, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.remove_mod_pin(&name))
 }
 
 #[tauri::command]
 async fn get_repo_path() -> Result<String, String> {
-    let config_dir = dirs::config_dir()
-        .ok_or_else(|| AppError::DirNotFound(PathBuf::from("config directory")).to_string())?;
-    let repo_path = config_dir.join("Balatro").join("mod_index");
+    let repo_path = Paths::get()?.mod_index_dir();
     Ok(repo_path.to_string_lossy().into_owned())
 }
 
@@ -295,10 +397,16 @@ fn get_color_pair(id: &str) -> &cache::ColorPair {
     &COLOR_PAIRS[id.as_bytes().iter().fold(*SEED, |acc, &b| acc + b as usize) % COLOR_PAIRS.len()]
 }
 
-#[tauri::command]
-async fn get_mod_list(
-    state: tauri::State<'_, AppState<'_, '_>>,
-) -> Result<Vec<cache::Mod>, String> {
+/// The index as catalog entries, the shape the frontend and the resolver use.
+fn catalog_mods(state: &AppState<'_, '_>) -> Result<Vec<cache::Mod>, String> {
+    Ok(catalog_entries(state)?
+        .into_iter()
+        .map(|(_, m)| m)
+        .collect())
+}
+
+/// Like [`catalog_mods`], paired with each mod's index id.
+fn catalog_entries(state: &AppState<'_, '_>) -> Result<Vec<(String, cache::Mod)>, String> {
     // TODO: load cache here? or maybe unify the schema across the tauri boundary
     Ok(state
         .manager
@@ -308,35 +416,207 @@ async fn get_mod_list(
         .mods
         .iter()
         .cloned()
-        .map(|(id, m)| cache::Mod {
-            title: m.meta.title,
-            description: m
-                .description
-                .unwrap_or("No description available".to_string()),
-            image: m
-                .thumbnail
-                .and_then(|t| t.data.ok())
-                .map(|d| format!("data:image/jpeg;base64,{}", base64.encode(d))),
-            colors: get_color_pair(id.as_str()).clone(),
-            categories: m
-                .meta
-                .categories
-                .into_iter()
-                .map(cache::Category::from)
-                .collect(),
-            requires_steamodded: m.meta.requires_steamodded,
-            requires_talisman: m.meta.requires_talisman,
-            publisher: m.meta.author,
-            repo: m.meta.repo,
-            download_url: m.meta.download_url,
-            folder_name: m.meta.folder_name,
-            version: Some(m.meta.version),
-            installed: false,
-            last_updated: m.meta.last_updated.unwrap_or(0),
+        .map(|(id, m)| {
+            let catalog_mod = cache::Mod {
+                title: m.meta.title,
+                description: m
+                    .description
+                    .unwrap_or("No description available".to_string()),
+                image: m
+                    .thumbnail
+                    .and_then(|t| t.data.ok())
+                    .map(|d| format!("data:image/jpeg;base64,{}", base64.encode(d))),
+                colors: get_color_pair(id.as_str()).clone(),
+                categories: m
+                    .meta
+                    .categories
+                    .into_iter()
+                    .map(cache::Category::from)
+                    .collect(),
+                requires_steamodded: m.meta.requires_steamodded,
+                requires_talisman: m.meta.requires_talisman,
+                publisher: m.meta.author,
+                repo: m.meta.repo,
+                download_url: m.meta.download_url,
+                folder_name: m.meta.folder_name,
+                version: Some(m.meta.version),
+                installed: false,
+                last_updated: m.meta.last_updated.unwrap_or(0),
+            };
+            (id.as_str().to_string(), catalog_mod)
         })
         .collect::<Vec<_>>())
 }
 
+#[tauri::command]
+async fn export_lockfile(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    path: String,
+) -> Result<(), String> {
+    let catalog = catalog_entries(&state)?;
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(lockfile::export(&db, &catalog)?.write(Path::new(&path)))
+}
+
+#[tauri::command]
+async fn import_lockfile(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState<'_, '_>>,
+    path: String,
+    job_id: Option<String>,
+) -> Result<ImportReport, String> {
+    let lockfile = Lockfile::read(Path::new(&path))?;
+    let catalog = catalog_entries(&state)?;
+    let job = start_download(&app, &state, job_id)?;
+    map_error(lockfile::import(&state.db, &lockfile, &catalog, &job).await)
+}
+
+#[tauri::command]
+async fn verify_mods(
+    state: tauri::State<'_, AppState<'_, '_>>,
+) -> Result<Vec<ModIntegrity>, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(integrity::verify(&db))
+}
+
+#[tauri::command]
+async fn repair_mods(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState<'_, '_>>,
+    names: Option<Vec<String>>,
+    job_id: Option<String>,
+) -> Result<RepairReport, String> {
+    let job = start_download(&app, &state, job_id)?;
+    map_error(integrity::repair(&state.db, names.as_deref(), &job).await)
+}
+
+#[tauri::command]
+async fn get_mod_versions(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+) -> Result<Vec<ModVersion>, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(version_history::history(&db, &name))
+}
+
+#[tauri::command]
+async fn get_operations(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    page: Option<u32>,
+    per_page: Option<u32>,
+    mod_name: Option<String>,
+) -> Result<OperationsPage, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.get_operations(
+        page.unwrap_or(1),
+        per_page.unwrap_or(operations::DEFAULT_PAGE_SIZE),
+        mod_name.as_deref(),
+    ))
+}
+
+#[tauri::command]
+async fn rollback_mod(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+    archive_hash: String,
+    job_id: Option<String>,
+) -> Result<ModVersion, String> {
+    let job = start_download(&app, &state, job_id)?;
+    map_error(version_history::rollback(&state.db, &name, &archive_hash, &job).await)
+}
+
+#[tauri::command]
+async fn get_mod_list(
+    state: tauri::State<'_, AppState<'_, '_>>,
+) -> Result<Vec<cache::Mod>, String> {
+    catalog_mods(&state)
+}
+
+#[tauri::command]
+async fn resolve_mod_dependencies(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    title: String,
+) -> Result<ResolutionPlan, String> {
+    let catalog = catalog_mods(&state)?;
+    let installed = {
+        let db = state
+            .db
+            .lock()
+            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+        InstalledSet::from_database(&db)?
+    };
+    map_error(dependencies::resolve(&title, &installed, &catalog))
+}
+
+#[tauri::command]
+async fn install_mod_with_dependencies(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState<'_, '_>>,
+    title: String,
+    force: Option<bool>,
+    job_id: Option<String>,
+) -> Result<Vec<String>, ForceableError> {
+    let catalog = catalog_mods(&state)?;
+    let job = start_download(&app, &state, job_id)?;
+    Ok(dependencies::install_with_dependencies(
+        &state.db,
+        &catalog,
+        &title,
+        force.unwrap_or(false),
+        &job,
+    )
+    .await?)
+}
+
+async fn plan_updates(
+    state: &AppState<'_, '_>,
+    names: Option<&[String]>,
+) -> Result<UpdatePlan, String> {
+    let catalog = catalog_mods(state)?;
+    let installed_mods = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
+        .get_installed_mods()?;
+    let releases = LatestReleases::fetch(&installed_mods).await;
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(updates::plan(&db, &catalog, &releases, names))
+}
+
+#[tauri::command]
+async fn plan_mod_updates(state: tauri::State<'_, AppState<'_, '_>>) -> Result<UpdatePlan, String> {
+    plan_updates(&state, None).await
+}
+
+#[tauri::command]
+async fn apply_mod_updates(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState<'_, '_>>,
+    names: Option<Vec<String>>,
+    job_id: Option<String>,
+) -> Result<UpdateReport, String> {
+    let plan = plan_updates(&state, names.as_deref()).await?;
+    let catalog = catalog_mods(&state)?;
+    let job = start_download(&app, &state, job_id)?;
+    map_error(updates::apply(&state.db, &catalog, &plan, &job).await)
+}
+
 #[tauri::command]
 async fn read_text_file(path: &str) -> Result<String, String> {
     let path = PathBuf::from(path);
@@ -414,17 +694,11 @@ async fn check_untracked_mods() -> Result<bool, String> {
 
 #[tauri::command]
 async fn get_mods_folder(state: tauri::State<'_, AppState<'_, '_>>) -> Result<String, String> {
-    #[cfg(not(target_os = "linux"))]
-    let mods_dir = get_lovely_mods_dir(None);
-    #[cfg(target_os = "linux")]
-    let mods_dir = get_lovely_mods_dir(
-        state
-            .db
-            .lock()
-            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
-            .get_installation_path()?
-            .as_ref(),
-    );
+    let mods_dir = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
+        .get_mods_dir()?;
     Ok(mods_dir.to_string_lossy().into_owned())
 }
 
@@ -450,11 +724,7 @@ async fn is_mod_enabled(
         return Err(format!("Mod directory not found: {mod_name}"));
     }
 
-    // Check if .lovelyignore file exists in the mod directory
-    let ignore_file_path = mod_dir.join(".lovelyignore");
-
-    // If the file exists, the mod is disabled
-    Ok(!ignore_file_path.exists())
+    Ok(installer::is_mod_enabled(mod_dir))
 }
 
 #[tauri::command]
@@ -462,79 +732,43 @@ async fn toggle_mod_enabled(
     state: tauri::State<'_, AppState<'_, '_>>,
     mod_name: String,
     enabled: bool,
-) -> Result<(), String> {
+    force: Option<bool>,
+) -> Result<(), ForceableError> {
     // Database lock and mod lookup remain the same
     let db = state
         .db
         .lock()
         .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
     let installed_mods = db.get_installed_mods()?;
-    let mod_dir = &installed_mods
+    let installed_mod = installed_mods
         .iter()
         .find(|m| m.name == mod_name)
-        .ok_or_else(|| format!("Mod not found: {mod_name}"))?
-        .path
-        .clone();
-    let mod_dir: &Path = Path::new(mod_dir);
+        .ok_or_else(|| format!("Mod not found: {mod_name}"))?;
+    let mod_dir: &Path = Path::new(&installed_mod.path);
 
     if !mod_dir.exists() {
-        return Err(format!("Mod directory not found: {mod_name}"));
+        return Err(format!("Mod directory not found: {mod_name}").into());
     }
 
-    // Collect entries first - this prevents the borrow of mod_dir from being split across threads
-    let entries: Vec<_> = fs::read_dir(mod_dir)
-        .map_err(|e| format!("Failed to read mod directory: {e}"))?
-        .collect::<Result<_, _>>()
-        .map_err(|e| format!("Failed to read entry: {e}"))?;
-
-    let ignore_file_path = mod_dir.join(".lovelyignore");
+    if enabled && !force.unwrap_or(false) {
+        conflicts::check_conflicts(&db.get_mods_dir()?, mod_dir)?;
+        game_version::check_mod(db.get_installation_path()?.as_ref(), mod_dir)?;
+    }
+    Ok(operations::record(
+        &db,
+        toggle_kind(enabled),
+        &[AffectedMod::from(installed_mod)],
+        None,
+        installer::set_mod_enabled(mod_dir, enabled),
+    )?)
+}
 
+fn toggle_kind(enabled: bool) -> OperationKind {
     if enabled {
-        // Process directories in parallel
-        entries
-            .par_iter()
-            .filter(|entry| entry.path().is_dir())
-            .try_for_each(|entry| {
-                let ignore_path = entry.path().join(".lovelyignore");
-                if ignore_path.exists() {
-                    fs::remove_file(&ignore_path).map_err(|e| {
-                        format!(
-                            "Failed to remove .lovelyignore in {}: {}",
-                            entry.path().display(),
-                            e
-                        )
-                    })
-                } else {
-                    Ok(())
-                }
-            })?;
-
-        // Handle the top-level ignore file
-        if ignore_file_path.exists() {
-            fs::remove_file(&ignore_file_path)
-                .map_err(|e| format!("Failed to remove top-level .lovelyignore: {e}"))?;
-        }
+        OperationKind::Enable
     } else {
-        // Process directories in parallel for disabling
-        entries
-            .par_iter()
-            .filter(|entry| entry.path().is_dir())
-            .try_for_each(|entry| {
-                fs::write(entry.path().join(".lovelyignore"), "").map_err(|e| {
-                    format!(
-                        "Failed to create .lovelyignore in {}: {}",
-                        entry.path().display(),
-                        e
-                    )
-                })
-            })?;
-
-        // Handle the top-level ignore file
-        fs::write(&ignore_file_path, "")
-            .map_err(|e| format!("Failed to create top-level .lovelyignore: {e}"))?;
+        OperationKind::Disable
     }
-
-    Ok(())
 }
 
 #[tauri::command]
@@ -546,79 +780,38 @@ async fn is_mod_enabled_by_path(mod_path: String) -> Result<bool, String> {
         return Err(format!("Mod path does not exist: {mod_path}"));
     }
 
-    // Check if .lovelyignore file exists in the mod directory
-    let ignore_file_path = path.join(".lovelyignore");
-
-    // If the file exists, the mod is disabled
-    Ok(!ignore_file_path.exists())
+    Ok(installer::is_mod_enabled(&path))
 }
 
 #[tauri::command]
-async fn toggle_mod_enabled_by_path(mod_path: String, enabled: bool) -> Result<(), String> {
+async fn toggle_mod_enabled_by_path(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    mod_path: String,
+    enabled: bool,
+    force: Option<bool>,
+) -> Result<(), ForceableError> {
     let path = PathBuf::from(&mod_path);
 
     // Check if the mod directory exists
     if !path.exists() {
-        return Err(format!("Mod path does not exist: {mod_path}"));
+        return Err(format!("Mod path does not exist: {mod_path}").into());
     }
 
-    // Read directory entries to find subdirectories
-    let entries = fs::read_dir(&path)
-        .map_err(|e| format!("Failed to read mod directory: {e}"))?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to collect directory entries: {e}"))?;
-
-    // The .lovelyignore file path in this mod's root directory
-    let ignore_file_path = path.join(".lovelyignore");
-
-    if enabled {
-        // Process subdirectories in parallel using Rayon
-        entries
-            .par_iter()
-            .filter(|entry| entry.path().is_dir())
-            .try_for_each(|entry| {
-                // Check for .lovelyignore in each subdirectory
-                let subdir_ignore = entry.path().join(".lovelyignore");
-                if subdir_ignore.exists() {
-                    fs::remove_file(&subdir_ignore).map_err(|e| {
-                        format!(
-                            "Failed to remove .lovelyignore in {}: {}",
-                            entry.path().display(),
-                            e
-                        )
-                    })
-                } else {
-                    Ok(())
-                }
-            })?;
-
-        // If enabling the mod, remove the root .lovelyignore file if it exists
-        if ignore_file_path.exists() {
-            fs::remove_file(&ignore_file_path)
-                .map_err(|e| format!("Failed to remove .lovelyignore file: {e}"))?;
-        }
-    } else {
-        // Process subdirectories in parallel using Rayon
-        entries
-            .par_iter()
-            .filter(|entry| entry.path().is_dir())
-            .try_for_each(|entry| {
-                // Create .lovelyignore in each subdirectory
-                fs::write(entry.path().join(".lovelyignore"), "").map_err(|e| {
-                    format!(
-                        "Failed to create .lovelyignore in {}: {}",
-                        entry.path().display(),
-                        e
-                    )
-                })
-            })?;
-
-        // If disabling the mod, create an empty .lovelyignore file in the root
-        fs::write(&ignore_file_path, "")
-            .map_err(|e| format!("Failed to create .lovelyignore file: {e}"))?;
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    if enabled && !force.unwrap_or(false) {
+        conflicts::check_conflicts(&db.get_mods_dir()?, &path)?;
+        game_version::check_mod(db.get_installation_path()?.as_ref(), &path)?;
     }
-
-    Ok(())
+    Ok(operations::record(
+        &db,
+        toggle_kind(enabled),
+        &[AffectedMod::at_path(&db, &path)],
+        None,
+        installer::set_mod_enabled(&path, enabled),
+    )?)
 }
 
 #[tauri::command]
@@ -627,17 +820,11 @@ async fn process_dropped_file(
     path: String,
 ) -> Result<String, String> {
     // Get the mods directory path
-    #[cfg(not(target_os = "linux"))]
-    let mods_dir = get_lovely_mods_dir(None);
-    #[cfg(target_os = "linux")]
-    let mods_dir = get_lovely_mods_dir(
-        state
-            .db
-            .lock()
-            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
-            .get_installation_path()?
-            .as_ref(),
-    );
+    let mods_dir = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
+        .get_mods_dir()?;
 
     // Create the mods directory if it doesn't exist
     fs::create_dir_all(&mods_dir).map_err(|e| format!("Failed to create mods directory: {e}"))?;
@@ -650,53 +837,23 @@ async fn process_dropped_file(
         .to_str()
         .ok_or_else(|| "Invalid file name".to_string())?;
 
-    let outdir = mods_dir.join(file_name.trim_end_matches(".zip"));
-    if outdir.exists() {
-        fs::remove_dir_all(&outdir)
-            .map_err(|e| format!("Failed to remove existing mod directory: {e}"))?;
-    }
+    let mod_name = file_name.trim_end_matches(".zip");
 
+    // Extract next to the Mods dir first, so an archive that isn't a mod
+    // leaves an installed copy of the same name alone
+    let staging = Staging::new(&mods_dir, mod_name)?;
+    let staged = staging.path().join(mod_name);
     ZipArchive::new(fs::File::open(path).map_err(|e| format!("failed to open zip file: {e}"))?)
         .map_err(|e| format!("failed to parse zip file: {e}"))?
-        .extract_unwrapped_root_dir(&outdir, root_dir_common_filter)
+        .extract_unwrapped_root_dir(&staged, root_dir_common_filter)
         .map_err(|e| format!("failed to extract mod zip: {e}"))?;
 
-    if !has_lua_files(&outdir) {
-        log::warn!(
-            "no Lua files found in the mod directory {}, will delete it :(",
-            outdir.display()
-        );
-        fs::remove_dir_all(&outdir)
-            .map_err(|e| format!("Failed to remove invalid mod directory: {e}"))?;
-
-        return Err(
-            "No Lua files found in the archive. This doesn't appear to be a valid Balatro mod."
-                .to_string(),
-        );
-    }
+    let outdir = mods_dir.join(mod_name);
+    staging.commit(&staged, &outdir)?;
 
     Ok(outdir.to_string_lossy().to_string())
 }
 
-// Helper function to check for .lua files
-fn has_lua_files(dir: &PathBuf) -> bool {
-    // Walk the directory recursively to find any .lua files
-    for entry in WalkDir::new(dir)
-        .into_iter()
-        .filter_map(std::result::Result::ok)
-    {
-        if entry.file_type().is_file() {
-            if let Some(extension) = entry.path().extension() {
-                if extension == "lua" {
-                    return true;
-                }
-            }
-        }
-    }
-
-    false
-}
-
 /// Process a mod archive from raw binary data (alternative approach if needed)
 #[tauri::command]
 fn process_mod_archive(
@@ -706,17 +863,11 @@ fn process_mod_archive(
 ) -> Result<String, String> {
     // Get the mods directory path
 
-    #[cfg(not(target_os = "linux"))]
-    let mods_dir = get_lovely_mods_dir(None);
-    #[cfg(target_os = "linux")]
-    let mods_dir = get_lovely_mods_dir(
-        state
-            .db
-            .lock()
-            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
-            .get_installation_path()?
-            .as_ref(),
-    );
+    let mods_dir = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
+        .get_mods_dir()?;
 
     // Create the mods directory if it doesn't exist
     fs::create_dir_all(&mods_dir).map_err(|e| format!("Failed to create mods directory: {e}"))?;
@@ -728,30 +879,25 @@ fn process_mod_archive(
         .trim_end_matches(".tar.gz")
         .trim_end_matches(".tgz");
 
-    // Create a target directory for this mod
-    let outdir = mods_dir.join(mod_name);
-
-    // If the mod directory already exists, remove it first
-    if outdir.exists() {
-        fs::remove_dir_all(&outdir)
-            .map_err(|e| format!("Failed to remove existing mod directory: {e}"))?;
-    }
+    // Extract next to the Mods dir first, like process_dropped_file
+    let staging = Staging::new(&mods_dir, mod_name)?;
+    let staged = staging.path().join(mod_name);
 
     // Create a cursor for the data
     let cursor = Cursor::new(data);
 
     // Process based on file extension
-    extract_zip_from_memory(cursor, &outdir)?;
+    extract_zip_from_memory(cursor, &staged)?;
 
     // Check for nested directories (same as in process_dropped_file)
-    if let Ok(entries) = fs::read_dir(&outdir) {
+    if let Ok(entries) = fs::read_dir(&staged) {
         let dirs: Vec<_> = entries
             .filter_map(Result::ok)
             .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
             .collect();
 
         if dirs.len() == 1
-            && fs::read_dir(&outdir)
+            && fs::read_dir(&staged)
                 .map(std::iter::Iterator::count)
                 .unwrap_or(0)
                 == 1
@@ -762,7 +908,7 @@ fn process_mod_archive(
                 .map_err(|e| format!("Failed to read nested directory: {e}"))?
             {
                 let entry = entry.map_err(|e| format!("Failed to read entry: {e}"))?;
-                let target_path = outdir.join(entry.file_name());
+                let target_path = staged.join(entry.file_name());
 
                 if entry
                     .file_type()
@@ -782,6 +928,9 @@ fn process_mod_archive(
         }
     }
 
+    let outdir = mods_dir.join(mod_name);
+    staging.commit(&staged, &outdir)?;
+
     // Return the path to the installed mod
     Ok(outdir.to_string_lossy().to_string())
 }
@@ -842,10 +991,7 @@ async fn refresh_mods_folder(state: tauri::State<'_, AppState<'_, '_>>) -> Resul
         .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
     let installed_mods = db.get_installed_mods()?;
 
-    #[cfg(not(target_os = "linux"))]
-    let mods_dir = get_lovely_mods_dir(None);
-    #[cfg(target_os = "linux")]
-    let mods_dir = get_lovely_mods_dir(db.get_installation_path()?.as_ref());
+    let mods_dir = db.get_mods_dir()?;
 
     let entries = std::fs::read_dir(&mods_dir).map_err(|e| AppError::FileRead {
         path: mods_dir.clone(),
@@ -872,257 +1018,163 @@ async fn refresh_mods_folder(state: tauri::State<'_, AppState<'_, '_>>) -> Resul
             source: e.to_string(),
         })?;
 
-        match (ft.is_dir(), ft.is_file()) {
-            (true, _) => {
-                if !installed_mods.iter().any(|m| m.path.contains(name)) {
-                    std::fs::remove_dir_all(&path).map_err(|e| AppError::FileWrite {
-                        path: path.clone(),
-                        source: e.to_string(),
-                    })?;
-                }
-            }
-            (_, true) => {
-                if !installed_mods.iter().any(|m| m.path.contains(name)) {
-                    std::fs::remove_file(&path).map_err(|e| AppError::FileWrite {
-                        path: path.clone(),
-                        source: e.to_string(),
-                    })?;
-                }
-            }
-            _ => {}
+        if installed_mods.iter().any(|m| m.path.contains(name)) {
+            continue;
         }
+        let removed = match (ft.is_dir(), ft.is_file()) {
+            (true, _) => std::fs::remove_dir_all(&path),
+            (_, true) => std::fs::remove_file(&path),
+            _ => continue,
+        };
+        operations::record(
+            &db,
+            OperationKind::Cleanup,
+            &[AffectedMod::new(name, None)],
+            Some(format!("Deleted untracked {}", path.display()).as_str()),
+            removed.map_err(|e| AppError::FileWrite {
+                path: path.clone(),
+                source: e.to_string(),
+            }),
+        )?;
     }
     Ok(())
 }
 
-#[allow(clippy::too_many_lines)]
-async fn _launch_balatro(path: &PathBuf, vanilla: bool) -> Result<(), String> {
-    #[cfg(target_os = "macos")]
-    {
-        let lovely_path = map_error(lovely::ensure_lovely_exists().await)?;
-        let balatro_executable = path.join("Balatro.app/Contents/MacOS/love");
-
-        // Instead of using double quotes which cause conflicts in AppleScript,
-        // wrap the file paths in single quotes.
-        let command_line = format!(
-            "cd '{}' && {} DYLD_INSERT_LIBRARIES='{}' '{}'",
-            path.display(),
-            if vanilla {
-                format!(
-                    "LOVELY_MOD_DIR={}",
-                    tempfile::tempdir().unwrap().path().display()
-                )
-            } else {
-                ""
-            },
-            lovely_path.display(),
-            balatro_executable.display(),
-        );
-
-        // Construct the AppleScript command to run the command_line in Terminal.
-        let applescript = format!(
-            "tell application \"Terminal\" to do script \"{}\"",
-            command_line
-        );
-
-        Command::new("osascript")
-            .arg("-e")
-            .arg(applescript)
-            .spawn()
-            .map_err(|e| AppError::ProcessExecution(e.to_string()))?;
-    }
+#[tauri::command]
+async fn launch_balatro(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState<'_, '_>>,
+    vanilla: bool,
+    force: Option<bool>,
+    job_id: Option<String>,
+) -> Result<VersionCheck, ForceableError> {
+    let path_str = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
+        .get_installation_path()?
+        .ok_or_else(|| AppError::InvalidState("No installation path set".to_string()))?;
+    let path = PathBuf::from(path_str);
 
-    #[cfg(target_os = "windows")]
-    {
-        // Find the executable file in the directory
-        let exe_path = find_executable_in_directory(path)
-            .ok_or_else(|| format!("No executable found in {}", path.display()))?;
-
-        // If version.dll doesn't exist, download it
-        lovely::ensure_version_dll_exists(path)
-            .await
-            .inspect_err(|_| log::error!("Failed to install `lovely`"))?;
-
-        let mut command = Command::new(&exe_path);
-        command.current_dir(path);
-        if vanilla {
-            command.env("LOVELY_MOD_DIR", tempfile::tempdir().unwrap().path());
-        }
-        command
-            .spawn()
-            .map_err(|e| format!("Failed to launch {}: {}", exe_path.display(), e))?;
+    let job = start_download(&app, &state, job_id)?;
+    Ok(launcher::launch_balatro(&state.db, &path, vanilla, force.unwrap_or(false), &job).await?)
+}
 
-        log::debug!("Launched game from {}", exe_path.display());
-    }
+#[tauri::command]
+async fn get_game_version(
+    state: tauri::State<'_, AppState<'_, '_>>,
+) -> Result<VersionCheck, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    let path_str = db
+        .get_installation_path()?
+        .ok_or_else(|| AppError::InvalidState("No installation path set".to_string()))?;
+    map_error(game_version::compare(&db, Path::new(&path_str)))
+}
 
-    #[cfg(target_os = "linux")]
-    {
-        const BALATRO_STEAMID: &str = "2379780";
+#[tauri::command]
+async fn get_incompatible_mods(
+    state: tauri::State<'_, AppState<'_, '_>>,
+) -> Result<Vec<Incompatibility>, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(game_version::incompatible_mods(
+        db.get_installation_path()?.as_ref(),
+        &db.get_mods_dir()?,
+    ))
+}
 
-        lovely::ensure_version_dll_exists(path)
-            .await
-            .inspect_err(|_| log::error!("Failed to install `lovely`"))?;
+#[tauri::command]
+async fn get_lovely_releases() -> Result<Vec<LovelyRelease>, String> {
+    map_error(lovely::list_releases().await)
+}
 
-        if let Ok(steam_exe) = which::which("steam") {
-            let mut command = Command::new(steam_exe);
-            command.args(vec!["-applaunch", BALATRO_STEAMID]);
-            if vanilla {
-                command.env("LOVELY_MOD_DIR", tempfile::tempdir().unwrap().path());
-            }
-            if command.spawn().is_ok() {
-                log::debug!("Launched Balatro through Steam executable");
-                return Ok(());
-            }
-        }
+#[tauri::command]
+async fn get_lovely_status(
+    state: tauri::State<'_, AppState<'_, '_>>,
+) -> Result<LovelyStatus, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(lovely::installation_status(&db))
+}
 
-        let url_handler = Command::new("xdg-mime")
-            .args(["query", "default", "x-scheme-handler/steam"])
-            .output()
-            .map_err(|e| format!("Failed to query `steam://` handler: {e}"))
-            .and_then(|output| {
-                let output = String::from_utf8_lossy(&output.stdout);
-                if output.trim().is_empty() {
-                    return Err("No default `steam://` handler found".to_string());
-                }
-                if output.trim() != "steam.desktop" {
-                    log::warn!(
-                        "The system's default `steam://` handler is {output} instead of steam"
-                    );
-                }
-                Ok(())
-            });
-        if url_handler.is_ok() && {
-            let mut command = Command::new("xdg-open");
-            if vanilla {
-                command.env("LOVELY_MOD_DIR", tempfile::tempdir().unwrap().path());
-            }
-            command
-                .arg(format!("steam://run/{BALATRO_STEAMID}"))
-                .spawn()
-                .is_ok()
-        } {
-            log::debug!("launched balatro through steam url protocol");
-            return Ok(());
-        }
+#[tauri::command]
+async fn get_lovely_patch_report(
+    state: tauri::State<'_, AppState<'_, '_>>,
+) -> Result<PatchReport, String> {
+    let mods_dir = {
+        let db = state
+            .db
+            .lock()
+            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+        db.get_mods_dir()?
+    };
+    map_error(lovely_patches::analyse(&mods_dir))
+}
 
-        let balatro_exe = find_executable_in_directory(path)
-            .ok_or_else(|| format!("No executable found in {}", path.display()))
-            .inspect_err(|e| log::error!("{e}"))?;
-        let mut command = Command::new(&balatro_exe);
-        command
-            .current_dir(path)
-            .env("WINEDLLOVERRIDES", "version=n,b");
-        if vanilla {
-            command.env("LOVELY_MOD_DIR", tempfile::tempdir().unwrap().path());
+#[tauri::command]
+async fn preview_lovely_patches(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    source_dir: Option<String>,
+    targets: Option<Vec<String>>,
+) -> Result<PatchPreview, String> {
+    let (installation_path, mods_dir) = {
+        let db = state
+            .db
+            .lock()
+            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+        (db.get_installation_path()?, db.get_mods_dir()?)
+    };
+    let source_dir = match source_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let game_dir = installation_path
+                .as_ref()
+                .ok_or_else(|| AppError::InvalidState("No installation path set".to_string()))?;
+            game_source::extract(Path::new(game_dir))?.dir
         }
-
-        log::debug!("attempting to launch {}", balatro_exe.display());
-        command
-            .spawn()
-            .map_err(|e| format!("failed to launch {}: {}", balatro_exe.display(), e))?;
-        log::debug!("launched Balatro.exe directly with WINEDLLOVERRIDES");
-    }
-
-    Ok(())
+    };
+    let report = lovely_patches::analyse(&mods_dir)?;
+    map_error(patch_preview::preview(
+        &source_dir,
+        &report.mods,
+        &targets.unwrap_or_default(),
+    ))
 }
+
 #[tauri::command]
-async fn launch_balatro(
+async fn extract_game_source(
     state: tauri::State<'_, AppState<'_, '_>>,
-    vanilla: bool,
-) -> Result<(), String> {
+) -> Result<GameSource, String> {
     let path_str = state
         .db
         .lock()
         .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
         .get_installation_path()?
         .ok_or_else(|| AppError::InvalidState("No installation path set".to_string()))?;
-    let path = PathBuf::from(path_str);
-
-    _launch_balatro(&path, vanilla).await
-}
-
-// Add this helper function for Linux if it doesn't exist already
-#[cfg(target_os = "linux")]
-fn find_executable_in_directory(dir: &Path) -> Option<PathBuf> {
-    if let Ok(entries) = std::fs::read_dir(dir) {
-        let mut executables: Vec<PathBuf> = Vec::new();
-
-        // Collect all executable files
-        for entry in entries.filter_map(Result::ok) {
-            let path = entry.path();
-            if path.is_file() {
-                // Check if file is executable on Linux
-                if let Ok(metadata) = std::fs::metadata(&path) {
-                    use std::os::unix::fs::PermissionsExt;
-                    let permissions = metadata.permissions();
-                    if permissions.mode() & 0o111 != 0 {
-                        executables.push(path);
-                    }
-                }
-            }
-        }
-
-        if executables.is_empty() {
-            return None;
-        }
-
-        // First look for any executable with "balatro" in the name
-        for exe in &executables {
-            if let Some(file_name) = exe.file_name().and_then(|n| n.to_str()) {
-                if file_name.to_lowercase().contains("balatro") {
-                    return Some(exe.clone());
-                }
-            }
-        }
-
-        // Then look for "love" executable
-        for exe in &executables {
-            if let Some(file_name) = exe.file_name().and_then(|n| n.to_str()) {
-                if file_name.to_lowercase() == "love" {
-                    return Some(exe.clone());
-                }
-            }
-        }
-
-        // If no specific executable was found, return the first one
-        return Some(executables[0].clone());
-    }
-
-    None
+    map_error(game_source::extract(Path::new(&path_str)))
 }
 
-#[cfg(target_os = "windows")]
-fn find_executable_in_directory(dir: &PathBuf) -> Option<PathBuf> {
-    if let Ok(entries) = std::fs::read_dir(dir) {
-        // Create a Vec to hold all executable files
-        let mut executables: Vec<PathBuf> = Vec::new();
-
-        // First, collect all executable files in the directory
-        for entry in entries.filter_map(Result::ok) {
-            let path = entry.path();
-            if path.is_file() && path.extension() == Some(std::ffi::OsStr::new("exe")) {
-                executables.push(path);
-            }
-        }
-
-        if executables.is_empty() {
-            return None;
-        }
-
-        // First, look for any executable with "balatro" in the name (case-insensitive)
-        for exe in &executables {
-            if let Some(file_name) = exe.file_name().and_then(|n| n.to_str()) {
-                if file_name.to_lowercase().contains("balatro") {
-                    return Some(exe.clone());
-                }
-            }
-        }
-
-        // If no Balatro-specific executable was found, return the first executable
-        return Some(executables[0].clone());
-    }
+#[tauri::command]
+async fn get_game_sources() -> Result<Vec<GameSource>, String> {
+    map_error(game_source::list())
+}
 
-    None
+#[tauri::command]
+async fn install_lovely(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState<'_, '_>>,
+    tag: Option<String>,
+    job_id: Option<String>,
+) -> Result<LovelyInstall, String> {
+    let job = start_download(&app, &state, job_id)?;
+    map_error(lovely::install_release(&state.db, tag.as_deref(), &job).await)
 }
 
 #[tauri::command]
@@ -1144,30 +1196,6 @@ async fn check_mod_installation(mod_type: String) -> Result<bool, String> {
     }
 }
 
-fn is_valid_game_dir(game_dir: &Path) -> bool {
-    #[cfg(any(target_os = "linux", target_os = "windows"))]
-    let exe = PathBuf::from(&game_dir).join("Balatro.exe");
-
-    #[cfg(target_os = "macos")]
-    let exe = PathBuf::from(&game_dir).join("Balatro.app/Contents/Resources/Balatro.love");
-
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
-    return exe.exists();
-
-    #[cfg(target_os = "windows")]
-    {
-        let dll_files = ["love.dll", "lua51.dll", "SDL2.dll"];
-        // TODO: lint on windows and confirm the clone is unnecessary
-        let dir = game_dir.clone();
-
-        for dll in dll_files.iter() {
-            if game_dir.join(dll).exists() {
-                return true;
-            }
-        }
-        false
-    }
-}
 #[tauri::command]
 async fn check_existing_installation(
     state: tauri::State<'_, AppState<'_, '_>>,
@@ -1189,10 +1217,13 @@ async fn check_existing_installation(
 
 #[tauri::command]
 async fn install_mod(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState<'_, '_>>,
     url: String,
     folder_name: String,
-) -> Result<PathBuf, String> {
+    force: Option<bool>,
+    job_id: Option<String>,
+) -> Result<PathBuf, ForceableError> {
     let folder_name = {
         if folder_name.is_empty() {
             None
@@ -1201,13 +1232,57 @@ async fn install_mod(
         }
     };
 
-    let installation_path = state
-        .db
-        .lock()
-        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
-        .get_installation_path()?;
+    // The catalog has no hashes, but an archive recorded for the same version
+    // before can be installed from the cache
+    let version = catalog_mods(&state)
+        .ok()
+        .and_then(|catalog| catalog.into_iter().find(|m| m.download_url == url))
+        .and_then(|m| m.version);
+    let (mods_dir, expected_hash) = {
+        let db = state
+            .db
+            .lock()
+            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+        let expected_hash = version_history::recorded_archive(&db, &url, version.as_deref())?;
+        (db.get_mods_dir()?, expected_hash)
+    };
 
-    map_error(bmm_lib::installer::install_mod(installation_path.as_ref(), url, folder_name).await)
+    let job = start_download(&app, &state, job_id)?;
+    let fallback_name = folder_name.clone().unwrap_or_else(|| url.clone());
+    let detail = format!("From {url}");
+    let check = conflicts::staged_check(&mods_dir);
+    let result = async {
+        let (installed_path, source) = bmm_lib::installer::install_mod_with_source(
+            &mods_dir,
+            url,
+            folder_name,
+            expected_hash.as_deref(),
+            (!force.unwrap_or(false)).then_some(&check),
+            &job,
+        )
+        .await?;
+        state
+            .db
+            .lock()
+            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
+            .set_mod_source(&installed_path.to_string_lossy(), &source)?;
+        Ok(installed_path)
+    }
+    .await;
+    let affected = match &result {
+        Ok(installed_path) => local_mod_detection::detect_mod(installed_path).map_or_else(
+            || AffectedMod::new(fallback_name, None),
+            |detected| AffectedMod::new(detected.name, detected.version),
+        ),
+        Err(_) => AffectedMod::new(fallback_name, None),
+    };
+    Ok(operations::record_locked(
+        &state.db,
+        OperationKind::Install,
+        &[affected],
+        Some(detail.as_str()),
+        result,
+    )?)
 }
 
 #[tauri::command]
@@ -1237,7 +1312,13 @@ async fn add_installed_mod(
             Some(current_version)
         }
     };
-    map_error(db.add_installed_mod(&name, &path, &dependencies, current_version))
+    // The install already stored where the mod came from and recorded the
+    // operation; the version history is only written now that the version is
+    // known as well
+    map_error(db.get_mod_source(&path).and_then(|source| match source {
+        Some(source) => db.record_install(&name, &path, &dependencies, current_version, &source),
+        None => db.add_installed_mod(&name, &path, &dependencies, current_version),
+    }))
 }
 
 #[tauri::command]
@@ -1248,13 +1329,17 @@ async fn force_remove_mod(
 ) -> Result<(), String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
 
-    let installation_path = db.get_installation_path()?;
-    map_error(bmm_lib::installer::uninstall_mod(
-        installation_path.as_ref(),
-        PathBuf::from(path),
-    ))?;
-
-    map_error(db.remove_installed_mod(&name))
+    let mods_dir = db.get_mods_dir()?;
+    let affected = AffectedMod::at_path(&db, Path::new(&path));
+    let result = bmm_lib::installer::uninstall_mod(&mods_dir, PathBuf::from(path))
+        .and_then(|()| db.remove_installed_mod(&name));
+    map_error(operations::record(
+        &db,
+        OperationKind::Uninstall,
+        &[affected],
+        Some("Removed without checking dependents"),
+        result,
+    ))
 }
 
 // Update the reindex_mods function to only clean database entries
@@ -1266,23 +1351,201 @@ async fn reindex_mods(state: tauri::State<'_, AppState<'_, '_>>) -> Result<(usiz
         .map_err(|e| AppError::LockPoisoned(format!("Database lock poisoned: {e}")))?;
 
     // Database cleanup only - don't touch the filesystem
-    let installed_mods = db.get_installed_mods().map_err(|e| e.to_string())?;
-    let mut to_remove = Vec::new();
-
-    for (index, installed_mod) in installed_mods.iter().enumerate() {
-        if !PathBuf::from(&installed_mod.path).exists() {
-            to_remove.push(index);
-        }
+    let removed = map_error(db.remove_missing_mods())?;
+    if !removed.is_empty() {
+        operations::record(
+            &db,
+            OperationKind::Reindex,
+            &removed.iter().map(AffectedMod::from).collect::<Vec<_>>(),
+            Some("Directories no longer exist"),
+            Ok(()),
+        )?;
     }
 
-    let cleaned_entries = to_remove.len();
-    for &index in to_remove.iter().rev() {
-        db.remove_installed_mod(&installed_mods[index].name)
-            .map_err(|e| e.to_string())?;
-    }
+    Ok((0, removed.len()))
+}
 
-    // Return 0 for files_removed since we don't remove any files
-    Ok((0, cleaned_entries))
+#[tauri::command]
+async fn get_mod_collections(
+    state: tauri::State<'_, AppState<'_, '_>>,
+) -> Result<Vec<ModCollection>, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.collections().list())
+}
+
+/// Creates a collection from the mods that are enabled right now.
+#[tauri::command]
+async fn create_mod_collection(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+) -> Result<(), String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    let mods_dir = db.get_mods_dir()?;
+    map_error(
+        db.collections()
+            .create(&name, &mod_collections::enabled_mods(&mods_dir)?),
+    )
+}
+
+/// Overwrites a collection with the mods that are enabled right now.
+#[tauri::command]
+async fn save_mod_collection(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+) -> Result<(), String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    let mods_dir = db.get_mods_dir()?;
+    map_error(
+        db.collections()
+            .set_enabled_mods(&name, &mod_collections::enabled_mods(&mods_dir)?),
+    )
+}
+
+#[tauri::command]
+async fn rename_mod_collection(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+    new_name: String,
+) -> Result<(), String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.collections().rename(&name, &new_name))
+}
+
+#[tauri::command]
+async fn clone_mod_collection(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+    new_name: String,
+) -> Result<(), String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.collections().clone_collection(&name, &new_name))
+}
+
+#[tauri::command]
+async fn delete_mod_collection(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+) -> Result<(), String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.collections().delete(&name))
+}
+
+#[tauri::command]
+async fn switch_mod_collection(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+    force: Option<bool>,
+) -> Result<(), ForceableError> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    let mods_dir = db.get_mods_dir()?;
+    let toggles = db
+        .collections()
+        .switch(&name, &mods_dir, force.unwrap_or(false))?;
+    Ok(mod_collections::record_switch(&db, &name, toggles)?)
+}
+
+#[tauri::command]
+async fn get_installations(
+    state: tauri::State<'_, AppState<'_, '_>>,
+) -> Result<Vec<Installation>, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.installations().list())
+}
+
+#[tauri::command]
+async fn add_installation(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+    game_dir: String,
+    mods_dir: Option<String>,
+) -> Result<Installation, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.installations().add(
+        &name,
+        Path::new(&game_dir),
+        mods_dir.as_deref().map(Path::new),
+    ))
+}
+
+#[tauri::command]
+async fn rename_installation(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+    new_name: String,
+) -> Result<(), String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.installations().rename(&name, &new_name))
+}
+
+#[tauri::command]
+async fn set_installation_mods_dir(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+    mods_dir: Option<String>,
+) -> Result<(), String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(
+        db.installations()
+            .set_mods_dir(&name, mods_dir.as_deref().map(Path::new)),
+    )
+}
+
+#[tauri::command]
+async fn remove_installation(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+) -> Result<(), String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.installations().remove(&name))
+}
+
+/// Makes installation `name` the one every other command acts on.
+#[tauri::command]
+async fn switch_installation(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    name: String,
+) -> Result<Installation, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.installations().switch(&name))
 }
 
 #[tauri::command]
@@ -1300,17 +1563,11 @@ async fn delete_manual_mod(
         ));
     }
 
-    #[cfg(not(target_os = "linux"))]
-    let mods_dir = get_lovely_mods_dir(None);
-    #[cfg(target_os = "linux")]
-    let mods_dir = get_lovely_mods_dir(
-        state
-            .db
-            .lock()
-            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
-            .get_installation_path()?
-            .as_ref(),
-    );
+    let mods_dir = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
+        .get_mods_dir()?;
 
     // Security check: Make sure the path is within the Mods directory
     let canonicalized_path = match path.canonicalize() {
@@ -1340,19 +1597,28 @@ async fn delete_manual_mod(
     log::info!("Deleting manual mod at path: {}", path.display());
 
     // Delete the directory or file
-    if path.is_dir() {
-        match std::fs::remove_dir_all(&path) {
-            Ok(()) => log::info!("Successfully removed directory: {}", path.display()),
-            Err(e) => return Err(format!("Failed to remove directory: {e}")),
-        }
+    let removed = if path.is_dir() {
+        std::fs::remove_dir_all(&path)
     } else {
-        match std::fs::remove_file(&path) {
-            Ok(()) => log::info!("Successfully removed file: {}", path.display()),
-            Err(e) => return Err(format!("Failed to remove file: {e}")),
-        }
+        std::fs::remove_file(&path)
+    };
+    if removed.is_ok() {
+        log::info!("Successfully removed {}", path.display());
     }
-
-    Ok(())
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(operations::record(
+        &db,
+        OperationKind::Uninstall,
+        &[AffectedMod::at_path(&db, &path)],
+        Some(format!("Deleted manually installed {}", path.display()).as_str()),
+        removed.map_err(|e| AppError::FileWrite {
+            path: path.clone(),
+            source: e.to_string(),
+        }),
+    ))
 }
 
 #[tauri::command]
@@ -1387,31 +1653,7 @@ async fn cascade_uninstall(
     root_mod: String,
 ) -> Result<(), String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
-    let mut to_uninstall = vec![root_mod.clone()];
-    let mut processed = HashSet::new();
-
-    while let Some(current) = to_uninstall.pop() {
-        if processed.contains(&current) {
-            continue;
-        }
-        processed.insert(current.clone());
-
-        // Get mod details
-        let mod_details = map_error(db.get_mod_details(&current))?;
-
-        // Add dependents to queue
-        let dependents = map_error(db.get_dependents(&current))?;
-        to_uninstall.extend(dependents);
-
-        // Perform actual uninstall
-        let installation_path = db.get_installation_path()?;
-        map_error(bmm_lib::installer::uninstall_mod(
-            installation_path.as_ref(),
-            PathBuf::from(mod_details.path),
-        ))?;
-        map_error(db.remove_installed_mod(&current))?;
-    }
-
+    map_error(installer::cascade_uninstall(&db, &root_mod))?;
     Ok(())
 }
 
@@ -1444,12 +1686,17 @@ async fn remove_installed_mod(
         }
     }
 
-    let installation_path = db.get_installation_path()?;
-    map_error(bmm_lib::installer::uninstall_mod(
-        installation_path.as_ref(),
-        PathBuf::from(path),
-    ))?;
-    map_error(db.remove_installed_mod(&name))
+    let mods_dir = db.get_mods_dir()?;
+    let affected = AffectedMod::at_path(&db, Path::new(&path));
+    let result = bmm_lib::installer::uninstall_mod(&mods_dir, PathBuf::from(path))
+        .and_then(|()| db.remove_installed_mod(&name));
+    map_error(operations::record(
+        &db,
+        OperationKind::Uninstall,
+        &[affected],
+        None,
+        result,
+    ))
 }
 
 #[tauri::command]
@@ -1469,7 +1716,8 @@ async fn set_balatro_path(
         Ok(db) => db,
         Err(e) => return Err(e.to_string()),
     };
-    map_error(db.set_installation_path(&path))
+    map_error(db.update_settings(|settings| settings.installation_path = Some(path)))?;
+    Ok(())
 }
 
 #[tauri::command]
@@ -1483,7 +1731,8 @@ async fn find_steam_balatro(
         .collect::<Vec<_>>();
     if let Some(path) = balatros.first() {
         let db = state.db.lock().map_err(|e| e.to_string())?;
-        map_error(db.set_installation_path(&path.to_string_lossy()))?;
+        let path = path.to_string_lossy().into_owned();
+        map_error(db.update_settings(|settings| settings.installation_path = Some(path)))?;
     }
     Ok(balatros
         .iter()
@@ -1494,69 +1743,77 @@ async fn find_steam_balatro(
 #[tauri::command]
 async fn get_steamodded_versions(
     state: tauri::State<'_, AppState<'_, '_>>,
-) -> Result<Vec<String>, String> {
-    let installer = ModInstaller::new(
-        state
-            .db
-            .lock()
-            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
-            .get_installation_path()?
-            .as_ref(),
-        ModType::Steamodded,
-    );
+) -> Result<AllowedVersions, String> {
+    let installer = ModInstaller::new(None, ModType::Steamodded);
 
-    installer
+    let versions = installer
         .get_available_versions()
         .await
-        .map(|versions| versions.into_iter().map(|v| v.to_string()).collect())
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(pins::allowed_versions(&db, "Steamodded", versions))
 }
 
 #[tauri::command]
 async fn install_steamodded_version(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState<'_, '_>>,
     version: String,
+    job_id: Option<String>,
 ) -> Result<String, String> {
-    let installer = ModInstaller::new(
+    let mods_dir = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
+        .get_mods_dir()?;
+    let installer = ModInstaller::new(Some(&mods_dir), ModType::Steamodded);
+    let job = start_download(&app, &state, job_id)?;
+    let result = async {
+        let (path, source) = installer
+            .install_version_with_source(&version, &job)
+            .await?;
         state
             .db
             .lock()
             .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
-            .get_installation_path()?
-            .as_ref(),
-        ModType::Steamodded,
-    );
-    installer
-        .install_version(&version)
-        .await
-        .map_err(|e| e.to_string())
+            .set_mod_source(&path, &source)?;
+        Ok(path)
+    }
+    .await;
+    map_error(operations::record_locked(
+        &state.db,
+        OperationKind::FrameworkInstall,
+        &[AffectedMod::new(
+            ModType::Steamodded.to_string(),
+            Some(version),
+        )],
+        None,
+        result,
+    ))
 }
 
 #[tauri::command]
 async fn get_talisman_versions(
     state: tauri::State<'_, AppState<'_, '_>>,
-) -> Result<Vec<String>, String> {
-    let installer = ModInstaller::new(
-        state
-            .db
-            .lock()
-            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
-            .get_installation_path()?
-            .as_ref(),
-        ModType::Talisman,
-    );
+) -> Result<AllowedVersions, String> {
+    let installer = ModInstaller::new(None, ModType::Talisman);
 
-    installer
+    let versions = installer
         .get_available_versions()
         .await
-        .map(|versions| versions.into_iter().map(|v| v.to_string()).collect())
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(pins::allowed_versions(&db, "Talisman", versions))
 }
 
 #[tauri::command]
-async fn get_latest_steamodded_release(
-    state: tauri::State<'_, AppState<'_, '_>>,
-) -> Result<String, String> {
+async fn get_latest_steamodded_release() -> Result<String, String> {
     if let Ok(Some(versions)) = cache::load_versions_cache("steamodded") {
         if !versions.is_empty() {
             // We have cached versions, use the first one (most recent)
@@ -1567,15 +1824,7 @@ async fn get_latest_steamodded_release(
         }
     }
 
-    let installer = ModInstaller::new(
-        state
-            .db
-            .lock()
-            .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
-            .get_installation_path()?
-            .as_ref(),
-        ModType::Steamodded,
-    );
+    let installer = ModInstaller::new(None, ModType::Steamodded);
 
     installer
         .get_latest_release()
@@ -1588,23 +1837,40 @@ async fn get_latest_steamodded_release(
 
 #[tauri::command]
 async fn install_talisman_version(
+    app: tauri::AppHandle,
     state: tauri::State<'_, AppState<'_, '_>>,
     version: String,
+    job_id: Option<String>,
 ) -> Result<String, String> {
-    let installer = ModInstaller::new(
+    let mods_dir = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
+        .get_mods_dir()?;
+    let installer = ModInstaller::new(Some(&mods_dir), ModType::Talisman);
+    let job = start_download(&app, &state, job_id)?;
+    let result = async {
+        let (path, source) = installer
+            .install_version_with_source(&version, &job)
+            .await?;
         state
             .db
             .lock()
             .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?
-            .get_installation_path()?
-            .as_ref(),
-        ModType::Talisman,
-    );
-
-    installer
-        .install_version(&version)
-        .await
-        .map_err(|e| e.to_string())
+            .set_mod_source(&path, &source)?;
+        Ok(path)
+    }
+    .await;
+    map_error(operations::record_locked(
+        &state.db,
+        OperationKind::FrameworkInstall,
+        &[AffectedMod::new(
+            ModType::Talisman.to_string(),
+            Some(version),
+        )],
+        None,
+        result,
+    ))
 }
 
 #[tauri::command]
@@ -1764,10 +2030,7 @@ async fn remove_backup(path: String) -> Result<(), String> {
 }
 
 fn get_backup_dir() -> Result<PathBuf, String> {
-    let temp_dir = std::env::temp_dir().join("bromomethane_backups");
-    std::fs::create_dir_all(&temp_dir)
-        .map_err(|e| format!("Failed to create backup directory: {e}"))?;
-    Ok(temp_dir)
+    map_error(paths::ensure_dir(Paths::get()?.backup_dir.clone()))
 }
 
 fn copy_dir_all(src: &Path, dst: &Path) -> std::io::Result<()> {
@@ -1792,7 +2055,8 @@ async fn set_background_state(
     enabled: bool,
 ) -> Result<(), String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
-    map_error(db.set_background_enabled(enabled))
+    map_error(db.update_settings(|settings| settings.background_enabled = enabled))?;
+    Ok(())
 }
 
 #[tauri::command]
@@ -1825,7 +2089,8 @@ async fn check_custom_balatro(
     let is_valid = is_valid_game_dir(&path_to_check);
     if is_valid {
         let db = state.db.lock().map_err(|e| e.to_string())?;
-        map_error(db.set_installation_path(&path_to_check.to_string_lossy()))?;
+        let path = path_to_check.to_string_lossy().into_owned();
+        map_error(db.update_settings(|settings| settings.installation_path = Some(path)))?;
     }
     Ok(is_valid)
 }
@@ -1835,7 +2100,7 @@ async fn is_security_warning_acknowledged(
     state: tauri::State<'_, AppState<'_, '_>>,
 ) -> Result<bool, String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
-    map_error(db.is_security_warning_acknowledged())
+    Ok(map_error(db.get_settings())?.security_warning_acknowledged)
 }
 
 #[tauri::command]
@@ -1844,7 +2109,55 @@ async fn set_security_warning_acknowledged(
     acknowledged: bool,
 ) -> Result<(), String> {
     let db = state.db.lock().map_err(|e| e.to_string())?;
-    map_error(db.set_security_warning_acknowledged(acknowledged))
+    map_error(
+        db.update_settings(|settings| settings.security_warning_acknowledged = acknowledged),
+    )?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_settings(state: tauri::State<'_, AppState<'_, '_>>) -> Result<Settings, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.get_settings())
+}
+
+#[tauri::command]
+async fn set_settings(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    settings: Settings,
+) -> Result<(), String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(db.set_settings(&settings))
+}
+
+#[tauri::command]
+async fn export_settings(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    path: String,
+) -> Result<(), String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(settings::export(&db, Path::new(&path)))
+}
+
+#[tauri::command]
+async fn import_settings(
+    state: tauri::State<'_, AppState<'_, '_>>,
+    path: String,
+) -> Result<Settings, String> {
+    let db = state
+        .db
+        .lock()
+        .map_err(|_| AppError::LockPoisoned("Database lock poisoned".to_string()))?;
+    map_error(settings::import(&db, Path::new(&path)))
 }
 
 #[tauri::command]
@@ -1875,6 +2188,7 @@ pub fn run() {
 
             app.manage(AppState {
                 db: Mutex::new(db),
+                downloads: DownloadJobs::default(),
                 reqwest: reqwest::Client::new(),
                 manager: RwLock::new(ModManager::new(
                     ModIndex::default(),
@@ -1900,8 +2214,11 @@ pub fn run() {
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            add_installation,
             add_installed_mod,
+            apply_mod_updates,
             backup_local_mod,
+            cancel_download,
             cascade_uninstall,
             check_custom_balatro,
             check_existing_installation,
@@ -1909,25 +2226,48 @@ pub fn run() {
             check_steam_running,
             check_untracked_mods,
             clear_cache,
+            clone_mod_collection,
             clone_repo,
+            create_mod_collection,
             delete_manual_mod,
+            delete_mod_collection,
             exit_application,
+            export_lockfile,
+            export_settings,
+            extract_game_source,
             fetch_thumbnails_by_indices,
             find_steam_balatro,
             force_remove_mod,
             get_balatro_path,
             get_dependents,
             get_detected_local_mods,
+            get_game_sources,
+            get_game_version,
+            get_incompatible_mods,
+            get_installations,
             get_installed_mods_from_db,
             get_last_fetched,
             get_latest_steamodded_release,
+            get_lovely_patch_report,
+            get_lovely_releases,
+            get_lovely_status,
+            get_mod_collections,
             get_mod_list,
+            get_mod_pins,
+            get_mod_update_status,
+            get_mod_versions,
             get_mods_folder,
+            get_operations,
             get_repo_path,
+            get_settings,
             get_steamodded_versions,
             get_talisman_versions,
+            import_lockfile,
+            import_settings,
             init_index,
+            install_lovely,
             install_mod,
+            install_mod_with_dependencies,
             install_steamodded_version,
             install_talisman_version,
             is_mod_enabled,
@@ -1939,6 +2279,9 @@ pub fn run() {
             mod_update_available,
             open_directory,
             path_exists,
+            pin_mod,
+            plan_mod_updates,
+            preview_lovely_patches,
             process_dropped_file,
             process_mod_archive,
             pull_repo,
@@ -1946,16 +2289,29 @@ pub fn run() {
             refresh_mods_folder,
             reindex_mods,
             remove_backup,
+            remove_installation,
             remove_installed_mod,
+            rename_installation,
+            rename_mod_collection,
+            repair_mods,
+            resolve_mod_dependencies,
             restore_from_backup,
+            rollback_mod,
+            save_mod_collection,
             save_mods_cache,
             save_versions_cache,
             set_background_state,
             set_balatro_path,
+            set_installation_mods_dir,
             set_security_warning_acknowledged,
+            set_settings,
+            switch_installation,
+            switch_mod_collection,
             toggle_mod_enabled,
             toggle_mod_enabled_by_path,
+            unpin_mod,
             update_last_fetched,
+            verify_mods,
             verify_path_exists,
         ])
         .run(tauri::generate_context!());